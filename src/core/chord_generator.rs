@@ -1,128 +1,140 @@
 pub mod chord_fingerings;
+pub mod chord_identifier;
 
-use crate::Note::{self, *};
+use crate::Instrument;
+use crate::Note;
 use crate::chord_generator::chord_fingerings::{Fingering, StringState};
 use crate::chord_generator::chord_fingerings::StringState::*;
 
 
-pub const STRINGS: usize = 6;
-const MAX_CHORD_SIZE: u8 = 4;
-
-
 pub fn get_fingerings(
-    tuning: &[Note; STRINGS],
-    notes: &Vec<Note>,
+    instrument: Instrument,
+    tuning: &[Note], // по ноте на каждую струну инструмента
+    notes: &[Note], // все ноты аккорда, первая - тоника
+    required: &[Note], // ноты, без которых аккорд не получится
+    bass: Option<Note>, // нота на самой низкой звучащей струне, по умолчанию тоника
     title: Option<String>
 ) -> Vec<Fingering> {
-    let fret = get_fretboard(tuning);
+    let fret = get_fretboard(tuning, instrument.get_frets());
+    let search = FingeringSearch {
+        fretboard: &fret,
+        required,
+        bass: bass.unwrap_or(notes[0]),
+        max_chord_size: instrument.get_max_stretch(),
+        right_bass: !instrument.is_reentrant()
+    };
     let mut fingerings: Vec<Fingering> = Vec::new();
-    // добавить ещё минимальные варианты, где все дублированые или сложные ноты выбрасываются
+
+    // минимальные варианты, где необязательные ноты выбрасываются
+    let mut note_sets = vec![notes];
+    if required.len() < notes.len() { note_sets.push(required) }
+
     for i in 0..12 {
-        if let Some(string_state) = generate_from_fret(&fret, notes, i, true, true) {
-            if let Some(fing) = Fingering::new(string_state, title.clone()) {
-                if fingerings.iter().all(|f| *f != fing) {
-                    fingerings.push(fing)
-                }
-            }
-        }
-        if let Some(string_state) = generate_from_fret(&fret, notes, i, true, false) {
-            if let Some(fing) = Fingering::new(string_state, title.clone()) {
-                if fingerings.iter().all(|f| *f != fing) {
-                    fingerings.push(fing)
+        for note_set in &note_sets {
+            for is_open in [true, false] {
+                if let Some(string_state) = search.generate_from_fret(note_set, i, is_open) {
+                    if let Some(mut fing) = Fingering::new(string_state, title.clone()) {
+                        if fingerings.iter().all(|f| f.get_strings() != fing.get_strings()) {
+                            fing.set_score(get_playability(&fing, &fret, notes, search.bass, search.right_bass));
+                            fingerings.push(fing)
+                        }
+                    }
                 }
             }
         }
     }
 
+    // самые удобные - первыми, при равенстве остаётся порядок по ладам
+    fingerings.sort_by_key(|f| std::cmp::Reverse(f.get_score()));
+
     return fingerings
 }
 
-pub fn get_fretboard(tuning: &[Note; STRINGS]) -> [[Note; 25]; STRINGS] {
-    let mut fretboard = [[A; 25]; STRINGS];
-    for (index, note) in tuning.iter().enumerate() {
-        let mut note = note.clone();
-        for n in &mut fretboard[index] {
-            *n = note;
+pub fn get_fretboard(tuning: &[Note], frets: u8) -> Vec<Vec<Note>> {
+    let mut fretboard = Vec::new();
+    for note in tuning {
+        let mut note = *note;
+        let mut string = Vec::new();
+        for _ in 0..=frets {
+            string.push(note);
             note.increase();
         }
+        fretboard.push(string);
     }
 
     return fretboard
 }
 
 
-fn generate_from_fret(
-    fretboard: &[[Note; 25]; STRINGS],
-    notes: &Vec<Note>, // first is keynote
-    from_fret: u8,
-    right_bass: bool,
-    is_open: bool
-) -> Option<[StringState; STRINGS]> {
-    let mut string_state = [Muted; STRINGS];
-    for (index, string) in fretboard.iter().enumerate() {
-        let mut fret_counter: u8 = 0;
-        for (fret_num, fret) in string.iter().enumerate() {
-            let fret_num = fret_num.try_into().unwrap();
-            if fret_num == 0 && notes.iter().any(|n| n == fret) {
-                string_state[index] = Open
-            }
+// Общее для перебора аппликатур со всех ладов
+struct FingeringSearch<'a> {
+    fretboard: &'a [Vec<Note>],
+    required: &'a [Note],
+    bass: Note,
+    max_chord_size: u8,
+    right_bass: bool // бас на самой низкой звучащей струне (не для укулеле)
+}
+
+impl FingeringSearch<'_> {
+    fn generate_from_fret(
+        &self,
+        notes: &[Note], // first is keynote
+        from_fret: u8,
+        is_open: bool
+    ) -> Option<Vec<StringState>> {
+        let FingeringSearch { fretboard, required, bass, max_chord_size, right_bass } = *self;
+        // бас может не входить в аккорд (например C/B)
+        let mut fret_notes = notes.to_vec();
+        if !fret_notes.contains(&bass) { fret_notes.push(bass) }
+
+        let mut string_state = vec![Muted; fretboard.len()];
+        for (index, string) in fretboard.iter().enumerate() {
+            let mut fret_counter: u8 = 0;
+            for (fret_num, fret) in string.iter().enumerate() {
+                let fret_num = fret_num.try_into().unwrap();
+                if fret_num == 0 && fret_notes.iter().any(|n| n == fret) {
+                    string_state[index] = Open
+                }
 
-            if fret_num < from_fret { continue }
-            if fret_counter < MAX_CHORD_SIZE { fret_counter += 1 } else { break }
-            if fret_num != 0 && notes.iter().any(|n| n == fret) {
-                if is_open {
-                    if string_state[index] == Muted { string_state[index] = FrettedOn(fret_num) }
-                } else { string_state[index] = FrettedOn(fret_num) }
-                break;
+                if fret_num < from_fret { continue }
+                if fret_counter < max_chord_size { fret_counter += 1 } else { break }
+                if fret_num != 0 && fret_notes.iter().any(|n| n == fret) {
+                    if is_open {
+                        if string_state[index] == Muted { string_state[index] = FrettedOn(fret_num) }
+                    } else { string_state[index] = FrettedOn(fret_num) }
+                    break;
+                }
             }
         }
-    }
-
-    // бас соответствующий тонике
-    if right_bass {
-        let bass = notes[0];
-
-        for (i, s) in string_state.iter_mut().rev().enumerate() {
-            if *s == Muted { continue }
 
-            let string_num = get_reversed_string_num(i);
+        // бас соответствующий тонике (или заданной ноте для аккордов вида C/G)
+        if right_bass {
+            for (string_num, s) in string_state.iter_mut().enumerate().rev() {
+                if *s == Muted { continue }
 
-            if let Some(note) = get_note_from_position(fretboard, *s, string_num) {
-                if note != bass { *s = Muted }
-                else { break }
+                if let Some(note) = get_note_from_position(fretboard, *s, string_num) {
+                    if note != bass { *s = Muted }
+                    else { break }
+                }
             }
         }
-    }
 
-    // все ноты глухие
-    if string_state.iter().all(|s| *s == Muted) { return None }
+        // все ноты глухие
+        if string_state.iter().all(|s| *s == Muted) { return None }
 
-    // присутствуют ли все ноты
-    if notes.len() < 5 {
-        if !notes.iter().all(|n|
-            string_state.iter().enumerate().any(|(i, s)|
-                get_note_from_position(fretboard, *s, i) == Some(*n)
-            )
-        ) { return None }
-    } else {
-        let mut important_notes = Vec::new();
-        important_notes.push(notes[0]);
-        important_notes.push(notes[1]);
-        important_notes.push( notes[notes.len() - 1] );
-        important_notes.push( notes[notes.len() - 2] );
-
-        if !important_notes.iter().all(|n|
+        // присутствуют ли все обязательные ноты
+        if !required.iter().all(|n|
             string_state.iter().enumerate().any(|(i, s)|
                 get_note_from_position(fretboard, *s, i) == Some(*n)
             )
         ) { return None }
-    }
 
-    return Some(string_state)
+        return Some(string_state)
+    }
 }
 
 fn get_note_from_position(
-    fretboard: &[[Note; 25]; STRINGS],
+    fretboard: &[Vec<Note>],
     position: StringState,
     string_num: usize
 ) -> Option<Note> {
@@ -133,10 +145,50 @@ fn get_note_from_position(
     }
 }
 
-fn get_reversed_string_num(i: usize) -> usize {
-    for (index, value) in (0..STRINGS).rev().enumerate() {
-        if i == value { return index }
+
+// Удобство аппликатуры от 0 до 100: штрафы за растяжку, количество пальцев,
+// баррэ, заглушенные струны внутри аккорда, бас не на той ноте и недостающие ноты
+fn get_playability(
+    fing: &Fingering,
+    fretboard: &[Vec<Note>],
+    notes: &[Note],
+    bass: Note,
+    check_bass: bool
+) -> u8 {
+    let strings = fing.get_strings();
+    let mut score: i32 = 100;
+
+    score -= (i32::from(fing.get_chord_size()) - 1).max(0) * 6;
+    score -= fing.get_fingers_count() as i32 * 4;
+    score -= fing.get_bars_count() as i32 * 8;
+    if fing.get_chord_size() != 0 {
+        // чем дальше от порожка, тем неудобнее, а открытые струны там ещё и звучат не в позиции
+        let position = (i32::from(fing.get_fret_num()) - 1).max(0);
+        score -= position + (position - 4).max(0) * 3;
+        if position >= 4 {
+            score -= strings.iter().filter(|s| **s == Open).count() as i32 * position;
+        }
     }
 
-    i
+    let sounding: Vec<usize> = (0..strings.len()).filter(|i| strings[*i] != Muted).collect();
+    if let (Some(first), Some(last)) = (sounding.first(), sounding.last()) {
+        let inner_muted = (*first..=*last).filter(|i| strings[*i] == Muted).count();
+        let outer_muted = strings.len() - (last - first + 1);
+        score -= inner_muted as i32 * 15;
+        score -= outer_muted as i32 * 3;
+
+        // самая низкая струна - последняя звучащая
+        if check_bass && get_note_from_position(fretboard, strings[*last], *last) != Some(bass) {
+            score -= 10
+        }
+    }
+
+    let missing = notes.iter()
+        .filter(|n| !strings.iter().enumerate().any(|(i, s)|
+            get_note_from_position(fretboard, *s, i) == Some(**n)
+        ))
+        .count();
+    score -= missing as i32 * 8;
+
+    return score.clamp(0, 100) as u8
 }
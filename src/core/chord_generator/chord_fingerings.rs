@@ -3,7 +3,6 @@ use serde::{Serialize, Deserialize};
 use crossterm::terminal::size;
 
 use crate::chord_generator::chord_fingerings::StringState::*;
-use crate::chord_generator::STRINGS;
 
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -11,9 +10,14 @@ pub struct Fingering {
     title: Option<String>,
     fret_num: u8,
     chord_size: u8,
-    strings: [StringState; STRINGS],
-    bars: Option<BTreeMap<u8, u8>> // лад - верхушка баррэ 
-}                                  // (баррэ начинается всегда с первой струны)
+    strings: Vec<StringState>, // от первой струны к последней
+    bars: Option<BTreeMap<u8, u8>>, // лад - верхушка баррэ 
+                                    // (баррэ начинается всегда с первой струны)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    score: Option<u8>, // удобство от 0 до 100, есть только у сгенерированных аппликатур
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingers: Option<Vec<Option<Finger>>> // пальцы, заданные пользователем, по струнам
+}
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 pub enum StringState {
@@ -22,8 +26,44 @@ pub enum StringState {
     FrettedOn(u8)
 }
 
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Finger {
+    Index,
+    Middle,
+    Ring,
+    Pinky,
+    Thumb
+}
+
+impl Finger {
+    pub fn new(text: &str) -> Option<Self> {
+        Some( match text {
+            "1" => Finger::Index,
+            "2" => Finger::Middle,
+            "3" => Finger::Ring,
+            "4" => Finger::Pinky,
+            "T" | "t" => Finger::Thumb,
+            _ => return None
+        } )
+    }
+
+    fn from_number(num: u8) -> Option<Self> {
+        Self::new(&num.to_string())
+    }
+
+    pub fn get_char(&self) -> char {
+        match self {
+            Finger::Index => '1',
+            Finger::Middle => '2',
+            Finger::Ring => '3',
+            Finger::Pinky => '4',
+            Finger::Thumb => 'T'
+        }
+    }
+}
+
 impl Fingering {
-    pub fn new(strings: [StringState; STRINGS], title: Option<String>) -> Option<Self> {
+    pub fn new(strings: Vec<StringState>, title: Option<String>) -> Option<Self> {
         let mut fret_num: u8 = 25;
         for s in &strings {
             if let FrettedOn(f) = s {
@@ -70,29 +110,132 @@ impl Fingering {
             if let Some(h) = bar_head && fretted_counter > 1 { bars.insert(fret, h); }
         }
 
+        let fing = Self {
+            title,
+            fret_num,
+            chord_size,
+            strings,
+            bars: if bars.is_empty() { None } else { Some(bars) },
+            score: None,
+            fingers: None
+        };
+
+        if fing.get_fingers_count() > 4 { return None }
+        Some(fing)
+    }
+
+    // Сколько пальцев нужно: каждое баррэ - один палец
+    pub fn get_fingers_count(&self) -> usize {
         let mut fretted = 0;
-        for fret in 0..chord_size {
-            let fret = fret + fret_num;
-            if let Some(_) = bars.get(&fret) { continue }
-            for s in &strings {
+        for fret in 0..self.chord_size {
+            let fret = fret + self.fret_num;
+            if self.bars.as_ref().is_some_and(|b| b.contains_key(&fret)) { continue }
+            for s in &self.strings {
                 if let FrettedOn(f) = s && *f == fret {
                     fretted += 1;
                 }
             }
         }
 
-        if fretted + bars.len() > 4 { return None }
-        Some( Self {
-            title,
-            fret_num,
-            chord_size,
-            strings,
-            bars: if bars.is_empty() { None } else { Some(bars) }
-        } )
+        return fretted + self.get_bars_count()
+    }
+
+    // лад - верхушка баррэ
+    pub fn get_bars(&self) -> Option<&BTreeMap<u8, u8>> {
+        self.bars.as_ref()
+    }
+
+    pub fn get_bars_count(&self) -> usize {
+        self.bars.as_ref().map(|b| b.len()).unwrap_or(0)
+    }
+
+    pub fn get_fret_num(&self) -> u8 {
+        self.fret_num
+    }
+
+    // Сколько ладов охватывает аппликатура
+    pub fn get_chord_size(&self) -> u8 {
+        self.chord_size
+    }
+
+    pub fn get_score(&self) -> Option<u8> {
+        self.score
+    }
+
+    pub fn set_score(&mut self, score: u8) {
+        self.score = Some(score);
+    }
+
+    pub fn set_title(&mut self, title: Option<String>) {
+        self.title = title;
+    }
+
+    // Пальцы по струнам: заданные пользователем или расставленные автоматически
+    pub fn get_fingers(&self) -> Vec<Option<Finger>> {
+        if let Some(fingers) = &self.fingers { fingers.clone() }
+        else { self.assign_fingers() }
+    }
+
+    // Палец должен быть у каждой зажатой струны и только у неё
+    pub fn set_fingers(&mut self, fingers: Vec<Option<Finger>>) -> bool {
+        if fingers.len() != self.strings.len() { return false }
+        if !self.strings.iter().zip(&fingers).all(|(s, f)|
+            matches!(s, FrettedOn(_)) == f.is_some()
+        ) { return false }
+
+        self.fingers = Some(fingers);
+        return true
+    }
+
+    // Баррэ и отдельные нажатия по порядку ладов, на одном ладу - сначала басовые струны.
+    // Номер пальца не меньше расстояния от первого лада аппликатуры
+    fn assign_fingers(&self) -> Vec<Option<Finger>> {
+        let mut fingers = vec![None; self.strings.len()];
+        let bar_on = |fret: u8| self.bars.as_ref().and_then(|b| b.get(&fret)).copied();
+
+        // (лад, струна, баррэ ли)
+        let mut presses: Vec<(u8, usize, bool)> = Vec::new();
+        for (i, s) in self.strings.iter().enumerate() {
+            if let FrettedOn(f) = s && bar_on(*f).is_none_or(|head| i > head.into()) {
+                presses.push( (*f, i, false) )
+            }
+        }
+        if let Some(bars) = &self.bars {
+            for (fret, head) in bars {
+                presses.push( (*fret, (*head).into(), true) )
+            }
+        }
+        presses.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        let mut last_num = 0;
+        for (fret, string, is_bar) in presses {
+            let num = (last_num + 1).max(fret - self.fret_num + 1).min(4);
+            if num == last_num { break } // пальцев не хватило
+            last_num = num;
+
+            let finger = Finger::from_number(num);
+            if is_bar {
+                for (i, s) in self.strings.iter().enumerate().take(string + 1) {
+                    if *s == FrettedOn(fret) { fingers[i] = finger }
+                }
+            } else {
+                fingers[string] = finger;
+            }
+        }
+
+        return fingers
     }
 
     pub fn get_text(&self) -> String {
         let mut text = String::new();
+        let strings_count = self.strings.len();
+        let grid_width = self.get_grid_width();
+        let fingers = self.get_fingers();
+        let get_finger = |string_num: usize| fingers.get(string_num)
+            .copied()
+            .flatten()
+            .map(|f| f.get_char())
+            .unwrap_or('o');
 
         let strings: Vec<&StringState> = self.strings.iter().rev().collect();
         // нулевой проход для отрисовки вначале открытых и заглушенных струн
@@ -104,33 +247,35 @@ impl Fingering {
             })
         }
         text.push_str( &format!("  \n{}   ",
-                if self.fret_num - 1 == 0 { "=".repeat(11) }
-                else { "-".repeat(11) }));
+                if self.fret_num == 1 || self.chord_size == 0 { "=".repeat(grid_width) }
+                else { "-".repeat(grid_width) }));
         text.push('\n');
 
         let mut fret_counter = self.fret_num;
         for i in 0..self.chord_size {
             let current_fret = i + self.fret_num;
-            text.push_str( &"| ".repeat(6) );
+            text.push_str( &"| ".repeat(strings_count) );
             text.push_str("  ");
 
             text.push('\n');
             let is_bar: bool;
             if let Some(bars) = &self.bars {
                 if let Some(bar_head) = bars.get(&current_fret) {
+                    // номер пальца на верхушке баррэ
                     let bar: usize = (bar_head + 1).into();
-                    text.push_str( &"| ".repeat(STRINGS - bar) );
-                    text.push_str( &">>".repeat(bar - 1) );
-                    text.push_str("> ");
+                    text.push_str( &"| ".repeat(strings_count - bar) );
+                    text.push(get_finger(bar - 1));
+                    text.push_str( &">".repeat(bar * 2 - 2) );
+                    text.push(' ');
                     is_bar = true;
                 } else { is_bar = false }
             } else { is_bar = false }
 
             if !is_bar {
-                for s in &strings {
+                for (i, s) in strings.iter().enumerate() {
                     text.push_str(&
                         if let FrettedOn(f) = s && *f == (current_fret) {
-                            "o ".to_string()
+                            format!("{} ", get_finger(strings_count - 1 - i))
                         } else { "| ".to_string() }
                     )
                 }
@@ -140,23 +285,37 @@ impl Fingering {
             if fret_counter < 10 { text.push(' ') }
             text.push('\n');
 
-            text.push_str( &"| ".repeat(6) );
+            text.push_str( &"| ".repeat(strings_count) );
             text.push_str("  \n");
 
-            text.push_str( &"-".repeat(11) );
+            text.push_str( &"-".repeat(grid_width) );
             text.push_str("   ");
             if (i + 1) < self.chord_size { text.push('\n') }
 
             fret_counter += 1;
         }
 
-        // длина каждой строки 14 символов
+        // длина каждой строки - get_width()
         return text
     }
     
     pub fn get_title(&self) -> Option<String> {
         self.title.clone()
     }
+
+    pub fn get_strings(&self) -> &Vec<StringState> {
+        &self.strings
+    }
+
+    // Ширина сетки ладов: по символу на струну и пробелы между ними
+    fn get_grid_width(&self) -> usize {
+        self.strings.len() * 2 - 1
+    }
+
+    // Ширина строки в get_text (сетка + номер лада)
+    pub fn get_width(&self) -> usize {
+        self.get_grid_width() + 3
+    }
 }
 
 pub fn sum_text_in_fingerings(fingerings: &Vec<Fingering>, width: Option<usize>) -> Option<String> {
@@ -166,7 +325,7 @@ pub fn sum_text_in_fingerings(fingerings: &Vec<Fingering>, width: Option<usize>)
     )};
 
     let indent: usize = 5;
-    let line_width: usize = 14;
+    let line_width: usize = fingerings.iter().map(|f| f.get_width()).max().unwrap_or(14);
     let fingerings_in_line: usize = width / (line_width + indent);
 
     let mut s = String::new();
@@ -183,10 +342,10 @@ pub fn sum_text_in_fingerings(fingerings: &Vec<Fingering>, width: Option<usize>)
         }
 
         let title = if let Some(title) = &f.title {
-            if title.len() > 4 {
-                title.to_string() + &" ".repeat(line_width - title.len())
+            if title.len() > 4 || title.len() >= line_width {
+                title.to_string() + &" ".repeat(line_width.saturating_sub(title.len()))
             } else {
-                let left_part_width = ( line_width - title.len() ) / 2 - title.len();
+                let left_part_width = (( line_width - title.len() ) / 2).saturating_sub(title.len());
                 let right_part_width = line_width - left_part_width - title.len();
             
                 " ".repeat(left_part_width) + title + &" ".repeat(right_part_width)
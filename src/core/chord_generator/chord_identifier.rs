@@ -0,0 +1,79 @@
+// Обратный поиск: название аккорда по аппликатуре
+use crate::{Note, Instrument};
+use crate::chord_generator::get_fretboard;
+use crate::chord_generator::chord_fingerings::StringState::{self, *};
+use crate::song::chord::Chord;
+
+
+// От простых к сложным, чем раньше в списке, тем проще название
+const SUFFIXES: [&str; 32] = [
+    "", "m", "5", "7", "m7", "maj7", "sus4", "sus2", "6", "m6",
+    "dim", "aug", "7sus4", "add9", "madd9", "9", "m9", "maj9", "6/9", "dim7",
+    "m7b5", "mmaj7", "7b5", "7#5", "7b9", "7#9", "11", "m11", "13", "maj13",
+    "7#11", "m13"
+];
+
+
+// Все подходящие названия, самые простые - первыми.
+// strings - от первой струны к последней, как в Fingering
+pub fn identify(instrument: Instrument, tuning: &[Note], strings: &[StringState]) -> Vec<Chord> {
+    let frets = strings.iter()
+        .map(|s| if let FrettedOn(f) = s { *f } else { 0 })
+        .max()
+        .unwrap_or(0);
+    let fretboard = get_fretboard(tuning, frets);
+
+    let sounding: Vec<Note> = strings.iter()
+        .enumerate()
+        .filter_map(|(i, s)| match s {
+            Muted => None,
+            Open => Some(fretboard[i][0]),
+            FrettedOn(f) => Some(fretboard[i][usize::from(*f)])
+        })
+        .collect();
+    // самая низкая звучащая струна - последняя, у укулеле бас не определить
+    let Some(lowest) = sounding.last().copied() else { return Vec::new() };
+    let bass = if instrument.is_reentrant() { None } else { Some(lowest) };
+
+    let mut notes: Vec<Note> = Vec::new();
+    for n in &sounding {
+        if !notes.contains(n) { notes.push(*n) }
+    }
+
+    let mut found: Vec<(usize, Chord)> = Vec::new();
+    for root in &notes {
+        for (rank, suffix) in SUFFIXES.iter().enumerate() {
+            let Some(chord) = Chord::new(&format!("{}{}", root.get_text(), suffix)) else { continue };
+            let chord_notes = chord.get_notes();
+            let required: Vec<Note> = chord.get_tones().iter()
+                .filter(|t| !t.optional)
+                .map(|t| root.transpose(t.interval.into()))
+                .collect();
+
+            // бас не из аккорда допустим только как слэш (C/B), и то не у квинтаккорда
+            let bass_in_chord = bass.is_none_or(|b| chord_notes.contains(&b));
+            let is_match = required.iter().all(|n| notes.contains(n)) &&
+                notes.iter().all(|n| chord_notes.contains(n) || Some(*n) == bass) &&
+                (bass_in_chord || chord_notes.len() >= 3);
+            if !is_match { continue }
+
+            let missing = chord_notes.iter().filter(|n| !notes.contains(n)).count();
+            let mut score = rank * 2 + missing;
+            let text = match bass {
+                Some(bass) if bass != *root => {
+                    // обращение (бас из аккорда) проще, чем чужой бас
+                    score += if bass_in_chord { 4 } else { 10 };
+                    format!("{}/{}", chord.text, bass.get_text())
+                },
+                _ => chord.text.clone()
+            };
+
+            if let Some(chord) = Chord::new(&text) && found.iter().all(|(_, c)| c.text != chord.text) {
+                found.push( (score, chord) )
+            }
+        }
+    }
+
+    found.sort_by_key(|(score, _)| *score);
+    return found.into_iter().map(|(_, chord)| chord).collect()
+}
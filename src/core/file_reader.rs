@@ -1,45 +1,91 @@
 pub mod txt_reader;
 pub mod chordpro_reader;
 pub mod sbp_reader; // SongbookPro
+pub mod opensong_reader;
+pub mod openlyrics_reader;
 
 
 
 use std::path::Path;
 use std::io::Read;
 use std::fs;
-use crate::{Song, Metadata};
+use crate::{Song, Metadata, Instrument, Fingering, Key};
 use anyhow::Result;
 use zip::ZipArchive;
+use uuid::Uuid;
+use sbp_reader::SbpBackup;
+
+
+// Песня из ChordPro, аппликатуры из её {define} и {define}, которые не удалось прочитать
+pub type ChordProSong = (Song, Vec<(Instrument, Fingering)>, Vec<String>);
 
 
 impl Song {
     pub fn from_txt(file_path: &Path, title: &str, artist: &str) -> Result<Self> {
-        let (blocks, chord_list) = txt_reader::read_from_txt(
-            &fs::read_to_string(file_path)?
-        );
-        let metadata = Metadata {
+        Self::from_txt_with_key_guess(file_path, title, artist).map(|(song, _)| song)
+    }
+
+    // Тональность, угаданная по аккордам, и уверенность в ней; None - если она была в тексте
+    pub fn from_txt_with_key_guess(file_path: &Path, title: &str, artist: &str) -> Result<(Self, Option<(Key, f32)>)> {
+        let mut metadata = Metadata {
             artist: artist.to_string(),
             title: title.to_string(),
             key: None,
             capo: None,
-            autoscroll_speed: None
+            tempo: None,
+            time: None,
+            key_shift: None,
+            copyright: None,
+            autoscroll_speed: None,
+            spelling: None,
+            tuning: None,
+            album: None,
+            year: None,
+            composer: None,
+            lyricist: None,
+            ccli: None,
+            language: None,
+            url: None,
+            tags: Vec::new()
         };
-        let mut song = Self { blocks, chord_list, metadata, notes: None };
-        song.detect_key();
+        let (blocks, chord_list) = txt_reader::read_from_txt(
+            &fs::read_to_string(file_path)?,
+            &mut metadata
+        );
+        let mut song = Self { id: Uuid::new_v4(), blocks, chord_list, metadata, notes: None };
+        let guess = if song.metadata.key.is_none() { song.detect_key() } else { None };
+        if let Some( (key, _) ) = guess { song.metadata.key = Some(key) }
 
 
-        Ok(song)
+        Ok( (song, guess) )
     }
 
-    pub fn from_chordpro(file_path: &Path) -> Result<Self> {
-        let (metadata, blocks, chord_list) = chordpro_reader::read_from_chordpro(
-            &fs::read_to_string(file_path)?
-        );
-        let metadata = metadata.expect("Cannot read metadata(title or artist)!");
-        Ok( Self { blocks, chord_list, metadata, notes: None } )
+    // Файл может содержать несколько песен ({new_song}), каждая читается отдельно
+    pub fn from_chordpro(file_path: &Path) -> Result<Vec<Result<ChordProSong>>> {
+        let text = fs::read_to_string(file_path)?;
+        Ok(
+            chordpro_reader::split_songs(&text)
+                .into_iter()
+                .map(chordpro_reader::read_from_chordpro)
+                .collect()
+        )
+    }
+
+    pub fn from_opensong(file_path: &Path) -> Result<Self> {
+        opensong_reader::read_from_opensong(&fs::read_to_string(file_path)?)
+    }
+
+    pub fn from_openlyrics(file_path: &Path) -> Result<Self> {
+        openlyrics_reader::read_from_openlyrics(&fs::read_to_string(file_path)?)
     }
 
     pub fn from_sbp(file_path: &Path) -> Result<Vec<Self>> {
+        let backup = Self::read_sbp_backup(file_path)?;
+        Ok( backup.songs.into_iter().map(|(song, _folder)| song).collect() )
+    }
+
+    // Вместе с папками и сетами
+    pub fn read_sbp_backup(file_path: &Path) -> Result<SbpBackup> {
         let file = fs::File::open(file_path)?;
         let mut archive = ZipArchive::new(file)?;
 
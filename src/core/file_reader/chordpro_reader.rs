@@ -1,165 +1,506 @@
+use std::io::{Error, ErrorKind};
+use std::mem;
+
+use anyhow::Result;
+use uuid::Uuid;
+
 use crate::song::{
+    Song,
     Metadata,
     block::{Block, Line},
-    row::{Row, ChordPosition},
+    row::{Row, Beat, ChordPosition},
     chord::Chord
 };
+use crate::file_writer::chordpro_writer::{
+    SPELLING_DIRECTIVE,
+    TUNING_DIRECTIVE,
+    AUTOSCROLL_SPEED_DIRECTIVE,
+    KEY_SHIFT_DIRECTIVE,
+    SONG_NOTE_DIRECTIVE,
+    UPBEAT_DIRECTIVE,
+    RHYTHM_DIRECTIVE
+};
+use crate::file_reader::ChordProSong;
+use crate::{Key, Spelling, Tuning, Instrument, Fingering, Finger, StringState};
+
+
+// {name-selector: value}
+struct Directive<'a> {
+    name: String, // полное имя: sov -> start_of_verse
+    selector: Option<&'a str>,
+    value: &'a str
+}
+
+// Ритм и затакт из директив songbook, относятся к следующей строке
+#[derive(Default)]
+struct Pending {
+    rhythm: Option<Vec<Beat>>,
+    upbeats: Vec<Chord>
+}
+
+// Секции, которые читаются не как строки песни
+enum RawSection {
+    Tab(String),
+    Text(String),
+    Grid
+}
 
 
-pub fn read_from_chordpro(text: &str) -> (Option<Metadata>, Vec<Block>, Vec<Chord>) {
+// Песни в одном файле разделяются {new_song} или {ns}
+pub fn split_songs(text: &str) -> Vec<&str> {
+    let mut songs = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if let Some(d) = parse_directive(line) && d.name == "new_song" {
+            songs.push(&text[start..offset]);
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    songs.push(&text[start..]);
+
+    // Пустые куски - например, {new_song} в самом начале
+    songs.retain(|song| !song.trim().is_empty());
+    return songs
+}
+
+
+pub fn read_from_chordpro(text: &str) -> Result<ChordProSong> {
     let mut blocks: Vec<Block> = Vec::new();
     let mut chord_list: Vec<Chord> = Vec::new();
-    
-    
-    let block_starts = [
-        "{start_of_verse",
-        "{sov",
-        
-        "{start_of_chorus",
-        "{soc",
-        
-        "{start_of_bridge",
-        "{sob"
-    ];
-    
-    let block_ends = [
-        "{end_of_verse",
-        "{eov",
-        
-        "{end_of_chorus",
-        "{eoc",
-        
-        "{end_of_bridge",
-        "{eob"
-    ];
-    
+    let mut definitions: Vec<(Instrument, Fingering)> = Vec::new();
+    let mut warnings: Vec<String> = Vec::new();
     
     let mut title = String::new();
     let mut artist = String::new();
+    let mut subtitle = String::new();
     let mut key_text = String::new();
+    let mut capo: Option<u8> = None;
+    let mut tempo: Option<u16> = None;
+    let mut time: Option<String> = None;
+    let mut key_shift: Option<i32> = None;
+    let mut copyright: Option<String> = None;
+    let mut spelling: Option<Spelling> = None;
+    let mut tuning: Option<Tuning> = None;
+    let mut autoscroll_speed: Option<u64> = None;
+    let mut album: Option<String> = None;
+    let mut year: Option<u16> = None;
+    let mut composer: Option<String> = None;
+    let mut lyricist: Option<String> = None;
+    let mut ccli: Option<String> = None;
+    let mut language: Option<String> = None;
+    let mut url: Option<String> = None;
+    let mut tags: Vec<String> = Vec::new();
+    let mut song_notes = String::new();
     
     let mut bnote = String::new();
-    let mut is_in_block = false;
+    let mut environment: Option<String> = None; // открытый блок: verse, chorus, intro...
     let mut block_lines: Vec<Line> = Vec::new();
     let mut block_title = String::new();
+    let mut last_chorus: Option<usize> = None;
+    let mut pending = Pending::default();
 
-    let mut tab_buf = String::new();
-    let mut is_in_tab = false;
+    let mut raw: Option<RawSection> = None;
+    let mut skipped: Option<String> = None; // секция для другого инструмента
 
     for line in text.lines() {
-        if block_ends.iter().any(|end| line.starts_with(end)) {
-            is_in_block = false;
-            blocks.push( Block {
-                title: if block_title.is_empty() { None } else { Some(block_title) },
-                lines: block_lines,
-                notes: None
-            } );
-            
-            block_title = String::new();
-            block_lines = Vec::new();
-        } else if is_in_block {
-            read_line(line, &mut block_lines, &mut chord_list);
-        } else if block_starts.iter().any(|start| line.starts_with(start)) {
-            is_in_block = true;
-            if let Some(start_index) = line.find(":") {
-                let line_after_start = &line[start_index + 1..];
-                if let Some(end_index) = line_after_start.find("}") {
-                    block_title = line_after_start[..end_index].trim().to_string()
-                }
+        let directive = parse_directive(line);
+
+        if let Some(env) = &skipped {
+            if let Some(d) = &directive && d.name.strip_prefix("end_of_") == Some(env.as_str()) {
+                skipped = None;
             }
-        
-        
-        } else if line.starts_with("{sot}") || line.starts_with("{start_of_tab}") {
-            is_in_tab = true;
-        } else if line.starts_with("{eot}") || line.starts_with("{end_of_tab}") {
-            is_in_tab = false;
-            block_lines.push( Line::Tab(tab_buf) );
-            tab_buf = String::new();
-        } else if is_in_tab {
-            if !tab_buf.is_empty() {
-                tab_buf.push('\n');
+            continue
+        }
+
+        if let Some(section) = raw.as_mut() {
+            let end = match section {
+                RawSection::Tab(_) => "end_of_tab",
+                RawSection::Text(_) => "end_of_textblock",
+                RawSection::Grid => "end_of_grid"
+            };
+            if directive.as_ref().is_some_and(|d| d.name == end) {
+                match raw.take() {
+                    Some(RawSection::Tab(buf)) => block_lines.push( Line::Tab(buf) ),
+                    Some(RawSection::Text(buf)) => block_lines.push( Line::PlainText(buf) ),
+                    _ => if environment.as_deref() == Some("grid") {
+                        environment = None;
+                        push_block(&mut blocks, &mut block_title, &mut block_lines, &mut bnote);
+                    }
+                }
+            } else {
+                match section {
+                    RawSection::Tab(buf) | RawSection::Text(buf) => {
+                        if !buf.is_empty() { buf.push('\n') }
+                        buf.push_str(line);
+                    },
+                    RawSection::Grid => read_grid_line(line, &mut block_lines, &mut chord_list)
+                }
             }
-            tab_buf.push_str(line);
+            continue
+        }
 
 
-        } else if line.starts_with("{title:") || line.starts_with("{t:") {
-            if let Some(end_index) = line.find("}") {
-                title = if line.starts_with("{t:") {
-                    line[3..end_index].trim()
-                } else {
-                    line[7..end_index].trim()
-                }.to_string();
-            }
-        
-        } else if line.starts_with("{artist:") {
-            if let Some(end_index) = line.find("}") {
-                artist = line[8..end_index].trim().to_string()
+        if let Some(d) = directive {
+            // Аппликатуры нужны для любого инструмента
+            if d.name == "define" {
+                match read_definition(d.value, d.selector) {
+                    Some(definition) => definitions.push(definition),
+                    None => warnings.push( format!("Cannot read the chord definition: {}", d.value) )
+                }
+                continue
             }
-        } else if line.starts_with("{subtitle:") {
-            if let Some(end_index) = line.find("}") {
-                artist = line[10..end_index].trim().to_string()
+            if !is_selected(d.selector) {
+                if let Some(env) = d.name.strip_prefix("start_of_") {
+                    skipped = Some(env.to_string());
+                }
+                continue
             }
-        } else if line.starts_with("{st:") {
-            if let Some(end_index) = line.find("}") {
-                artist = line[4..end_index].trim().to_string()
+
+            let text = if d.value.is_empty() { None } else { Some(d.value.to_string()) };
+            match d.name.as_str() {
+                "album" => album = text,
+                "year" => year = d.value.parse::<u16>().ok(),
+                "composer" => composer = text,
+                "lyricist" => lyricist = text,
+                "ccli" => ccli = text,
+                "language" => language = text,
+                "url" | "source" => url = text,
+                "tag" | "tags" => for tag in Metadata::parse_tags(d.value) {
+                    if !tags.contains(&tag) { tags.push(tag) }
+                },
+                _ => {}
             }
-        
-        } else if line.starts_with("{key:") {
-            if let Some(end_index) = line.find("}") {
-                key_text = line[5..end_index].trim().to_string()
+
+            match d.name.as_str() {
+                "title" => title = d.value.to_string(),
+                "artist" => artist = d.value.to_string(),
+                "subtitle" => subtitle = d.value.to_string(),
+                "key" => key_text = d.value.to_string(),
+                "capo" => capo = d.value.parse::<u8>().ok(),
+                "tempo" => tempo = d.value.split_whitespace()
+                    .next()
+                    .and_then(|t| t.parse::<u16>().ok()),
+                "time" => time = if d.value.is_empty() { None } else { Some(d.value.to_string()) },
+                "copyright" => copyright = if d.value.is_empty() { None } else { Some(d.value.to_string()) },
+                KEY_SHIFT_DIRECTIVE => key_shift = d.value.parse::<i32>().ok().filter(|k| *k != 0),
+                SPELLING_DIRECTIVE => spelling = Spelling::new(d.value),
+                TUNING_DIRECTIVE => tuning = Tuning::new(d.value),
+                AUTOSCROLL_SPEED_DIRECTIVE => autoscroll_speed = d.value.parse::<u64>().ok(),
+                SONG_NOTE_DIRECTIVE => {
+                    if !song_notes.is_empty() { song_notes.push('\n') }
+                    song_notes.push_str(d.value);
+                },
+                RHYTHM_DIRECTIVE => pending.rhythm = Some( rhythm_from_directive(d.value) ),
+                UPBEAT_DIRECTIVE => for text in d.value.split_whitespace() {
+                    let chord = Chord::from_text(text);
+                    add_to_chord_list(&chord, &mut chord_list);
+                    pending.upbeats.push(chord);
+                },
+
+                "comment" | "comment_italic" | "comment_box" | "highlight" => {
+                    if !bnote.is_empty() { bnote.push('\n') }
+                    bnote.push_str(d.value);
+                },
+
+                // Повтор последнего припева
+                "chorus" => if let Some(i) = last_chorus {
+                    let mut chorus = blocks[i].clone();
+                    if environment.is_some() {
+                        block_lines.append(&mut chorus.lines);
+                    } else {
+                        if !block_lines.is_empty() {
+                            push_block(&mut blocks, &mut block_title, &mut block_lines, &mut bnote);
+                        }
+                        if !d.value.is_empty() { chorus.title = Some(d.value.to_string()) }
+                        chorus.notes = if bnote.is_empty() { None } else { Some(mem::take(&mut bnote)) };
+                        blocks.push(chorus);
+                    }
+                },
+
+                "start_of_tab" => raw = Some( RawSection::Tab(String::new()) ),
+                "start_of_textblock" => raw = Some( RawSection::Text(String::new()) ),
+                "start_of_grid" => {
+                    // Сетка вне блока - отдельный блок
+                    if environment.is_none() {
+                        if !block_lines.is_empty() {
+                            push_block(&mut blocks, &mut block_title, &mut block_lines, &mut bnote);
+                        }
+                        environment = Some("grid".to_string());
+                        block_title = get_label(d.value).unwrap_or("Grid").to_string();
+                    }
+                    raw = Some(RawSection::Grid);
+                },
+                name if name.starts_with("start_of_") => {
+                    // Всё, что было до начала блока - отдельный блок
+                    if !block_lines.is_empty() {
+                        push_block(&mut blocks, &mut block_title, &mut block_lines, &mut bnote);
+                    }
+
+                    let env = &name["start_of_".len()..];
+                    block_title = match get_label(d.value) {
+                        Some(label) => label.to_string(),
+                        None if matches!(env, "verse" | "chorus" | "bridge") => String::new(),
+                        None => get_capitalized(env)
+                    };
+                    environment = Some(env.to_string());
+                },
+                name if name.starts_with("end_of_") => if let Some(env) = environment.take() {
+                    push_block(&mut blocks, &mut block_title, &mut block_lines, &mut bnote);
+                    if env == "chorus" { last_chorus = Some(blocks.len() - 1) }
+                },
+
+                _ => {} // Остальные директивы не нужны
             }
+            continue
+        }
 
 
-        } else if line.starts_with("{comment:") || line.starts_with("{c:") {
-            if let Some(end_index) = line.find("}") {
-                bnote = if line.starts_with("{c:") {
-                    line[3..end_index].trim()
-                } else {
-                    line[9..end_index].trim()
-                }.to_string();
+        if line.starts_with('#') { // Комментарий в файле
+            continue
+        }
+        // \# и \{ в начале строки - текст, а не комментарий или директива
+        let line = match line.strip_prefix('\\') {
+            Some(l) if l.starts_with(['#', '{']) => l,
+            _ => line
+        };
+        if environment.is_some() {
+            if line.is_empty() { block_lines.push(Line::EmptyLine) }
+            else { read_line(line, &mut block_lines, &mut chord_list, &mut pending) }
+        } else if line.trim().is_empty() {
+            if !block_lines.is_empty() {
+                push_block(&mut blocks, &mut block_title, &mut block_lines, &mut bnote);
             }
-        
-        
-        } else if line.is_empty() && !block_lines.is_empty() {
-            blocks.push( Block {
-                title: if block_title.is_empty() { None } else { Some(block_title) },
-                lines: block_lines,
-                notes: if bnote.is_empty() { None } else { Some(bnote) }
-            } );
-            
-            bnote = String::new();
-            block_title = String::new();
-            block_lines = Vec::new();
         } else {
-            read_line(line, &mut block_lines, &mut chord_list);
+            read_line(line, &mut block_lines, &mut chord_list, &mut pending);
         }
     }
 
     // Последний block
     if !block_lines.is_empty() {
-        blocks.push( Block {
-            title: if block_title.is_empty() { None } else { Some(block_title) },
-            lines: block_lines,
-            notes: None
-        } );
+        push_block(&mut blocks, &mut block_title, &mut block_lines, &mut bnote);
     }
 
+
+    if title.is_empty() {
+        return Err( Error::new(ErrorKind::InvalidData, "There's no title in the song ({title: ...})!").into() )
+    }
+    if artist.is_empty() { artist = subtitle }
+    if artist.is_empty() {
+        return Err( Error::new(ErrorKind::InvalidData, "There's no artist in the song ({artist: ...} or {subtitle: ...})!").into() )
+    }
+
+    let metadata = Metadata {
+        title,
+        artist,
+        key: Key::new(&key_text),
+        capo,
+        tempo,
+        time,
+        key_shift,
+        copyright,
+        autoscroll_speed,
+        spelling,
+        tuning,
+        album,
+        year,
+        composer,
+        lyricist,
+        ccli,
+        language,
+        url,
+        tags
+    };
+    let notes = if song_notes.is_empty() { None } else { Some(song_notes) };
     
-    return ( 
-        if !title.is_empty() && !artist.is_empty() { Some( Metadata {
-            title,
-            artist,
-            key: crate::Key::new(&key_text),
-            capo: None,
-            autoscroll_speed: None
-        } ) } else { None },
-        blocks,
-        chord_list
-    )
+    Ok( (Song { id: Uuid::new_v4(), metadata, chord_list, blocks, notes }, definitions, warnings) )
+}
+
+
+fn push_block(blocks: &mut Vec<Block>, title: &mut String, lines: &mut Vec<Line>, notes: &mut String) {
+    blocks.push( Block {
+        title: if title.is_empty() { None } else { Some(mem::take(title)) },
+        lines: mem::take(lines),
+        notes: if notes.is_empty() { None } else { Some(mem::take(notes)) }
+    } );
 }
 
+fn add_to_chord_list(chord: &Chord, chord_list: &mut Vec<Chord>) {
+    if chord.is_valid() && chord_list.iter().all(|c| c != chord) {
+        chord_list.push(chord.clone())
+    }
+}
 
-fn read_line(text: &str, lines: &mut Vec<Line>, chord_list: &mut Vec<Chord>) {
+
+fn parse_directive(line: &str) -> Option<Directive<'_>> {
+    let inner = line.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let (head, value) = match inner.find(|c: char| c == ':' || c.is_whitespace()) {
+        Some(i) => (&inner[..i], inner[i + 1..].trim()),
+        None => (inner, "")
+    };
+    let (name, selector) = match head.split_once('-') {
+        Some( (name, selector) ) => (name, Some(selector)),
+        None => (head, None)
+    };
+
+    // {meta: album Nevermind} - то же, что {album: Nevermind}
+    if name == "meta" {
+        let (name, value) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+        return Some( Directive { name: name.to_lowercase(), selector, value: value.trim() } )
+    }
+
+    let name = match name {
+        "t" => "title",
+        "st" => "subtitle",
+        "c" => "comment",
+        "ci" => "comment_italic",
+        "cb" => "comment_box",
+        "sov" => "start_of_verse",
+        "eov" => "end_of_verse",
+        "soc" => "start_of_chorus",
+        "eoc" => "end_of_chorus",
+        "sob" => "start_of_bridge",
+        "eob" => "end_of_bridge",
+        "sot" => "start_of_tab",
+        "eot" => "end_of_tab",
+        "sog" => "start_of_grid",
+        "eog" => "end_of_grid",
+        "ns" => "new_song",
+        n => n
+    };
+
+    return Some( Directive { name: name.to_string(), selector, value } )
+}
+
+// Условные директивы ({title-guitar: ...}) читаются для гитары - инструмента по умолчанию
+fn is_selected(selector: Option<&str>) -> bool {
+    let Some(selector) = selector else { return true };
+    if let Some(selector) = selector.strip_suffix('!') {
+        return Instrument::new(selector) != Some(Instrument::Guitar)
+    }
+
+    return Instrument::new(selector) == Some(Instrument::Guitar)
+}
+
+// "Verse 1", label="Verse 1" или форма сетки: 1+4x2+4
+fn get_label(value: &str) -> Option<&str> {
+    if let Some(start) = value.find("label=\"") {
+        let label = &value[start + "label=\"".len()..];
+        return label.find('"').map(|end| &label[..end])
+    }
+    if value.is_empty() || value.contains('=')
+        || value.chars().all(|c| c.is_ascii_digit() || c == 'x' || c == '+') {
+        return None
+    }
+
+    return Some(value)
+}
+
+fn get_capitalized(env: &str) -> String {
+    let text = env.replace('_', " ");
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => text
+    }
+}
+
+
+// {define: Am base-fret 1 frets x 0 2 2 1 0 fingers 0 0 2 3 1 0}, струны - от самой низкой
+fn read_definition(text: &str, selector: Option<&str>) -> Option<(Instrument, Fingering)> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let (name, words) = words.split_first()?;
+
+    let mut base_fret: u8 = 1;
+    let mut frets: Vec<&str> = Vec::new();
+    let mut fingers: Vec<&str> = Vec::new();
+    if words.contains(&"frets") {
+        let mut current = "";
+        let mut i = 0;
+        while i < words.len() {
+            match words[i] {
+                "base-fret" => {
+                    i += 1;
+                    base_fret = words.get(i)?.parse::<u8>().ok()?;
+                },
+                w @ ("frets" | "fingers" | "keys" | "copy" | "display" | "format") => current = w,
+                w if current == "frets" => frets.push(w),
+                w if current == "fingers" => fingers.push(w),
+                _ => {}
+            }
+            i += 1;
+        }
+    } else {
+        // Старый формат: {define: Am 1 x 0 2 2 1 0}
+        let (base, rest) = words.split_first()?;
+        base_fret = base.parse::<u8>().ok()?;
+        frets.extend(rest);
+    }
+    if base_fret == 0 { return None }
+
+    let instrument = match selector {
+        Some(s) => Instrument::new(s)?,
+        None => Instrument::ALL.into_iter().find(|i| i.get_strings() == frets.len())?
+    };
+    if frets.len() != instrument.get_strings() { return None }
+
+    // В Fingering первой идёт самая тонкая струна, лады за грифом - ошибка
+    let mut strings = Vec::new();
+    for fret in frets.iter().rev() {
+        strings.push( match *fret {
+            "x" | "X" | "N" | "-1" => StringState::Muted,
+            "0" => StringState::Open,
+            f => StringState::FrettedOn(
+                f.parse::<u8>().ok()?
+                    .checked_add(base_fret - 1)
+                    .filter(|f| *f <= instrument.get_frets())?
+            )
+        } );
+    }
+
+    let mut fingering = Fingering::new(strings, Some(name.to_string()))?;
+    if fingers.len() == frets.len() {
+        // Если пальцы не подходят, останутся автоматические
+        fingering.set_fingers( fingers.iter().rev().map(|f| Finger::new(f)).collect() );
+    }
+
+    Some( (instrument, fingering) )
+}
+
+
+// Строка сетки: | Am . . . | C . . . |
+fn read_grid_line(text: &str, lines: &mut Vec<Line>, chord_list: &mut Vec<Chord>) {
+    let mut chords = Vec::new();
+    for word in text.split_whitespace() {
+        let chord = Chord::from_text(word);
+        if chord.is_valid() {
+            add_to_chord_list(&chord, chord_list);
+            chords.push(chord);
+        }
+    }
+
+    if !chords.is_empty() { lines.push( Line::ChordsLine(chords) ) }
+}
+
+// "↓ 0:↓ 4:↑" - без индекса - затакт
+fn rhythm_from_directive(text: &str) -> Vec<Beat> {
+    let mut beats = Vec::new();
+    for item in text.split_whitespace() {
+        if let Some((index, symbol)) = item.split_once(':')
+            && let Ok(index) = index.parse::<usize>()
+            && let Some(symbol) = symbol.chars().next() {
+            beats.push( Beat::OnIndex{ index, symbol } );
+        } else if let Some(symbol) = item.chars().next() {
+            beats.push( Beat::UpBeat(symbol) );
+        }
+    }
+
+    return beats
+}
+
+
+fn read_line(text: &str, lines: &mut Vec<Line>, chord_list: &mut Vec<Chord>, pending: &mut Pending) {
     if text.is_empty() && lines.is_empty() {
         return
     }
@@ -175,12 +516,9 @@ fn read_line(text: &str, lines: &mut Vec<Line>, chord_list: &mut Vec<Chord>) {
         match c {
             ']' => {
                 is_chord = false;
-                if let Some(chord) = Chord::new(&current_chord) {
-                    if chord_list.iter().all(|c| *c != chord) {
-                        chord_list.push(chord.clone())
-                    }
-                    chords.push(ChordPosition::OnIndex{index, chord});
-                }
+                let chord = Chord::from_text(&current_chord);
+                add_to_chord_list(&chord, chord_list);
+                chords.push(ChordPosition::OnIndex{index, chord});
                 current_chord.clear();
             },
             '[' => is_chord = true,
@@ -191,6 +529,23 @@ fn read_line(text: &str, lines: &mut Vec<Line>, chord_list: &mut Vec<Chord>) {
             }
         }
     }
+    // Пробелы нужны только для аккордов после конца текста
+    let row_text = row_text.trim_end().to_string();
+
+    // Ритм или затакт из директив - значит это строка песни, а не просто текст
+    if pending.rhythm.is_some() || !pending.upbeats.is_empty() {
+        let mut all_chords: Vec<ChordPosition> = pending.upbeats.drain(..)
+            .map(ChordPosition::UpBeat)
+            .collect();
+        all_chords.append(&mut chords);
+
+        lines.push( Line::TextBlock( Row {
+            chords: if all_chords.is_empty() { None } else { Some(all_chords) },
+            rhythm: pending.rhythm.take(),
+            text: if row_text.trim().is_empty() { None } else { Some(row_text) }
+        } ) );
+        return
+    }
     
     lines.push(
         if !row_text.trim().is_empty() && !chords.is_empty() {
@@ -0,0 +1,177 @@
+use std::io::{Error, ErrorKind};
+
+use anyhow::Result;
+use roxmltree::{Document, Node};
+
+use crate::song::{
+    Song,
+    Metadata,
+    block::{Block, Line},
+    row::{Row, ChordPosition},
+    chord::Chord
+};
+use crate::Key;
+use super::opensong_reader::{get_section_title, get_child_text, order_sections, parse_tempo};
+
+
+pub fn read_from_openlyrics(xml: &str) -> Result<Song> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+    let properties = root.children().find(|n| n.tag_name().name() == "properties");
+    let (Some(properties), "song") = (properties, root.tag_name().name()) else {
+        return Err( Error::new(ErrorKind::InvalidData, "It's not an OpenLyrics song (<song><properties>)!").into() )
+    };
+    let get = |name: &str| get_child_text(properties, name);
+    let get_all = |list: &str, name: &str| -> Vec<String> {
+        properties.children()
+            .filter(|n| n.tag_name().name() == list)
+            .flat_map(|n| n.children().filter(|c| c.tag_name().name() == name))
+            .filter_map(|n| n.text().map(|t| t.trim().to_string()))
+            .filter(|t| !t.is_empty())
+            .collect()
+    };
+
+    let Some(title) = get_all("titles", "title").into_iter().next() else {
+        return Err( Error::new(ErrorKind::InvalidData, "There's no title in the song (<titles>)!").into() )
+    };
+    // Автор слов и композитор отмечены type, исполнитель - остальные авторы
+    let get_authors = |kind: Option<&str>| -> Vec<String> {
+        properties.children()
+            .filter(|n| n.tag_name().name() == "authors")
+            .flat_map(|n| n.children().filter(|c| c.tag_name().name() == "author"))
+            .filter(|n| n.attribute("type") == kind)
+            .filter_map(|n| n.text().map(|t| t.trim().to_string()))
+            .filter(|t| !t.is_empty())
+            .collect()
+    };
+    let join = |list: Vec<String>| if list.is_empty() { None } else { Some(list.join(", ")) };
+    let mut authors = get_authors(None);
+    if authors.is_empty() { authors = get_all("authors", "author") }
+    if authors.is_empty() {
+        return Err( Error::new(ErrorKind::InvalidData, "There's no artist in the song (<authors>)!").into() )
+    }
+
+    let mut song = Song::new(&title, &authors.join(", "));
+    song.metadata = Metadata {
+        key: get("key").and_then(|k| Key::new(&k)),
+        tempo: get("tempo").and_then(|t| parse_tempo(&t)),
+        time: get("timeSignature"),
+        copyright: get("copyright"),
+        year: get("released").and_then(|r| r.get(..4).and_then(|y| y.parse::<u16>().ok())),
+        composer: join(get_authors(Some("music"))),
+        lyricist: join(get_authors(Some("words"))),
+        ccli: get("ccliNo"),
+        tags: Metadata::parse_tags(&get_all("themes", "theme").join(",")),
+        ..song.metadata
+    };
+    let comments = get_all("comments", "comment");
+    if !comments.is_empty() { song.notes = Some(comments.join("\n")) }
+
+    let mut sections: Vec<(String, Block)> = Vec::new();
+    let verses = root.children()
+        .filter(|n| n.tag_name().name() == "lyrics")
+        .flat_map(|n| n.children().filter(|c| c.tag_name().name() == "verse"));
+    for verse in verses {
+        let name = verse.attribute("name").unwrap_or("").to_string();
+        let mut lines: Vec<Line> = Vec::new();
+        let mut notes: Vec<String> = Vec::new();
+        for group in verse.children().filter(|n| n.tag_name().name() == "lines") {
+            if !lines.is_empty() { lines.push(Line::EmptyLine) }
+            read_lines(group, &mut lines, &mut notes);
+        }
+
+        sections.push( (name.clone(), Block {
+            title: if name.is_empty() { None } else { Some(get_section_title(&name)) },
+            lines,
+            notes: if notes.is_empty() { None } else { Some(notes.join("\n")) }
+        }) );
+    }
+    song.blocks = order_sections(sections, get("verseOrder").as_deref());
+    song.chord_list = song.get_chord_list();
+
+
+    Ok(song)
+}
+
+
+// Строка, которая собирается из текста и <chord> до <br/>
+#[derive(Default)]
+struct PendingRow {
+    text: String,
+    chords: Vec<ChordPosition>
+}
+
+fn read_lines(group: Node, lines: &mut Vec<Line>, notes: &mut Vec<String>) {
+    let mut row = PendingRow::default();
+    read_node(group, &mut row, lines, notes);
+    push_row(&mut row, lines);
+}
+
+fn read_node(node: Node, row: &mut PendingRow, lines: &mut Vec<Line>, notes: &mut Vec<String>) {
+    for child in node.children() {
+        if child.is_text() {
+            row.text.push_str(&normalize(child.text().unwrap_or("")));
+            continue
+        }
+
+        match child.tag_name().name() {
+            "br" => push_row(row, lines),
+            "comment" => if let Some(t) = child.text() { notes.push(t.trim().to_string()) },
+            "chord" => {
+                let index = row.text.chars().count();
+                row.chords.push( ChordPosition::OnIndex{ index, chord: Chord::from_text(&get_chord_name(child)) } );
+                // В 0.9 аккорд может охватывать текст
+                read_node(child, row, lines, notes);
+            },
+            _ => read_node(child, row, lines, notes)
+        }
+    }
+}
+
+fn push_row(row: &mut PendingRow, lines: &mut Vec<Line>) {
+    let PendingRow { text, chords } = std::mem::take(row);
+    if text.trim().is_empty() && chords.is_empty() { return }
+
+    if text.trim().is_empty() {
+        lines.push( Line::ChordsLine(
+            chords.into_iter()
+                .map(|c| match c {
+                    ChordPosition::OnIndex{ chord, .. } | ChordPosition::UpBeat(chord) => chord
+                })
+                .collect()
+        ) );
+    } else {
+        lines.push( Line::TextBlock( Row {
+            rhythm: None,
+            chords: if chords.is_empty() { None } else { Some(chords) },
+            text: Some( text.trim_end().to_string() )
+        } ) );
+    }
+}
+
+// name из 0.8 или root, structure и bass из 0.9 (из структуры берётся только минор)
+fn get_chord_name(node: Node) -> String {
+    if let Some(name) = node.attribute("name") { return name.to_string() }
+
+    let mut name = node.attribute("root").unwrap_or("").to_string();
+    if node.attribute("structure").is_some_and(|s| s.contains("m3")) { name.push('m') }
+    if let Some(bass) = node.attribute("bass") {
+        name.push('/');
+        name.push_str(bass);
+    }
+
+    return name
+}
+
+// Переносы строк в XML - это только форматирование файла
+fn normalize(text: &str) -> String {
+    let parts: Vec<&str> = text.split('\n').collect();
+    let last = parts.len() - 1;
+    parts.iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let part = if i > 0 { part.trim_start() } else { part };
+            if i < last { part.trim_end() } else { part }
+        })
+        .collect()
+}
@@ -0,0 +1,227 @@
+use std::io::{Error, ErrorKind};
+use std::mem;
+
+use anyhow::Result;
+use roxmltree::{Document, Node};
+
+use crate::song::{
+    Song,
+    Metadata,
+    block::{Block, Line},
+    row::{Row, ChordPosition},
+    chord::Chord
+};
+use crate::Key;
+
+
+// Теги частей песни в OpenSong и OpenLyrics: V1 - Verse 1, C - Chorus...
+pub const SECTIONS: [(&str, &str); 7] = [
+    ("v", "Verse"),
+    ("c", "Chorus"),
+    ("b", "Bridge"),
+    ("p", "Pre-chorus"),
+    ("i", "Intro"),
+    ("e", "Ending"),
+    ("t", "Tag")
+];
+
+
+pub fn read_from_opensong(xml: &str) -> Result<Song> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "song" {
+        return Err( Error::new(ErrorKind::InvalidData, "It's not an OpenSong song (<song>)!").into() )
+    }
+    let get = |name: &str| get_child_text(root, name);
+
+    let Some(title) = get("title") else {
+        return Err( Error::new(ErrorKind::InvalidData, "There's no title in the song (<title>)!").into() )
+    };
+    let Some(artist) = get("author") else {
+        return Err( Error::new(ErrorKind::InvalidData, "There's no artist in the song (<author>)!").into() )
+    };
+
+    let mut song = Song::new(&title, &artist);
+    song.metadata = Metadata {
+        key: get("key").and_then(|k| Key::new(&k)),
+        capo: get("capo").and_then(|c| c.parse::<u8>().ok()).filter(|c| *c > 0),
+        tempo: get("tempo").and_then(|t| parse_tempo(&t)),
+        time: get("time_sig"),
+        copyright: get("copyright"),
+        ccli: get("ccli"),
+        // Темы через точку с запятой
+        tags: Metadata::parse_tags(&get("theme").unwrap_or_default().replace(';', ",")),
+        ..song.metadata
+    };
+
+    let lyrics = root.children()
+        .find(|n| n.has_tag_name("lyrics"))
+        .and_then(|n| n.text())
+        .unwrap_or("");
+    let (sections, notes) = read_lyrics(lyrics);
+    song.notes = notes;
+    song.blocks = order_sections(sections, get("presentation").as_deref());
+    song.chord_list = song.get_chord_list();
+
+
+    Ok(song)
+}
+
+
+// Части песни с тегами и заметки до первой части
+fn read_lyrics(lyrics: &str) -> (Vec<(String, Block)>, Option<String>) {
+    let mut sections: Vec<(String, Block)> = Vec::new();
+    let mut song_notes = String::new();
+
+    let mut tag: Option<String> = None;
+    let mut lines: Vec<Line> = Vec::new();
+    let mut notes = String::new();
+    let mut chords: Option<Vec<ChordPosition>> = None; // строка аккордов ждёт свой текст
+
+    for line in lyrics.lines() {
+        if line.starts_with('[') && let Some(end) = line.find(']') {
+            push_chords(&mut chords, &mut lines);
+            push_section(&mut sections, tag.take(), &mut lines, &mut notes);
+            tag = Some( line[1..end].trim().to_string() );
+        } else if let Some(chord_line) = line.strip_prefix('.') {
+            push_chords(&mut chords, &mut lines);
+            chords = Some( read_chord_line(chord_line) );
+        } else if let Some(comment) = line.strip_prefix(';') {
+            let target = if tag.is_none() && lines.is_empty() && sections.is_empty() { &mut song_notes }
+                else { &mut notes };
+            if !target.is_empty() { target.push('\n') }
+            target.push_str(comment.trim());
+        } else if line.trim().is_empty() {
+            push_chords(&mut chords, &mut lines);
+            if !lines.is_empty() { lines.push(Line::EmptyLine) }
+        } else {
+            // Строки текста начинаются с пробела или номера куплета
+            let text = match line.chars().next() {
+                Some(c) if c == ' ' || c.is_ascii_digit() => &line[1..],
+                _ => line
+            };
+            let chords = chords.take().filter(|c| !c.is_empty());
+            lines.push( Line::TextBlock( Row {
+                rhythm: None,
+                chords,
+                text: Some( text.trim_end().to_string() )
+            } ) );
+        }
+    }
+    push_chords(&mut chords, &mut lines);
+    push_section(&mut sections, tag, &mut lines, &mut notes);
+
+    let notes = if song_notes.is_empty() { None } else { Some(song_notes) };
+    return (sections, notes)
+}
+
+fn push_chords(chords: &mut Option<Vec<ChordPosition>>, lines: &mut Vec<Line>) {
+    let Some(chords) = chords.take() else { return };
+    if chords.is_empty() { return }
+
+    lines.push( Line::ChordsLine(
+        chords.into_iter()
+            .map(|c| match c {
+                ChordPosition::OnIndex{ chord, .. } | ChordPosition::UpBeat(chord) => chord
+            })
+            .collect()
+    ) );
+}
+
+fn push_section(sections: &mut Vec<(String, Block)>, tag: Option<String>, lines: &mut Vec<Line>, notes: &mut String) {
+    while lines.last() == Some(&Line::EmptyLine) { lines.pop(); }
+    if tag.is_none() && lines.is_empty() && notes.is_empty() { return }
+
+    let title = tag.as_deref().map(get_section_title);
+    sections.push( (tag.unwrap_or_default(), Block {
+        title,
+        lines: mem::take(lines),
+        notes: if notes.is_empty() { None } else { Some(mem::take(notes)) }
+    }) );
+}
+
+// Позиция аккорда - номер символа после точки
+fn read_chord_line(line: &str) -> Vec<ChordPosition> {
+    let mut chords = Vec::new();
+    let mut chord = String::new();
+    let mut start = 0;
+    for (i, c) in line.chars().chain([' ']).enumerate() {
+        if c.is_whitespace() {
+            if !chord.is_empty() {
+                chords.push( ChordPosition::OnIndex{ index: start, chord: Chord::from_text(&chord) } );
+                chord.clear();
+            }
+        } else {
+            if chord.is_empty() { start = i }
+            chord.push(c);
+        }
+    }
+
+    return chords
+}
+
+
+// Порядок из <presentation> или <verseOrder>, без него - как в тексте
+pub(super) fn order_sections(sections: Vec<(String, Block)>, order: Option<&str>) -> Vec<Block> {
+    let Some(order) = order.filter(|o| !o.trim().is_empty()) else {
+        return sections.into_iter().map(|(_, block)| block).collect()
+    };
+
+    let mut blocks: Vec<Block> = order.split_whitespace()
+        .filter_map(|tag| sections.iter().find(|(t, _)| t.eq_ignore_ascii_case(tag)))
+        .map(|(_, block)| block.clone())
+        .collect();
+    // Части, которых нет в порядке, не теряются
+    for (tag, block) in &sections {
+        if !order.split_whitespace().any(|t| t.eq_ignore_ascii_case(tag)) {
+            blocks.push(block.clone());
+        }
+    }
+
+    return blocks
+}
+
+// V1 -> Verse 1, C -> Chorus, неизвестный тег остаётся как есть
+pub fn get_section_title(tag: &str) -> String {
+    let lower = tag.to_lowercase();
+    let split = lower.find(|c: char| c.is_ascii_digit()).unwrap_or(lower.len());
+    let (letters, number) = lower.split_at(split);
+    if !number.chars().all(|c| c.is_ascii_digit()) { return tag.to_string() }
+
+    match SECTIONS.iter().find(|(t, _)| *t == letters) {
+        Some( (_, title) ) if number.is_empty() => title.to_string(),
+        Some( (_, title) ) => format!("{title} {number}"),
+        None => tag.to_string()
+    }
+}
+
+// Verse 1 -> v1, Припев -> c, для остальных None
+pub fn get_section_tag(title: &str) -> Option<String> {
+    let lower = title.trim().to_lowercase();
+    let (name, number) = match lower.rsplit_once(' ') {
+        Some( (name, number) ) if number.chars().all(|c| c.is_ascii_digit()) => (name.trim(), number),
+        _ => (lower.as_str(), "")
+    };
+
+    let tag = match name {
+        "куплет" => "v",
+        "припев" => "c",
+        "бридж" => "b",
+        _ => SECTIONS.iter().find(|(_, t)| t.to_lowercase() == name)?.0
+    };
+
+    Some( format!("{tag}{number}") )
+}
+
+pub(super) fn get_child_text(node: Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|n| n.tag_name().name() == name)
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+pub(super) fn parse_tempo(text: &str) -> Option<u16> {
+    let digits: String = text.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok().filter(|t| *t > 0)
+}
@@ -5,18 +5,28 @@ use crate::song::{
     row::{Row, ChordPosition},
     chord::Chord
 };
-use crate::{Key, Note};
+use crate::{Key, Mode, Note};
 use anyhow::Result;
+use serde_json::Value;
+use uuid::Uuid;
 
 
 #[derive(serde::Deserialize, Debug)]
 struct SongbookPro {
     songs: Vec<SbpSong>,
+    // У папок и сетов разные версии приложения пишут разные поля,
+    // поэтому они разбираются вручную
+    #[serde(default)]
+    folders: Vec<Value>,
+    #[serde(default)]
+    sets: Vec<Value>,
 }
 
 #[allow(non_snake_case)]
 #[derive(serde::Deserialize, Debug)]
 struct SbpSong {
+    #[serde(default, alias = "id")]
+    Id: Option<Value>,
     author: String,
     name: String,
     content: String,
@@ -24,51 +34,105 @@ struct SbpSong {
     KeyShift: u8,
     Capo: u8,
     NotesText: String,
+    #[serde(default, alias = "TimeSignature", alias = "timeSig")]
+    TimeSig: Option<Value>,
+    #[serde(default, alias = "tempo")]
+    Tempo: Option<Value>,
+    #[serde(default, alias = "copyright")]
+    Copyright: Option<Value>,
+    #[serde(default, alias = "folder", alias = "FolderId", alias = "folderId")]
+    Folder: Option<Value>,
 }
 
+// Песни с папками и сеты из бэкапа SongbookPro
+#[derive(Debug)]
+pub struct SbpBackup {
+    pub songs: Vec<(Song, Option<String>)>, // песня - папка
+    pub sets: Vec<SbpSet>
+}
+
+#[derive(Debug)]
+pub struct SbpSet {
+    pub name: String,
+    pub songs: Vec<(usize, Option<i32>)> // номер песни в SbpBackup::songs - транспонирование
+}
 
-pub fn read_from_sbp(file_content: &str) -> Result<Vec<Song>> {
+
+pub fn read_from_sbp(file_content: &str) -> Result<SbpBackup> {
     let sbp: SongbookPro = serde_json::from_str(file_content)?;
-    let mut songs: Vec<Song> = Vec::new();
-    for song in &sbp.songs {
+
+    let folders: Vec<(String, String)> = sbp.folders.iter()
+        .filter_map(|f| Some( (get_field(f, &["Id", "id"])?, get_field(f, &["Name", "name", "title"])?) ))
+        .collect();
+
+    let mut songs = Vec::new();
+    let mut ids: Vec<String> = Vec::new();
+    for (i, song) in sbp.songs.iter().enumerate() {
         let ( metadata, notes ) = convert_metadata(song);
         let ( blocks, chord_list ) = convert_content(&song.content);
-        let mut s = Song { metadata, chord_list, blocks, notes };
-        if song.KeyShift > 0 { s.transpose(song.KeyShift.into()) }
-        songs.push(s);
+        let folder = song.Folder.as_ref()
+            .and_then(|f| if let Value::Array(a) = f { a.first() } else { Some(f) })
+            .and_then(value_to_string)
+            .map(|f| folders.iter().find(|(id, _)| *id == f).map(|(_, name)| name.clone()).unwrap_or(f))
+            .filter(|f| !f.is_empty());
+
+        songs.push( (Song { id: Uuid::new_v4(), metadata, chord_list, blocks, notes }, folder) );
+        ids.push( song.Id.as_ref().and_then(value_to_string).unwrap_or(i.to_string()) );
     }
 
-    Ok(songs)
+    let mut sets = Vec::new();
+    for set in &sbp.sets {
+        let Some(name) = get_field(set, &["name", "Name", "title"]) else { continue };
+        let items = ["contents", "Contents", "songs", "Songs", "items"].iter()
+            .find_map(|k| set.get(*k).and_then(|v| v.as_array()));
+
+        let mut set_songs = Vec::new();
+        for item in items.into_iter().flatten() {
+            let id = if item.is_object() { get_field(item, &["SongId", "songId", "Id", "id"]) }
+                else { value_to_string(item) };
+            let shift = ["KeyShift", "keyShift", "KeyOffset"].iter()
+                .find_map(|k| item.get(*k).and_then(|v| v.as_i64()))
+                .and_then(|s| i32::try_from(s).ok())
+                .filter(|s| *s != 0);
+
+            if let Some(id) = id && let Some(index) = ids.iter().position(|i| *i == id) {
+                set_songs.push( (index, shift) );
+            }
+        }
+        sets.push( SbpSet { name, songs: set_songs } );
+    }
+
+    Ok( SbpBackup { songs, sets } )
 }
 
 
 fn convert_metadata(song: &SbpSong) -> ( Metadata, Option<String> ) {
     let key = match song.key {
-        0 => Some( Key { keynote: Note::A, is_minor: false } ),
-        1 => Some( Key { keynote: Note::ASharp, is_minor: false } ),
-        2 => Some( Key { keynote: Note::B, is_minor: false } ),
-        3 => Some( Key { keynote: Note::C, is_minor: false } ),
-        4 => Some( Key { keynote: Note::CSharp, is_minor: false } ),
-        5 => Some( Key { keynote: Note::D, is_minor: false } ),
-        6 => Some( Key { keynote: Note::DSharp, is_minor: false } ),
-        7 => Some( Key { keynote: Note::E, is_minor: false } ),
-        8 => Some( Key { keynote: Note::F, is_minor: false } ),
-        9 => Some( Key { keynote: Note::FSharp, is_minor: false } ),
-        10 => Some( Key { keynote: Note::G, is_minor: false } ),
-        11 => Some( Key { keynote: Note::GSharp, is_minor: false } ),
-
-        12 => Some( Key { keynote: Note::A, is_minor: true } ),
-        13 => Some( Key { keynote: Note::ASharp, is_minor: true } ),
-        14 => Some( Key { keynote: Note::B, is_minor: true } ),
-        15 => Some( Key { keynote: Note::C, is_minor: true } ),
-        16 => Some( Key { keynote: Note::CSharp, is_minor: true } ),
-        17 => Some( Key { keynote: Note::D, is_minor: true } ),
-        18 => Some( Key { keynote: Note::DSharp, is_minor: true } ),
-        19 => Some( Key { keynote: Note::E, is_minor: true } ),
-        20 => Some( Key { keynote: Note::F, is_minor: true } ),
-        21 => Some( Key { keynote: Note::FSharp, is_minor: true } ),
-        22 => Some( Key { keynote: Note::G, is_minor: true } ),
-        23 => Some( Key { keynote: Note::GSharp, is_minor: true } ),
+        0 => Some( Key::with_mode(Note::A, Mode::Major) ),
+        1 => Some( Key::with_mode(Note::ASharp, Mode::Major) ),
+        2 => Some( Key::with_mode(Note::B, Mode::Major) ),
+        3 => Some( Key::with_mode(Note::C, Mode::Major) ),
+        4 => Some( Key::with_mode(Note::CSharp, Mode::Major) ),
+        5 => Some( Key::with_mode(Note::D, Mode::Major) ),
+        6 => Some( Key::with_mode(Note::DSharp, Mode::Major) ),
+        7 => Some( Key::with_mode(Note::E, Mode::Major) ),
+        8 => Some( Key::with_mode(Note::F, Mode::Major) ),
+        9 => Some( Key::with_mode(Note::FSharp, Mode::Major) ),
+        10 => Some( Key::with_mode(Note::G, Mode::Major) ),
+        11 => Some( Key::with_mode(Note::GSharp, Mode::Major) ),
+
+        12 => Some( Key::with_mode(Note::A, Mode::Minor) ),
+        13 => Some( Key::with_mode(Note::ASharp, Mode::Minor) ),
+        14 => Some( Key::with_mode(Note::B, Mode::Minor) ),
+        15 => Some( Key::with_mode(Note::C, Mode::Minor) ),
+        16 => Some( Key::with_mode(Note::CSharp, Mode::Minor) ),
+        17 => Some( Key::with_mode(Note::D, Mode::Minor) ),
+        18 => Some( Key::with_mode(Note::DSharp, Mode::Minor) ),
+        19 => Some( Key::with_mode(Note::E, Mode::Minor) ),
+        20 => Some( Key::with_mode(Note::F, Mode::Minor) ),
+        21 => Some( Key::with_mode(Note::FSharp, Mode::Minor) ),
+        22 => Some( Key::with_mode(Note::G, Mode::Minor) ),
+        23 => Some( Key::with_mode(Note::GSharp, Mode::Minor) ),
 
         _ => None
     };
@@ -78,7 +142,25 @@ fn convert_metadata(song: &SbpSong) -> ( Metadata, Option<String> ) {
         artist: song.author.clone(),
         key,
         capo: if song.Capo > 0 { Some( song.Capo ) } else { None },
-        autoscroll_speed: None
+        tempo: song.Tempo.as_ref().and_then(value_to_string).and_then(|t| {
+            let digits: String = t.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok().filter(|t| *t > 0)
+        }),
+        time: song.TimeSig.as_ref().and_then(value_to_string).filter(|t| !t.is_empty()),
+        // Аккорды остаются в исходной тональности
+        key_shift: if song.KeyShift > 0 { Some( song.KeyShift.into() ) } else { None },
+        copyright: song.Copyright.as_ref().and_then(value_to_string).filter(|c| !c.is_empty()),
+        autoscroll_speed: None,
+        spelling: None,
+        tuning: None,
+        album: None,
+        year: None,
+        composer: None,
+        lyricist: None,
+        ccli: None,
+        language: None,
+        url: None,
+        tags: Vec::new()
     },
     if song.NotesText.is_empty() { None }
     else { Some( song.NotesText.clone() ) } )
@@ -100,11 +182,14 @@ fn convert_content(content: &str) -> ( Vec<Block>, Vec<Chord> ) {
             if lines.last() == Some(&Line::EmptyLine) {
                 lines.pop();
             }
-            blocks.push( Block {
-                title: if title.is_empty() { None } else { Some(title) },
-                lines,
-                notes: if notes.is_empty() { None } else { Some(notes) }
-            });
+            // До первого {c:} может ничего не быть
+            if !title.is_empty() || !notes.is_empty() || !lines.is_empty() {
+                blocks.push( Block {
+                    title: if title.is_empty() { None } else { Some(title) },
+                    lines,
+                    notes: if notes.is_empty() { None } else { Some(notes) }
+                });
+            }
             title = String::new();
             notes = String::new();
             lines = Vec::new();
@@ -169,10 +254,9 @@ fn unwrap_line_with_chords(line: &str, chord_list: &mut Vec<Chord>) -> Line {
             in_chord = true;
         } else if c == ']' {
             in_chord = false;
-            if let Some(chord) = Chord::new(&chord_text) {
-                if chord_list.iter().all(|ch| *ch != chord) { chord_list.push(chord.clone()) }
-                chords.push(ChordPosition::OnIndex {index: index, chord: chord} );
-            }
+            let chord = Chord::from_text(&chord_text);
+            if chord.is_valid() && chord_list.iter().all(|ch| *ch != chord) { chord_list.push(chord.clone()) }
+            chords.push(ChordPosition::OnIndex {index: index, chord: chord} );
             chord_text.clear();
         } else if in_chord {
             chord_text.push(c)
@@ -198,3 +282,16 @@ fn unwrap_line_with_chords(line: &str, chord_list: &mut Vec<Chord>) -> Line {
         })
     }
 }
+
+
+fn get_field(value: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|k| value.get(*k).and_then(value_to_string))
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some( s.trim().to_string() ),
+        Value::Number(n) => Some( n.to_string() ),
+        _ => None
+    }
+}
@@ -1,129 +1,266 @@
+use std::mem;
+
 use crate::song::{
+    Metadata,
     block::{Block, Line},
     row::{Row, ChordPosition},
     chord::Chord
 };
+use crate::{Key, Tuning, Note, Instrument};
+
+
+// Заголовки частей без скобок, только с двоеточием: Intro:, Verse 2:
+const SECTION_NAMES: [&str; 24] = [
+    "verse", "chorus", "pre-chorus", "prechorus", "post-chorus", "bridge",
+    "intro", "outro", "interlude", "instrumental", "solo", "riff",
+    "tab", "hook", "refrain", "coda", "ending", "break",
+    "куплет", "припев", "бридж", "проигрыш", "вступление", "кода"
+];
+
+
+// Часть, которая сейчас читается
+#[derive(Default)]
+struct BlockBuf {
+    title: Option<String>,
+    lines: Vec<Line>,
+    chords: Option<Vec<ChordPosition>>, // строка аккордов ждёт свой текст
+    tab: Vec<String>
+}
+
+impl BlockBuf {
+    fn push_pending(&mut self) {
+        if let Some(chords) = self.chords.take() {
+            self.lines.push( Line::ChordsLine(
+                chords.into_iter()
+                    .map(|c| match c {
+                        ChordPosition::OnIndex{ chord, .. } | ChordPosition::UpBeat(chord) => chord
+                    })
+                    .collect()
+            ) );
+        }
+        if !self.tab.is_empty() {
+            self.lines.push( Line::Tab(mem::take(&mut self.tab).join("\n")) );
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.title.is_none() && self.lines.is_empty() && self.chords.is_none() && self.tab.is_empty()
+    }
+
+    fn finish(&mut self, blocks: &mut Vec<Block>) {
+        self.push_pending();
+        if self.is_empty() { return }
+
+        let BlockBuf { title, mut lines, .. } = mem::take(self);
+        // Как раньше: заголовок без строк - часть с пустой строкой
+        if lines.is_empty() { lines.push(Line::EmptyLine) }
+        blocks.push( Block { title, lines, notes: None } );
+    }
+}
 
 
-pub fn read_from_txt(txt: &str) -> (Vec<Block>, Vec<Chord>) {
+// Текст с сайтов с аккордами: заголовки [Verse 1] и Intro:, табы e|---,
+// строки только с аккордами, Capo:, Key: и Tuning: идут в metadata.
+// Если заголовков нет, заголовком считается первая строка после пустой
+pub fn read_from_txt(txt: &str, metadata: &mut Metadata) -> (Vec<Block>, Vec<Chord>) {
     let mut blocks: Vec<Block> = Vec::new();
-    let mut chord_list: Vec<Chord> = Vec::new();
+    let guess_titles = !txt.lines().any(|l| get_header(l).is_some());
 
-    let mut title = String::new();
-    let mut rows: Vec<Row> = Vec::new();
-    let mut chords = Vec::new();
-    let mut last_line_is_chords = false;
+    let mut block = BlockBuf::default();
     let mut last_line_was_empty = true;
     for line in txt.lines() {
-        let line = line.to_string();
-        if line.is_empty() {
+        let line = line.trim_end();
+        if is_tab_line(line) {
+            if block.chords.is_some() { block.push_pending() }
+            block.tab.push(line.to_string());
+            last_line_was_empty = false;
+            continue
+        } else if !block.tab.is_empty() {
+            block.push_pending();
+        }
+
+        if line.trim().is_empty() {
+            block.finish(&mut blocks);
             last_line_was_empty = true;
-            if !rows.is_empty() {
-                blocks.push(Block {
-                    title: if title.is_empty() { None } else { Some(title) },
-                    lines: rows.iter().map(|r| Line::TextBlock(r.clone())).collect(),
-                    notes: None
-                });
-                title = String::new();
-                rows.clear();
-            } else if last_line_is_chords {
-                if !chords.is_empty() {
-                    blocks.push(Block {
-                        title: if title.is_empty() { None } else { Some(title) },
-                        lines: vec!(Line::TextBlock(
-                                Row { chords: Some(chords), text: None, rhythm: None }),
-                        ),
-                        notes: None
-                    });
-                    title = String::new();
-                    chords = Vec::new();
-                    last_line_is_chords = false
+            continue
+        }
+
+        if read_metadata_line(line, metadata) { continue }
+
+        if let Some( (title, rest) ) = get_header(line) {
+            block.finish(&mut blocks);
+            block.title = Some(title);
+            if !rest.is_empty() {
+                if is_line_chords(rest) {
+                    block.lines.push( Line::ChordsLine(
+                        rest.split_whitespace().filter(|w| !is_bar_symbol(w)).map(Chord::from_text).collect()
+                    ) );
+                } else {
+                    block.lines.push( Line::TextBlock( Row { chords: None, text: Some(rest.to_string()), rhythm: None } ) );
                 }
-            } else if !title.is_empty() {
-                blocks.push(Block {
-                    title: Some(title),
-                    lines: vec!(Line::EmptyLine),
-                    notes: None
-                });
-                title = String::new();
             }
+        } else if is_line_chords(line) {
+            block.push_pending();
+            block.chords = Some( read_chords(line) );
+        } else if let Some(chords) = block.chords.take() {
+            let chords = if chords.is_empty() { None } else { Some(chords) };
+            block.lines.push( Line::TextBlock( Row { chords, text: Some(line.to_string()), rhythm: None } ) );
+        } else if guess_titles && last_line_was_empty && block.is_empty() {
+            block.title = Some(line.to_string());
+        } else {
+            block.lines.push( Line::TextBlock( Row { chords: None, text: Some(line.to_string()), rhythm: None } ) );
+        }
 
-            continue
-        };
-
-        if is_line_chords(&line) && !last_line_is_chords {
-            last_line_is_chords = true;
-
-            let line = line + " ";
-            let mut chord = String::new();
-            let mut indent = 0;
-            for i in line.chars() {
-                if i == ' ' {
-                    if !chord.is_empty() {
-                        if let Some(c) = Chord::new(&chord) {
-                            chords.push( ChordPosition::OnIndex{ index: ( indent - chord.chars().count() ), chord: c.clone() } );
-                            if chord_list.iter().all(|chord| *chord != c) {
-                                chord_list.push(c);
-                            }
-                        }
-
-                        chord.clear();
-                    }
-                    indent += 1;
-                    continue
-                }
+        last_line_was_empty = false;
+    }
+    // Последний block
+    block.finish(&mut blocks);
 
-                chord.push(i);
-                indent += 1;
+    let mut chord_list: Vec<Chord> = Vec::new();
+    for block in &blocks {
+        for line in &block.lines {
+            let chords: Vec<&Chord> = match line {
+                Line::TextBlock(row) => row.chords.iter()
+                    .flatten()
+                    .map(|c| match c {
+                        ChordPosition::OnIndex{ chord, .. } | ChordPosition::UpBeat(chord) => chord
+                    })
+                    .collect(),
+                Line::ChordsLine(chords) => chords.iter().collect(),
+                _ => Vec::new()
+            };
+            for c in chords {
+                if c.is_valid() && chord_list.iter().all(|chord| chord != c) {
+                    chord_list.push(c.clone());
+                }
             }
+        }
+    }
+
+    return (blocks, chord_list)
+}
+
 
-        } else if last_line_is_chords {
-            if chords.is_empty() {
-                rows.push(Row { chords: None, text: Some(line), rhythm: None });
-            } else {
-                rows.push(Row { chords: Some(chords), text: Some(line), rhythm: None });
-                chords = Vec::new();
+// Позиция аккорда - номер символа в строке
+fn read_chords(line: &str) -> Vec<ChordPosition> {
+    let mut chords = Vec::new();
+    let mut chord = String::new();
+    let mut start = 0;
+    for (i, c) in line.chars().chain([' ']).enumerate() {
+        if c.is_whitespace() {
+            if !chord.is_empty() && !is_bar_symbol(&chord) {
+                chords.push( ChordPosition::OnIndex{ index: start, chord: Chord::from_text(&chord) } );
             }
-            last_line_is_chords = false;
-        } else if last_line_was_empty {
-            title = line;
+            chord.clear();
         } else {
-            rows.push(Row { chords: None, text: Some(line), rhythm: None });
+            if chord.is_empty() { start = i }
+            chord.push(c);
         }
+    }
 
-        last_line_was_empty = false;
+    return chords
+}
+
+// [Verse 1] или Intro: Am G, после двоеточия могут быть аккорды
+fn get_header(line: &str) -> Option<(String, &str)> {
+    let line = line.trim();
+    if let Some(inner) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+        let inner = inner.trim();
+        if inner.is_empty() || inner.contains(['[', ']']) || is_line_chords(inner) { return None }
+        return Some( (inner.to_string(), "") )
     }
 
-    // Последний block
-    if !rows.is_empty() {
-        blocks.push(Block {
-            title: if title.is_empty() { None } else { Some(title) },
-            lines: rows.iter().map(|r| Line::TextBlock(r.clone())).collect(),
-            notes: None
-        });
+    let (name, rest) = line.split_once(':')?;
+    let lower = name.trim().to_lowercase();
+    let base = lower.trim_end_matches(|c: char| c.is_ascii_digit() || c.is_whitespace());
+    if !SECTION_NAMES.contains(&base) { return None }
+
+    Some( (name.trim().to_string(), rest.trim()) )
+}
+
+// Capo: 2, Key: G, Tuning: Drop D или E A D G B E
+fn read_metadata_line(line: &str, metadata: &mut Metadata) -> bool {
+    let Some( (name, value) ) = line.trim().split_once(':') else { return false };
+    let value = value.trim();
+    match name.trim().to_lowercase().as_str() {
+        "capo" => {
+            let digits: String = value.chars()
+                .skip_while(|c| !c.is_ascii_digit())
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            metadata.capo = digits.parse::<u8>().ok().filter(|c| *c > 0);
+        },
+        "key" => match value.split_whitespace().next().and_then(Key::new) {
+            Some(key) => metadata.key = Some(key),
+            None => return false
+        },
+        "tuning" => if let Some(tuning) = parse_tuning(value) {
+            // Стандартный строй не записывается
+            metadata.tuning = if tuning == Tuning::Standard { None } else { Some(tuning) }
+        },
+        _ => return false
     }
 
-    return (blocks, chord_list)
+    return true
+}
+
+// Пресет по названию или ноты от толстой струны, как их пишут на сайтах
+fn parse_tuning(text: &str) -> Option<Tuning> {
+    if let Some(tuning) = Tuning::new(text) { return Some(tuning) }
+
+    let notes_text = match (text.find('('), text.rfind(')')) {
+        (Some(start), Some(end)) if start < end => &text[start + 1..end],
+        _ => text
+    };
+    let notes: Vec<&str> = notes_text.split([' ', ',', '-'])
+        .filter(|n| !n.is_empty())
+        .collect();
+    if notes.iter().any(|n| Note::new(n).is_none()) { return None }
+    if Instrument::ALL.iter().all(|i| i.get_strings() != notes.len()) {
+        return Tuning::new(text.split('(').next().unwrap_or(""))
+    }
+
+    let reversed: Vec<&str> = notes.into_iter().rev().collect();
+    Tuning::new(&reversed.join(", "))
+}
+
+// Строка табулатуры: e|---0---, |--3--| или B|-----
+fn is_tab_line(line: &str) -> bool {
+    let line = line.trim();
+    let Some(bar) = line.find('|') else { return false };
+    let name = line[..bar].trim();
+    if name.chars().count() > 2 || !name.chars().all(|c| c.is_alphabetic() || c == '#') { return false }
+
+    let stave = &line[bar..];
+    stave.matches('-').count() >= 3 && stave.chars().all(|c| !c.is_alphabetic() || "hpbrsxv".contains(c))
+}
+
+// Такты и повторы в строке аккордов: |, x2, (x4)
+fn is_bar_symbol(word: &str) -> bool {
+    let word = word.trim_matches(['(', ')']);
+    return matches!(word, "|" | "||" | "-" | "/" | "%")
+        || word.strip_prefix(['x', 'х']).is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
 }
 
 fn is_line_chords(line: &str) -> bool {
-    let words: Vec<&str> = line.split_whitespace().collect();
+    let words: Vec<&str> = line.split_whitespace()
+        .filter(|w| !is_bar_symbol(w))
+        .collect();
     let chords = ["A", "B", "C", "D", "E", "F", "G"];
+    if words.is_empty() { return false }
 
     if !words.iter().all(|w| chords.iter().any(|c| w.starts_with(*c))) {
         return false
     }
-    
+
     // Проверка по второй букве
-    let allowed_second_chars = ['m', '+', '-', '5', '6', '7', '9', '1', 's', 'a', '#', 'b'];
+    let allowed_second_chars = ['m', '+', '-', '5', '6', '7', '9', '1', 's', 'a', '#', 'b', '/'];
     for word in words {
         // Если в слове есть вторая буква то проверить что она есть в списке разрешенных
-        if let Some(second_char) = word.chars().nth(1) {
-            if !allowed_second_chars.iter().any(|c| *c == second_char) {
-                return false
-            }
+        if let Some(second_char) = word.chars().nth(1) && !allowed_second_chars.contains(&second_char) {
+            return false
         }
     }
-    
+
     return true
 }
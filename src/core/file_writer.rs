@@ -0,0 +1,57 @@
+pub mod chordpro_writer;
+pub mod html_writer;
+pub mod markdown_writer;
+pub mod opensong_writer;
+pub mod openlyrics_writer;
+pub mod sbp_writer;
+#[cfg(feature = "pdf")]
+pub mod pdf_writer;
+
+
+
+use crate::{Song, Instrument};
+#[cfg(feature = "pdf")]
+use pdf_writer::PdfOptions;
+
+
+// Ширина строки для аппликатур в файлах
+pub const EXPORT_WIDTH: usize = 80;
+
+
+impl Song {
+    pub fn to_chordpro(&self) -> String {
+        chordpro_writer::write_to_chordpro(self)
+    }
+
+    // Как get_song_as_text, но аппликатуры не зависят от ширины терминала
+    pub fn to_text(&self, chords: bool, rhythm: bool, fingerings: Option<Instrument>, notes: bool) -> String {
+        self.get_song_as_text_with_width(chords, rhythm, fingerings, notes, Some(EXPORT_WIDTH))
+    }
+
+    pub fn to_markdown(&self, chords: bool, rhythm: bool, fingerings: Option<Instrument>, notes: bool) -> String {
+        markdown_writer::write_to_markdown(self, chords, rhythm, fingerings, notes)
+    }
+
+    // Zip-архив SongbookPro с одной песней
+    pub fn to_sbp(&self) -> anyhow::Result<Vec<u8>> {
+        sbp_writer::write_to_sbp(&[(self.clone(), None)], &[])
+    }
+
+    pub fn to_opensong(&self) -> String {
+        opensong_writer::write_to_opensong(self)
+    }
+
+    pub fn to_openlyrics(&self) -> String {
+        openlyrics_writer::write_to_openlyrics(self)
+    }
+
+    pub fn to_html(&self) -> String {
+        html_writer::write_to_html(self, None)
+    }
+
+    // font - моноширинный TTF
+    #[cfg(feature = "pdf")]
+    pub fn to_pdf(&self, options: &PdfOptions, font: &[u8]) -> anyhow::Result<Vec<u8>> {
+        pdf_writer::write_to_pdf(std::slice::from_ref(self), &self.metadata.title, options, font)
+    }
+}
@@ -0,0 +1,243 @@
+use crate::song::{
+    Song,
+    Metadata,
+    block::{Block, Line},
+    row::{Row, Beat, ChordPosition}
+};
+
+
+// Директивы, которых нет в стандарте ChordPro, но без них теряются данные песни
+pub const SPELLING_DIRECTIVE: &str = "x_songbook_spelling";
+pub const TUNING_DIRECTIVE: &str = "x_songbook_tuning";
+pub const AUTOSCROLL_SPEED_DIRECTIVE: &str = "x_songbook_autoscroll_speed";
+pub const KEY_SHIFT_DIRECTIVE: &str = "x_songbook_key_shift";
+pub const SONG_NOTE_DIRECTIVE: &str = "x_songbook_note";
+pub const UPBEAT_DIRECTIVE: &str = "x_songbook_upbeat";
+pub const RHYTHM_DIRECTIVE: &str = "x_songbook_rhythm";
+
+
+pub fn write_to_chordpro(song: &Song) -> String {
+    let mut s = String::new();
+
+    write_metadata(&song.metadata, &mut s);
+    if let Some(notes) = &song.notes {
+        for line in notes.lines() {
+            push_directive(&mut s, SONG_NOTE_DIRECTIVE, line);
+        }
+    }
+
+    for block in &song.blocks {
+        s.push('\n');
+        write_block(block, &mut s);
+    }
+
+
+    return s
+}
+
+
+fn write_metadata(metadata: &Metadata, s: &mut String) {
+    push_directive(s, "title", &metadata.title);
+    push_directive(s, "artist", &metadata.artist);
+    if let Some(key) = metadata.key {
+        push_directive(s, "key", &key.to_string());
+    }
+    if let Some(capo) = metadata.capo {
+        push_directive(s, "capo", &capo.to_string());
+    }
+    if let Some(tempo) = metadata.tempo {
+        push_directive(s, "tempo", &tempo.to_string());
+    }
+    if let Some(time) = &metadata.time {
+        push_directive(s, "time", time);
+    }
+    if let Some(copyright) = &metadata.copyright {
+        push_directive(s, "copyright", copyright);
+    }
+    if let Some(album) = &metadata.album {
+        push_directive(s, "album", album);
+    }
+    if let Some(year) = metadata.year {
+        push_directive(s, "year", &year.to_string());
+    }
+    if let Some(composer) = &metadata.composer {
+        push_directive(s, "composer", composer);
+    }
+    if let Some(lyricist) = &metadata.lyricist {
+        push_directive(s, "lyricist", lyricist);
+    }
+    // Своих директив для них нет, только {meta: имя значение}
+    for (name, value) in [("ccli", &metadata.ccli), ("language", &metadata.language), ("url", &metadata.url)] {
+        if let Some(value) = value {
+            push_directive(s, "meta", &format!("{name} {value}"));
+        }
+    }
+    for tag in &metadata.tags {
+        push_directive(s, "tag", tag);
+    }
+    if let Some(shift) = metadata.key_shift {
+        push_directive(s, KEY_SHIFT_DIRECTIVE, &shift.to_string());
+    }
+    if let Some(spelling) = metadata.spelling {
+        push_directive(s, SPELLING_DIRECTIVE, &spelling.to_string());
+    }
+    if let Some(tuning) = &metadata.tuning {
+        push_directive(s, TUNING_DIRECTIVE, &tuning.to_string());
+    }
+    if let Some(speed) = metadata.autoscroll_speed {
+        push_directive(s, AUTOSCROLL_SPEED_DIRECTIVE, &speed.to_string());
+    }
+}
+
+fn write_block(block: &Block, s: &mut String) {
+    let environment = get_environment(block.title.as_deref());
+
+    s.push_str("{start_of_");
+    s.push_str(environment);
+    if let Some(title) = &block.title {
+        s.push_str(": ");
+        s.push_str(&get_label(title));
+    }
+    s.push_str("}\n");
+
+    if let Some(notes) = &block.notes {
+        for line in notes.lines() {
+            push_directive(s, "comment", line);
+        }
+    }
+
+    for line in &block.lines {
+        match line {
+            Line::TextBlock(row) => write_row(row, s),
+            Line::ChordsLine(chords) => {
+                let line = chords.iter()
+                    .map(|c| format!("[{}]", c.text))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                s.push_str(&line);
+                s.push('\n');
+            },
+            // Одну строку без аккордов reader и так прочитает как текст
+            Line::PlainText(text) if is_simple_text(text) => {
+                s.push_str(text);
+                s.push('\n');
+            },
+            Line::PlainText(text) => {
+                s.push_str("{start_of_textblock}\n");
+                s.push_str(text);
+                s.push_str("\n{end_of_textblock}\n");
+            },
+            Line::Tab(text) => {
+                s.push_str("{start_of_tab}\n");
+                s.push_str(text);
+                s.push_str("\n{end_of_tab}\n");
+            },
+            Line::EmptyLine => s.push('\n')
+        }
+    }
+
+    s.push_str("{end_of_");
+    s.push_str(environment);
+    s.push_str("}\n");
+}
+
+fn write_row(row: &Row, s: &mut String) {
+    if let Some(beats) = &row.rhythm && !beats.is_empty() {
+        let rhythm = beats.iter()
+            .map(|b| match b {
+                Beat::UpBeat(symbol) => symbol.to_string(),
+                Beat::OnIndex{ index, symbol } => format!("{index}:{symbol}")
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        push_directive(s, RHYTHM_DIRECTIVE, &rhythm);
+    }
+
+    let mut upbeats: Vec<&str> = Vec::new();
+    let mut on_index: Vec<(usize, &str)> = Vec::new();
+    if let Some(chords) = &row.chords {
+        for chord in chords {
+            match chord {
+                ChordPosition::UpBeat(c) => upbeats.push(&c.text),
+                ChordPosition::OnIndex{ index, chord } => on_index.push( (*index, &chord.text) )
+            }
+        }
+    }
+    on_index.sort_by_key(|(index, _)| *index);
+    if !upbeats.is_empty() {
+        push_directive(s, UPBEAT_DIRECTIVE, &upbeats.join(" "));
+    }
+
+    let mut line = String::new();
+    write_inline_chords(row.text.as_deref().unwrap_or(""), &on_index, &mut line);
+    // Строку с # или { в начале reader принял бы за комментарий или директиву
+    if line.starts_with(['#', '{']) { s.push('\\') }
+    s.push_str(&line);
+}
+
+// Текст с [аккордами] внутри, on_index отсортирован по позиции
+pub(super) fn write_inline_chords(text: &str, on_index: &[(usize, &str)], s: &mut String) {
+    if text.is_empty() {
+        // Строка только из аккордов
+        let line = on_index.iter()
+            .map(|(_, chord)| format!("[{chord}]"))
+            .collect::<Vec<String>>()
+            .join(" ");
+        s.push_str(&line);
+        s.push('\n');
+        return
+    }
+
+    let mut chords = on_index.iter().peekable();
+    let mut count = 0;
+    for c in text.chars() {
+        while let Some((index, chord)) = chords.peek() && *index <= count {
+            s.push_str(&format!("[{chord}]"));
+            chords.next();
+        }
+        s.push(c);
+        count += 1;
+    }
+    // Аккорды после конца текста
+    for (index, chord) in chords {
+        while count < *index {
+            s.push(' ');
+            count += 1;
+        }
+        s.push_str(&format!("[{chord}]"));
+    }
+    s.push('\n');
+}
+
+
+fn is_simple_text(text: &str) -> bool {
+    !text.trim().is_empty()
+        && !text.contains('\n')
+        && !text.contains('[')
+        && !text.starts_with(['{', '#'])
+}
+
+// Номер ("1") или текст с "=" reader принял бы за атрибуты секции
+fn get_label(title: &str) -> String {
+    if title.contains('=') || title.chars().all(|c| c.is_ascii_digit() || c == 'x' || c == '+') {
+        return format!("label=\"{title}\"")
+    }
+
+    return title.to_string()
+}
+
+// ChordPro знает только куплет, припев и бридж
+fn get_environment(title: Option<&str>) -> &'static str {
+    let title = title.unwrap_or("").to_lowercase();
+    if title.contains("chorus") || title.contains("припев") { "chorus" }
+    else if title.contains("bridge") || title.contains("бридж") { "bridge" }
+    else { "verse" }
+}
+
+fn push_directive(s: &mut String, name: &str, value: &str) {
+    s.push('{');
+    s.push_str(name);
+    s.push_str(": ");
+    s.push_str(value);
+    s.push_str("}\n");
+}
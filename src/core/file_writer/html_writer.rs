@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use crate::song::{
+    Song,
+    block::{Block, Line},
+    row::{Row, Beat, ChordPosition}
+};
+
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 1em auto; max-width: 60em; padding: 0 1em; line-height: 1.3; }
+h1 { margin: 0; }
+h2 { margin: 0 0 .3em; font-weight: normal; color: #666; }
+.info { color: #666; }
+.controls { position: sticky; top: 0; background: #fff; padding: .4em 0; border-bottom: 1px solid #ddd; }
+.controls button { font-size: 1em; margin-right: .2em; }
+.block { margin: 1em 0; }
+.block-title { margin: 0 0 .3em; font-size: 1em; color: #060; }
+.notes { color: #777; white-space: pre-wrap; }
+.row { white-space: nowrap; }
+.seg { display: inline-flex; flex-direction: column; vertical-align: bottom; }
+.chord, .rhythm { color: #048; font-weight: bold; white-space: pre; }
+.chord b { margin-right: .5em; }
+.rhythm { color: #a60; }
+.lyric, .text { white-space: pre; }
+.text { white-space: pre-wrap; }
+.tab { margin: 0; }
+.empty { height: 1em; }
+.hide-chords .chord, .hide-rhythm .rhythm, .hide-notes .notes { display: none; }
+"#;
+
+// Транспонирование и переключатели как в TUI: c - аккорды, r - ритм, n - заметки
+const SCRIPT: &str = r#"
+const SHARPS = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+const FLATS = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+const flats = document.body.dataset.spelling === "flats";
+let steps = 0;
+
+function shift(note) {
+    let i = SHARPS.indexOf(note);
+    if (i < 0) i = FLATS.indexOf(note);
+    if (i < 0) return note;
+    return (flats ? FLATS : SHARPS)[((i + steps) % 12 + 12) % 12];
+}
+function transpose(chord) {
+    const m = chord.match(/^([A-G][#b]?)([^\/]*)(?:\/([A-G][#b]?))?$/);
+    if (!m) return chord;
+    return shift(m[1]) + m[2] + (m[3] ? "/" + shift(m[3]) : "");
+}
+function update() {
+    document.querySelectorAll("[data-chord]").forEach(e => e.textContent = transpose(e.dataset.chord));
+    document.getElementById("steps").textContent = (steps > 0 ? "+" : "") + steps;
+}
+function toggle(name) {
+    document.body.classList.toggle("hide-" + name);
+}
+
+document.querySelectorAll("[data-step]").forEach(b => b.onclick = () => { steps += Number(b.dataset.step); update(); });
+document.querySelectorAll("[data-toggle]").forEach(b => b.onclick = () => toggle(b.dataset.toggle));
+document.addEventListener("keydown", e => {
+    if (e.ctrlKey || e.metaKey || e.altKey) return;
+    const name = { c: "chords", r: "rhythm", n: "notes" }[e.key];
+    if (name) toggle(name);
+    if (e.key === "+" || e.key === "=") { steps += 1; update(); }
+    if (e.key === "-") { steps -= 1; update(); }
+});
+"#;
+
+
+// Самодостаточная страница, index - ссылка на оглавление библиотеки
+pub fn write_to_html(song: &Song, index: Option<&str>) -> String {
+    let metadata = &song.metadata;
+    let mut s = String::new();
+
+    s.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    s.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    s.push_str(&format!("<title>{} - {}</title>\n", escape(&metadata.artist), escape(&metadata.title)));
+    s.push_str(&format!("<style>{STYLE}</style>\n</head>\n"));
+    s.push_str(&format!("<body data-spelling=\"{}\">\n", metadata.get_spelling()));
+
+    if let Some(index) = index {
+        s.push_str(&format!("<a href=\"{}\">Contents</a>\n", escape(index)));
+    }
+    s.push_str(&format!("<h1>{}</h1>\n<h2>{}</h2>\n", escape(&metadata.title), escape(&metadata.artist)));
+
+    let mut info = Vec::new();
+    if let Some(key) = metadata.key {
+        info.push( format!("Key: <b data-chord=\"{0}\">{0}</b>", escape(&key.to_string())) );
+    }
+    if let Some(capo) = metadata.capo { info.push(format!("Capo: {capo}")) }
+    if let Some(tempo) = metadata.tempo { info.push(format!("Tempo: {tempo}")) }
+    if let Some(time) = &metadata.time { info.push(format!("Time: {}", escape(time))) }
+    if let Some(tuning) = &metadata.tuning { info.push(format!("Tuning: {tuning}")) }
+    if !info.is_empty() {
+        s.push_str(&format!("<p class=\"info\">{}</p>\n", info.join(" · ")));
+    }
+
+    s.push_str(concat!(
+        "<nav class=\"controls\">\n",
+        "<button data-step=\"-1\">−</button><span id=\"steps\">0</span><button data-step=\"1\">+</button>\n",
+        "<button data-toggle=\"chords\">Chords (c)</button>\n",
+        "<button data-toggle=\"rhythm\">Rhythm (r)</button>\n",
+        "<button data-toggle=\"notes\">Notes (n)</button>\n",
+        "</nav>\n"
+    ));
+
+    if let Some(notes) = &song.notes {
+        s.push_str(&format!("<div class=\"notes\">{}</div>\n", escape(notes)));
+    }
+    for block in &song.blocks {
+        write_block(block, &mut s);
+    }
+
+    s.push_str(&format!("<script>{SCRIPT}</script>\n</body>\n</html>\n"));
+
+
+    return s
+}
+
+// Оглавление для библиотеки: (ссылка, название)
+#[cfg(feature = "song_library")]
+pub fn write_index_to_html(title: &str, songs: &[(String, String)]) -> String {
+    let mut s = String::new();
+    s.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    s.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    s.push_str(&format!("<title>{}</title>\n", escape(title)));
+    s.push_str(&format!("<style>{STYLE}</style>\n</head>\n<body>\n"));
+    s.push_str(&format!("<h1>{}</h1>\n<ul>\n", escape(title)));
+    // link - путь через /, каждая часть кодируется отдельно
+    for (link, name) in songs {
+        let href: Vec<String> = link.split('/').map(encode_url_part).collect();
+        s.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", href.join("/"), escape(name)));
+    }
+    s.push_str("</ul>\n</body>\n</html>\n");
+
+    return s
+}
+
+
+fn write_block(block: &Block, s: &mut String) {
+    s.push_str("<section class=\"block\">\n");
+    if let Some(title) = &block.title {
+        s.push_str(&format!("<h3 class=\"block-title\">{}</h3>\n", escape(title)));
+    }
+    if let Some(notes) = &block.notes {
+        s.push_str(&format!("<div class=\"notes\">{}</div>\n", escape(notes)));
+    }
+
+    for line in &block.lines {
+        match line {
+            Line::TextBlock(row) => write_row(row, s),
+            Line::ChordsLine(chords) => {
+                let chords: Vec<&str> = chords.iter().map(|c| c.text.as_str()).collect();
+                s.push_str(&format!("<div class=\"chord\">{}</div>\n", get_chords(&chords)));
+            },
+            Line::PlainText(text) => s.push_str(&format!("<div class=\"text\">{}</div>\n", escape(text))),
+            Line::Tab(text) => s.push_str(&format!("<pre class=\"tab\">{}</pre>\n", escape(text))),
+            Line::EmptyLine => s.push_str("<div class=\"empty\"></div>\n")
+        }
+    }
+
+    s.push_str("</section>\n");
+}
+
+// Строка режется на куски по позициям аккордов и долей,
+// у каждого куска аккорд и доля стоят над его текстом
+fn write_row(row: &Row, s: &mut String) {
+    let text: Vec<char> = row.text.as_deref().unwrap_or("").chars().collect();
+
+    let mut upbeat: (Vec<&str>, Vec<char>) = (Vec::new(), Vec::new());
+    let mut marks: BTreeMap<usize, (Vec<&str>, Vec<char>)> = BTreeMap::new();
+    for chord in row.chords.iter().flatten() {
+        match chord {
+            ChordPosition::UpBeat(c) => upbeat.0.push(&c.text),
+            ChordPosition::OnIndex{ index, chord } => marks.entry(*index).or_default().0.push(&chord.text)
+        }
+    }
+    for beat in row.rhythm.iter().flatten() {
+        match beat {
+            Beat::UpBeat(symbol) => upbeat.1.push(*symbol),
+            Beat::OnIndex{ index, symbol } => marks.entry(*index).or_default().1.push(*symbol)
+        }
+    }
+
+    let has_chords = row.chords.as_ref().is_some_and(|c| !c.is_empty());
+    let has_rhythm = row.rhythm.as_ref().is_some_and(|r| !r.is_empty());
+    if !has_chords && !has_rhythm {
+        s.push_str(&format!("<div class=\"text\">{}</div>\n", escape(&text.iter().collect::<String>())));
+        return
+    }
+
+    let segment = |chords: &[&str], beats: &[char], text: &[char], s: &mut String| {
+        s.push_str("<span class=\"seg\">");
+        // пустой кусок тоже держит высоту, чтобы текст стоял на одной линии
+        if has_chords {
+            let chords = if chords.is_empty() { " ".to_string() } else { get_chords(chords) };
+            s.push_str(&format!("<span class=\"chord\">{chords}</span>"));
+        }
+        if has_rhythm {
+            let beats: String = if beats.is_empty() { " ".to_string() }
+                else { beats.iter().map(|b| format!("{b} ")).collect() };
+            s.push_str(&format!("<span class=\"rhythm\">{}</span>", escape(&beats)));
+        }
+        let text: String = if text.is_empty() { " ".to_string() } else { text.iter().collect() };
+        s.push_str(&format!("<span class=\"lyric\">{}</span>", escape(&text)));
+        s.push_str("</span>");
+    };
+
+    s.push_str("<div class=\"row\">");
+    if !upbeat.0.is_empty() || !upbeat.1.is_empty() {
+        segment(&upbeat.0, &upbeat.1, &[], s);
+    }
+
+    let positions: Vec<usize> = marks.keys().copied().collect();
+    let first = positions.first().copied().unwrap_or(text.len()).min(text.len());
+    if first > 0 { segment(&[], &[], &text[..first], s) }
+    for (i, position) in positions.iter().enumerate() {
+        let start = (*position).min(text.len());
+        let end = positions.get(i + 1).copied().unwrap_or(text.len()).clamp(start, text.len());
+        let (chords, beats) = &marks[position];
+        segment(chords, beats, &text[start..end], s);
+    }
+    s.push_str("</div>\n");
+}
+
+fn get_chords(chords: &[&str]) -> String {
+    chords.iter()
+        .map(|c| format!("<b data-chord=\"{0}\">{0}</b>", escape(c)))
+        .collect()
+}
+
+// Всё, кроме букв, цифр и -._~, - как %XX по байтам UTF-8: #, ?, % и пробел ломают ссылку
+#[cfg(feature = "song_library")]
+fn encode_url_part(text: &str) -> String {
+    let mut s = String::new();
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) { s.push(byte as char) }
+        else { s.push_str(&format!("%{byte:02X}")) }
+    }
+
+    return s
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,111 @@
+use crate::{Instrument, sum_text_in_fingerings};
+use crate::song::{
+    Song,
+    block::Line
+};
+use super::EXPORT_WIDTH;
+
+
+// Заголовки блоков - ## Заголовок, строки аккордов и ритма - в `code span`
+pub fn write_to_markdown(
+    song: &Song,
+    chords: bool,
+    rhythm: bool,
+    fingerings: Option<Instrument>,
+    notes: bool
+) -> String {
+    let metadata = &song.metadata;
+    let mut s = format!("# {}\n\n", escape(&metadata.title));
+    if !metadata.artist.is_empty() {
+        s.push_str(&format!("**{}**\n\n", escape(&metadata.artist)));
+    }
+
+    let mut info = Vec::new();
+    if let Some(key) = metadata.key { info.push(format!("Key: {key}")) }
+    if let Some(capo) = metadata.capo { info.push(format!("Capo: {capo}")) }
+    if let Some(tempo) = metadata.tempo { info.push(format!("Tempo: {tempo}")) }
+    if let Some(time) = &metadata.time { info.push(format!("Time: {}", escape(time))) }
+    if !info.is_empty() {
+        s.push_str(&info.join(" · "));
+        s.push_str("\n\n");
+    }
+
+    if let Some(n) = &song.notes && notes {
+        push_quote(n, &mut s);
+    }
+
+    if chords && let Some(instrument) = fingerings {
+        let fings = song.get_fingerings_for_showing(instrument);
+        if let Some(text) = sum_text_in_fingerings(&fings, Some(EXPORT_WIDTH)) {
+            s.push_str(&format!("```\n{}\n```\n\n", text.trim_end()));
+        }
+    }
+
+    for block in &song.blocks {
+        if let Some(title) = &block.title && !title.is_empty() {
+            s.push_str(&format!("## {}\n\n", escape(title)));
+        }
+        if let Some(n) = &block.notes && notes {
+            push_quote(n, &mut s);
+        }
+
+        // строки одного абзаца разделяются жёстким переносом
+        let mut paragraph: Vec<String> = Vec::new();
+        for line in &block.lines {
+            match line {
+                Line::TextBlock(row) => {
+                    let (chords_line, rhythm_line, text) = row.get_strings();
+                    if chords && !chords_line.trim().is_empty() {
+                        paragraph.push(format!("`{}`", chords_line.trim_end()));
+                    }
+                    if rhythm && !rhythm_line.trim().is_empty() {
+                        paragraph.push(format!("`{}`", rhythm_line.trim_end()));
+                    }
+                    if !text.trim().is_empty() { paragraph.push(escape(text.trim())) }
+                },
+                Line::ChordsLine(chords_line) => if chords {
+                    let chords_line: Vec<&str> = chords_line.iter().map(|c| c.text.as_str()).collect();
+                    paragraph.push(format!("`{}`", chords_line.join(" ")));
+                },
+                Line::PlainText(text) => {
+                    for l in text.lines() { paragraph.push(escape(l.trim())) }
+                },
+                Line::Tab(text) => {
+                    push_paragraph(&mut paragraph, &mut s);
+                    s.push_str(&format!("```\n{}\n```\n\n", text.trim_end()));
+                },
+                Line::EmptyLine => push_paragraph(&mut paragraph, &mut s)
+            }
+        }
+        push_paragraph(&mut paragraph, &mut s);
+    }
+
+
+    return format!("{}\n", s.trim_end())
+}
+
+
+fn push_paragraph(paragraph: &mut Vec<String>, s: &mut String) {
+    if paragraph.is_empty() { return }
+
+    s.push_str(&paragraph.join("  \n"));
+    s.push_str("\n\n");
+    paragraph.clear();
+}
+
+fn push_quote(text: &str, s: &mut String) {
+    for line in text.lines() {
+        s.push_str(&format!("> {}  \n", escape(line)));
+    }
+    s.push('\n');
+}
+
+fn escape(text: &str) -> String {
+    let mut s = String::new();
+    for c in text.chars() {
+        if "\\`*_[]<>#|~".contains(c) { s.push('\\') }
+        s.push(c);
+    }
+
+    return s
+}
@@ -0,0 +1,135 @@
+use crate::song::{
+    Song,
+    block::{Block, Line},
+    row::{Row, ChordPosition}
+};
+use super::opensong_writer::{get_sections, escape};
+
+
+// OpenLyrics 0.8, аккорды - <chord name="G"/> внутри текста
+pub fn write_to_openlyrics(song: &Song) -> String {
+    let metadata = &song.metadata;
+    let (sections, order) = get_sections(&song.blocks, false);
+
+    let mut s = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<song xmlns=\"http://openlyrics.info/namespace/2009/song\" version=\"0.8\" createdIn=\"songbook\" modifiedIn=\"songbook\">\n",
+        "  <properties>\n"
+    ));
+    s.push_str(&format!("    <titles>\n      <title>{}</title>\n    </titles>\n", escape(&metadata.title)));
+    s.push_str(&format!("    <authors>\n      <author>{}</author>\n", escape(&metadata.artist)));
+    if let Some(composer) = &metadata.composer {
+        s.push_str(&format!("      <author type=\"music\">{}</author>\n", escape(composer)));
+    }
+    if let Some(lyricist) = &metadata.lyricist {
+        s.push_str(&format!("      <author type=\"words\">{}</author>\n", escape(lyricist)));
+    }
+    s.push_str("    </authors>\n");
+    if let Some(copyright) = &metadata.copyright {
+        s.push_str(&format!("    <copyright>{}</copyright>\n", escape(copyright)));
+    }
+    if let Some(ccli) = &metadata.ccli {
+        s.push_str(&format!("    <ccliNo>{}</ccliNo>\n", escape(ccli)));
+    }
+    if let Some(year) = metadata.year {
+        s.push_str(&format!("    <released>{year}</released>\n"));
+    }
+    if let Some(key) = metadata.key {
+        s.push_str(&format!("    <key>{}</key>\n", escape(&key.to_string())));
+    }
+    if let Some(tempo) = metadata.tempo {
+        s.push_str(&format!("    <tempo type=\"bpm\">{tempo}</tempo>\n"));
+    }
+    if let Some(time) = &metadata.time {
+        s.push_str(&format!("    <timeSignature>{}</timeSignature>\n", escape(time)));
+    }
+    if !metadata.tags.is_empty() {
+        s.push_str("    <themes>\n");
+        for tag in &metadata.tags {
+            s.push_str(&format!("      <theme>{}</theme>\n", escape(tag)));
+        }
+        s.push_str("    </themes>\n");
+    }
+    s.push_str(&format!("    <verseOrder>{}</verseOrder>\n", escape(&order.join(" "))));
+    if let Some(notes) = &song.notes {
+        s.push_str(&format!("    <comments>\n      <comment>{}</comment>\n    </comments>\n", escape(notes)));
+    }
+    s.push_str("  </properties>\n  <lyrics>\n");
+
+    for (name, block) in &sections {
+        s.push_str(&format!("    <verse name=\"{}\">\n", escape(name)));
+        write_block(block, &mut s);
+        s.push_str("    </verse>\n");
+    }
+    s.push_str("  </lyrics>\n</song>\n");
+
+
+    return s
+}
+
+
+// Пустые строки делят часть на несколько <lines>
+fn write_block(block: &Block, s: &mut String) {
+    let mut groups: Vec<Vec<String>> = vec![Vec::new()];
+    for line in &block.lines {
+        let group = groups.last_mut().expect("There's always a group");
+        match line {
+            Line::TextBlock(row) => group.push(get_row(row)),
+            Line::ChordsLine(chords) => group.push(
+                chords.iter()
+                    .map(|c| format!("<chord name=\"{}\"/>", escape(&c.text)))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            Line::PlainText(text) | Line::Tab(text) => group.extend(text.lines().map(escape)),
+            Line::EmptyLine => if !group.is_empty() { groups.push(Vec::new()) }
+        }
+    }
+    groups.retain(|g| !g.is_empty());
+    if groups.is_empty() { groups.push(Vec::new()) }
+
+    for (i, group) in groups.iter().enumerate() {
+        s.push_str("      <lines>");
+        if i == 0 && let Some(notes) = &block.notes {
+            s.push_str(&format!("<comment>{}</comment>", escape(notes)));
+        }
+        s.push_str(&group.join("<br/>"));
+        s.push_str("</lines>\n");
+    }
+}
+
+fn get_row(row: &Row) -> String {
+    let mut upbeats: Vec<&str> = Vec::new();
+    let mut on_index: Vec<(usize, &str)> = Vec::new();
+    for chord in row.chords.iter().flatten() {
+        match chord {
+            ChordPosition::UpBeat(c) => upbeats.push(&c.text),
+            ChordPosition::OnIndex{ index, chord } => on_index.push( (*index, &chord.text) )
+        }
+    }
+    on_index.sort_by_key(|(index, _)| *index);
+    let chord = |text: &str| format!("<chord name=\"{}\"/>", escape(text));
+
+    // Затакт - аккорды перед текстом
+    let mut s: String = upbeats.iter().map(|c| chord(c)).collect();
+    let mut chords = on_index.iter().peekable();
+    let mut count = 0;
+    for c in row.text.as_deref().unwrap_or("").chars() {
+        while let Some((index, text)) = chords.peek() && *index <= count {
+            s.push_str(&chord(text));
+            chords.next();
+        }
+        s.push_str(&escape(&c.to_string()));
+        count += 1;
+    }
+    // Аккорды после конца текста
+    for (index, text) in chords {
+        while count < *index {
+            s.push(' ');
+            count += 1;
+        }
+        s.push_str(&chord(text));
+    }
+
+    return s
+}
@@ -0,0 +1,122 @@
+use crate::song::{
+    Song,
+    block::{Block, Line},
+    row::ChordPosition
+};
+use crate::file_reader::opensong_reader::get_section_tag;
+
+
+pub fn write_to_opensong(song: &Song) -> String {
+    let metadata = &song.metadata;
+    let (sections, order) = get_sections(&song.blocks, true);
+
+    let mut lyrics = String::new();
+    if let Some(notes) = &song.notes {
+        for line in notes.lines() { lyrics.push_str(&format!(";{line}\n")) }
+    }
+    for (tag, block) in &sections {
+        if !lyrics.is_empty() { lyrics.push('\n') }
+        lyrics.push_str(&format!("[{tag}]\n"));
+        write_block(block, &mut lyrics);
+    }
+
+    let mut s = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<song>\n");
+    push_element(&mut s, "title", &metadata.title);
+    push_element(&mut s, "author", &metadata.artist);
+    if let Some(copyright) = &metadata.copyright { push_element(&mut s, "copyright", copyright) }
+    if let Some(ccli) = &metadata.ccli { push_element(&mut s, "ccli", ccli) }
+    if !metadata.tags.is_empty() { push_element(&mut s, "theme", &metadata.tags.join("; ")) }
+    push_element(&mut s, "presentation", &order.join(" "));
+    if let Some(capo) = metadata.capo {
+        s.push_str(&format!("  <capo print=\"true\">{capo}</capo>\n"));
+    }
+    if let Some(key) = metadata.key { push_element(&mut s, "key", &key.to_string()) }
+    if let Some(tempo) = metadata.tempo { push_element(&mut s, "tempo", &tempo.to_string()) }
+    if let Some(time) = &metadata.time { push_element(&mut s, "time_sig", time) }
+    push_element(&mut s, "lyrics", lyrics.trim_end());
+    s.push_str("</song>\n");
+
+
+    return s
+}
+
+
+// Строки аккордов начинаются с точки, текста - с пробела, комментарии - с ;
+fn write_block(block: &Block, s: &mut String) {
+    if let Some(notes) = &block.notes {
+        for line in notes.lines() { s.push_str(&format!(";{line}\n")) }
+    }
+
+    for line in &block.lines {
+        match line {
+            Line::TextBlock(row) if row.text.is_none() => {
+                let chords: Vec<&str> = row.chords.iter()
+                    .flatten()
+                    .map(|c| match c {
+                        ChordPosition::OnIndex{ chord, .. } | ChordPosition::UpBeat(chord) => chord.text.as_str()
+                    })
+                    .collect();
+                if !chords.is_empty() { s.push_str(&format!(".{}\n", chords.join(" "))) }
+            },
+            Line::TextBlock(row) => {
+                let (chords, _rhythm, text) = row.get_strings();
+                if !chords.trim().is_empty() { s.push_str(&format!(".{}\n", chords.trim_end())) }
+                if row.text.is_some() { s.push_str(&format!(" {}\n", text.trim_end())) }
+            },
+            Line::ChordsLine(chords) => {
+                let chords: Vec<&str> = chords.iter().map(|c| c.text.as_str()).collect();
+                s.push_str(&format!(".{}\n", chords.join(" ")));
+            },
+            // Табов в OpenSong нет, они пишутся как текст
+            Line::PlainText(text) | Line::Tab(text) => {
+                for l in text.lines() { s.push_str(&format!(" {l}\n")) }
+            },
+            Line::EmptyLine => s.push('\n')
+        }
+    }
+}
+
+// Одинаковые части пишутся один раз, порядок - списком тегов.
+// Заголовки без тега (Проигрыш) становятся тегами сами
+pub(super) fn get_sections(blocks: &[Block], uppercase: bool) -> (Vec<(String, &Block)>, Vec<String>) {
+    let mut sections: Vec<(String, &Block)> = Vec::new();
+    let mut order: Vec<String> = Vec::new();
+    for block in blocks {
+        if let Some( (tag, _) ) = sections.iter().find(|(_, b)| *b == block) {
+            order.push(tag.clone());
+            continue
+        }
+
+        let title = block.title.as_deref().map(str::trim).filter(|t| !t.is_empty());
+        let base = match title {
+            Some(t) => match get_section_tag(t) {
+                Some(tag) if uppercase => tag.to_uppercase(),
+                Some(tag) => tag,
+                None => t.split_whitespace().collect::<Vec<&str>>().join("-")
+            },
+            None if uppercase => "V1".to_string(),
+            None => "v1".to_string()
+        };
+        let is_used = |tag: &str| sections.iter().any(|(t, _)| t.eq_ignore_ascii_case(tag));
+        let tag = if !is_used(&base) { base } else {
+            let letters = base.trim_end_matches(|c: char| c.is_ascii_digit());
+            (2..).map(|n| format!("{letters}{n}")).find(|t| !is_used(t)).unwrap_or(base.clone())
+        };
+
+        order.push(tag.clone());
+        sections.push( (tag, block) );
+    }
+
+    return (sections, order)
+}
+
+fn push_element(s: &mut String, name: &str, value: &str) {
+    s.push_str(&format!("  <{name}>{}</{name}>\n", escape(value)));
+}
+
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,487 @@
+use std::io::Cursor;
+
+use anyhow::Result;
+use printpdf::{
+    PdfDocument,
+    PdfLayerReference,
+    IndirectFontRef,
+    Mm,
+    Pt,
+    Point,
+    Polygon,
+    Color,
+    Rgb,
+    path::{PaintMode, WindingOrder},
+    utils::calculate_points_for_circle
+};
+
+use crate::song::{
+    Song,
+    block::{Block, Line}
+};
+use crate::{Fingering, Instrument, StringState};
+
+
+// A4, всё в пунктах
+const PAGE_WIDTH: f32 = 595.28;
+const PAGE_HEIGHT: f32 = 841.89;
+const MARGIN: f32 = 40.0;
+const COLUMN_GAP: f32 = 20.0;
+
+// Ширина символа моноширинного шрифта относительно его размера
+const CHAR_WIDTH: f32 = 0.61;
+const LINE_HEIGHT: f32 = 1.25;
+
+const TITLE_SIZE: f32 = 16.0;
+const ARTIST_SIZE: f32 = 11.0;
+const INFO_SIZE: f32 = 9.0;
+const TEXT_SIZE: f32 = 10.0;
+const MIN_TEXT_SIZE: f32 = 6.0;
+
+// Сетка аппликатуры
+const STRING_SPACING: f32 = 7.0;
+const FRET_HEIGHT: f32 = 9.0;
+const MIN_FRETS: u8 = 4;
+
+
+pub struct PdfOptions {
+    pub columns: usize,
+    pub chords: bool,
+    pub rhythm: bool,
+    pub fingerings: Option<Instrument>, // для какого инструмента рисовать аппликатуры
+    pub notes: bool
+}
+
+#[derive(Clone, Copy)]
+enum Shade {
+    Text,
+    Title,
+    Chords,
+    Rhythm,
+    Notes
+}
+
+enum Element {
+    Text { x: f32, y: f32, size: f32, shade: Shade, text: String },
+    Line { points: Vec<(f32, f32)>, width: f32 },
+    Dot { x: f32, y: f32, radius: f32 }
+}
+
+
+// Песни с новой страницы, для нескольких песен - оглавление в начале.
+// Шрифт должен быть моноширинным TTF, иначе аккорды съедут с текста
+pub fn write_to_pdf(songs: &[Song], title: &str, options: &PdfOptions, font: &[u8]) -> Result<Vec<u8>> {
+    let mut layout = Layout::new(options);
+    let mut starts = Vec::new();
+    for song in songs {
+        starts.push(layout.pages.len());
+        layout.add_song(song);
+    }
+
+    let mut pages = Vec::new();
+    let mut bookmarks = Vec::new();
+    if songs.len() > 1 {
+        let entries: Vec<String> = songs.iter()
+            .map(|s| format!("{} - {}", s.metadata.artist, s.metadata.title))
+            .collect();
+        pages = get_contents(&entries, &starts);
+        bookmarks.push( ("Contents".to_string(), 0) );
+    }
+    let offset = pages.len();
+    for (song, start) in songs.iter().zip(&starts) {
+        bookmarks.push( (song.metadata.title.clone(), start + offset) );
+    }
+    pages.append(&mut layout.pages);
+    if songs.len() > 1 { add_page_numbers(&mut pages) }
+
+
+    let (doc, first_page, first_layer) = PdfDocument::new(
+        title, Mm::from(Pt(PAGE_WIDTH)), Mm::from(Pt(PAGE_HEIGHT)), "Layer 1"
+    );
+    let font = doc.add_external_font(Cursor::new(font))?;
+    let mut indexes = Vec::new();
+    for (i, page) in pages.iter().enumerate() {
+        let (page_index, layer_index) = if i == 0 { (first_page, first_layer) }
+            else { doc.add_page(Mm::from(Pt(PAGE_WIDTH)), Mm::from(Pt(PAGE_HEIGHT)), "Layer 1") };
+        indexes.push(page_index);
+
+        let layer = doc.get_page(page_index).get_layer(layer_index);
+        for element in page {
+            draw(&layer, &font, element);
+        }
+    }
+    for (name, page) in bookmarks {
+        if let Some(index) = indexes.get(page) {
+            doc.add_bookmark(name, *index);
+        }
+    }
+
+
+    Ok( doc.save_to_bytes()? )
+}
+
+
+struct Layout<'a> {
+    options: &'a PdfOptions,
+    pages: Vec<Vec<Element>>,
+    column: usize,
+    top: f32, // верх колонок на текущей странице
+    y: f32
+}
+
+impl<'a> Layout<'a> {
+    fn new(options: &'a PdfOptions) -> Self {
+        Self {
+            options,
+            pages: Vec::new(),
+            column: 0,
+            top: PAGE_HEIGHT - MARGIN,
+            y: PAGE_HEIGHT - MARGIN
+        }
+    }
+
+    fn columns(&self) -> usize {
+        self.options.columns.max(1)
+    }
+
+    fn column_width(&self) -> f32 {
+        let columns = self.columns() as f32;
+        (PAGE_WIDTH - MARGIN * 2.0 - COLUMN_GAP * (columns - 1.0)) / columns
+    }
+
+    fn column_x(&self) -> f32 {
+        MARGIN + self.column as f32 * (self.column_width() + COLUMN_GAP)
+    }
+
+    fn push(&mut self, element: Element) {
+        if let Some(page) = self.pages.last_mut() { page.push(element) }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Vec::new());
+        self.column = 0;
+        self.top = PAGE_HEIGHT - MARGIN;
+        self.y = self.top;
+    }
+
+    fn next_column(&mut self) {
+        if self.column + 1 < self.columns() {
+            self.column += 1;
+            self.y = self.top;
+        } else {
+            self.new_page();
+        }
+    }
+
+    fn add_text(&mut self, x: f32, size: f32, shade: Shade, text: &str) {
+        self.y -= size * LINE_HEIGHT;
+        self.push( Element::Text { x, y: self.y, size, shade, text: text.to_string() } );
+    }
+
+
+    fn add_song(&mut self, song: &Song) {
+        self.new_page();
+
+        let metadata = &song.metadata;
+        self.add_text(MARGIN, TITLE_SIZE, Shade::Text, &metadata.title);
+        self.add_text(MARGIN, ARTIST_SIZE, Shade::Notes, &metadata.artist);
+
+        let mut info = Vec::new();
+        if let Some(key) = metadata.key { info.push(format!("Key: {key}")) }
+        if let Some(capo) = metadata.capo { info.push(format!("Capo: {capo}")) }
+        if let Some(tempo) = metadata.tempo { info.push(format!("Tempo: {tempo}")) }
+        if let Some(time) = &metadata.time { info.push(format!("Time: {time}")) }
+        if let Some(tuning) = &metadata.tuning { info.push(format!("Tuning: {tuning}")) }
+        if !info.is_empty() {
+            self.add_text(MARGIN, INFO_SIZE, Shade::Notes, &info.join("   "));
+        }
+        self.y -= INFO_SIZE;
+
+        if self.options.chords && let Some(instrument) = self.options.fingerings {
+            self.add_diagrams(&song.get_fingerings_for_showing(instrument));
+        }
+        // Под аппликатурами не осталось места даже для строки
+        if self.y - TEXT_SIZE * LINE_HEIGHT < MARGIN { self.new_page() }
+        self.top = self.y;
+
+
+        let mut blocks: Vec<Vec<(String, Shade)>> = Vec::new();
+        if self.options.notes && let Some(notes) = &song.notes {
+            blocks.push( notes.lines().map(|l| (l.to_string(), Shade::Notes)).collect() );
+        }
+        for block in &song.blocks {
+            blocks.push( get_block_lines(block, self.options) );
+        }
+
+        // Самая длинная строка должна поместиться в колонку
+        let max_chars = blocks.iter()
+            .flatten()
+            .map(|(text, _)| text.chars().count())
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        let size = (self.column_width() / (max_chars as f32 * CHAR_WIDTH))
+            .clamp(MIN_TEXT_SIZE, TEXT_SIZE);
+        let line_height = size * LINE_HEIGHT;
+
+        for lines in blocks {
+            if lines.is_empty() { continue }
+            let height = lines.len() as f32 * line_height;
+            let column_height = self.top - MARGIN;
+            // Блок переносится целиком, если он вообще помещается в колонку
+            if self.y - height < MARGIN && height <= column_height && self.y < self.top {
+                self.next_column();
+            }
+
+            for (text, shade) in lines {
+                if self.y - line_height < MARGIN { self.next_column() }
+                let x = self.column_x();
+                self.add_text(x, size, shade, &text);
+            }
+            self.y -= line_height / 2.0;
+        }
+    }
+
+
+    // Аппликатуры в ряд на всю ширину страницы, под заголовком.
+    // Ряд, который не помещается до низа страницы, переносится на новую
+    fn add_diagrams(&mut self, fingerings: &[Fingering]) {
+        let mut rows: Vec<Vec<(&Fingering, f32, f32)>> = Vec::new();
+        let mut x = PAGE_WIDTH; // первая аппликатура начинает ряд
+        for fing in fingerings {
+            let (width, height) = get_diagram_size(fing);
+            if x > MARGIN && x + width > PAGE_WIDTH - MARGIN {
+                rows.push(Vec::new());
+                x = MARGIN;
+            }
+            if let Some(row) = rows.last_mut() { row.push( (fing, width, height) ) }
+            x += width;
+        }
+
+        for row in rows {
+            let row_height = row.iter().map(|(_, _, h)| *h).fold(0.0, f32::max);
+            if self.y - row_height < MARGIN && self.y < PAGE_HEIGHT - MARGIN { self.new_page() }
+
+            let mut x = MARGIN;
+            for (fing, width, _) in row {
+                let elements = get_diagram(fing, x, self.y);
+                for element in elements { self.push(element) }
+                x += width;
+            }
+            self.y -= row_height;
+        }
+    }
+}
+
+
+fn get_block_lines(block: &Block, options: &PdfOptions) -> Vec<(String, Shade)> {
+    let mut lines = Vec::new();
+    if let Some(title) = &block.title {
+        lines.push( (title.clone(), Shade::Title) );
+    }
+    if options.notes && let Some(notes) = &block.notes {
+        for line in notes.lines() {
+            lines.push( (line.to_string(), Shade::Notes) );
+        }
+    }
+
+    for line in &block.lines {
+        match line {
+            Line::TextBlock(row) => {
+                let (chords, rhythm, text) = row.get_strings();
+                if options.chords && !chords.trim().is_empty() {
+                    lines.push( (chords, Shade::Chords) );
+                }
+                if options.rhythm && !rhythm.trim().is_empty() {
+                    lines.push( (rhythm, Shade::Rhythm) );
+                }
+                if !text.is_empty() { lines.push( (text, Shade::Text) ) }
+            },
+            Line::ChordsLine(chords) => if options.chords {
+                let text = chords.iter()
+                    .map(|c| c.text.clone())
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                lines.push( (text, Shade::Chords) );
+            },
+            Line::PlainText(text) | Line::Tab(text) => {
+                for l in text.lines() {
+                    lines.push( (l.to_string(), Shade::Text) );
+                }
+            },
+            Line::EmptyLine => lines.push( (String::new(), Shade::Text) )
+        }
+    }
+
+    return lines
+}
+
+
+fn get_diagram_size(fing: &Fingering) -> (f32, f32) {
+    let strings = fing.get_strings().len() as f32;
+    let frets = fing.get_chord_size().max(MIN_FRETS) as f32;
+
+    // слева и справа место под номер лада и отступ
+    let width = (strings - 1.0) * STRING_SPACING + 28.0;
+    let height = 12.0 + 8.0 + frets * FRET_HEIGHT + 12.0;
+    (width, height)
+}
+
+// Струны слева направо от самой низкой, как в get_text
+fn get_diagram(fing: &Fingering, x: f32, top: f32) -> Vec<Element> {
+    let mut elements = Vec::new();
+    let strings: Vec<StringState> = fing.get_strings().iter().rev().copied().collect();
+    let count = strings.len();
+    let frets = fing.get_chord_size().max(MIN_FRETS);
+    let fret_num = fing.get_fret_num();
+    let grid_width = (count - 1) as f32 * STRING_SPACING;
+    let left = x + 6.0;
+    let string_x = |i: usize| left + i as f32 * STRING_SPACING;
+
+    let title = fing.get_title().unwrap_or_default();
+    let title_x = left + grid_width / 2.0 - title.chars().count() as f32 * INFO_SIZE * CHAR_WIDTH / 2.0;
+    elements.push( Element::Text { x: title_x, y: top - INFO_SIZE, size: INFO_SIZE, shade: Shade::Chords, text: title } );
+
+    // Открытые и заглушенные струны над грифом
+    let grid_top = top - 20.0;
+    for (i, s) in strings.iter().enumerate() {
+        let mark = match s {
+            StringState::Open => "o",
+            StringState::Muted => "x",
+            StringState::FrettedOn(_) => continue
+        };
+        elements.push( Element::Text { x: string_x(i) - 2.0, y: grid_top + 2.5, size: 7.0, shade: Shade::Text, text: mark.to_string() } );
+    }
+
+    let grid_bottom = grid_top - frets as f32 * FRET_HEIGHT;
+    for i in 0..count {
+        elements.push( Element::Line { points: vec![(string_x(i), grid_top), (string_x(i), grid_bottom)], width: 0.5 } );
+    }
+    for fret in 0..=frets {
+        let y = grid_top - fret as f32 * FRET_HEIGHT;
+        let is_nut = fret == 0 && (fret_num == 1 || fing.get_chord_size() == 0);
+        elements.push( Element::Line { points: vec![(left, y), (left + grid_width, y)], width: if is_nut { 2.0 } else { 0.5 } } );
+    }
+    if fret_num > 1 && fing.get_chord_size() > 0 {
+        elements.push( Element::Text { x: left + grid_width + 3.0, y: grid_top - FRET_HEIGHT + 2.0, size: 7.0, shade: Shade::Text, text: fret_num.to_string() } );
+    }
+
+    // Баррэ - от первой струны до верхушки
+    let mut barred: Vec<(u8, usize)> = Vec::new(); // лад, первая (слева) струна под баррэ
+    if let Some(bars) = fing.get_bars() {
+        for (fret, head) in bars {
+            let from = count - 1 - *head as usize;
+            let y = grid_top - ((fret - fret_num) as f32 + 0.5) * FRET_HEIGHT;
+            elements.push( Element::Line { points: vec![(string_x(from), y), (string_x(count - 1), y)], width: 5.0 } );
+            barred.push( (*fret, from) );
+        }
+    }
+    for (i, s) in strings.iter().enumerate() {
+        if let StringState::FrettedOn(f) = s
+            && !barred.iter().any(|(fret, from)| fret == f && i >= *from) {
+            let y = grid_top - ((f - fret_num) as f32 + 0.5) * FRET_HEIGHT;
+            elements.push( Element::Dot { x: string_x(i), y, radius: 2.8 } );
+        }
+    }
+
+    // Пальцы под грифом
+    let fingers: Vec<_> = fing.get_fingers().into_iter().rev().collect();
+    for (i, finger) in fingers.iter().enumerate() {
+        if let Some(finger) = finger {
+            elements.push( Element::Text { x: string_x(i) - 2.0, y: grid_bottom - 8.0, size: 7.0, shade: Shade::Notes, text: finger.get_char().to_string() } );
+        }
+    }
+
+    return elements
+}
+
+
+// Оглавление с номерами страниц, номера с учётом самого оглавления
+fn get_contents(entries: &[String], starts: &[usize]) -> Vec<Vec<Element>> {
+    let line_height = TEXT_SIZE * LINE_HEIGHT;
+    let first_page_lines = ((PAGE_HEIGHT - MARGIN * 2.0 - TITLE_SIZE * 2.0 * LINE_HEIGHT) / line_height) as usize;
+    let other_pages_lines = ((PAGE_HEIGHT - MARGIN * 2.0) / line_height) as usize;
+    let pages_count = if entries.len() <= first_page_lines { 1 }
+        else { 1 + (entries.len() - first_page_lines).div_ceil(other_pages_lines) };
+
+    let max_chars = ((PAGE_WIDTH - MARGIN * 2.0) / (TEXT_SIZE * CHAR_WIDTH)) as usize;
+    let mut pages: Vec<Vec<Element>> = vec![Vec::new()];
+    let mut y = PAGE_HEIGHT - MARGIN - TITLE_SIZE * LINE_HEIGHT;
+    pages[0].push( Element::Text { x: MARGIN, y, size: TITLE_SIZE, shade: Shade::Text, text: "Contents".to_string() } );
+    y -= TITLE_SIZE * LINE_HEIGHT;
+
+    for (entry, start) in entries.iter().zip(starts) {
+        if y - line_height < MARGIN {
+            pages.push(Vec::new());
+            y = PAGE_HEIGHT - MARGIN;
+        }
+        y -= line_height;
+
+        // "Artist - Title ..... 3"
+        let number = (start + pages_count + 1).to_string();
+        let name_len = max_chars.saturating_sub(number.len() + 2);
+        let mut name: String = entry.chars().take(name_len).collect();
+        let dots = max_chars.saturating_sub(name.chars().count() + number.len() + 2);
+        name.push(' ');
+        name.push_str(&".".repeat(dots));
+        name.push(' ');
+        name.push_str(&number);
+
+        if let Some(page) = pages.last_mut() {
+            page.push( Element::Text { x: MARGIN, y, size: TEXT_SIZE, shade: Shade::Text, text: name } );
+        }
+    }
+
+    return pages
+}
+
+fn add_page_numbers(pages: &mut [Vec<Element>]) {
+    for (i, page) in pages.iter_mut().enumerate() {
+        let text = (i + 1).to_string();
+        let x = PAGE_WIDTH / 2.0 - text.len() as f32 * INFO_SIZE * CHAR_WIDTH / 2.0;
+        page.push( Element::Text { x, y: MARGIN / 2.0, size: INFO_SIZE, shade: Shade::Notes, text } );
+    }
+}
+
+
+fn draw(layer: &PdfLayerReference, font: &IndirectFontRef, element: &Element) {
+    let black = Color::Rgb( Rgb::new(0.0, 0.0, 0.0, None) );
+    match element {
+        Element::Text { x, y, size, shade, text } => {
+            if text.is_empty() { return }
+            layer.set_fill_color( get_color(*shade) );
+            layer.use_text(text.as_str(), *size, Mm::from(Pt(*x)), Mm::from(Pt(*y)), font);
+        },
+        Element::Line { points, width } => {
+            layer.set_outline_color(black);
+            layer.set_outline_thickness(*width);
+            layer.add_line( printpdf::Line {
+                points: points.iter()
+                    .map(|(x, y)| (Point::new(Mm::from(Pt(*x)), Mm::from(Pt(*y))), false))
+                    .collect(),
+                is_closed: false
+            } );
+        },
+        Element::Dot { x, y, radius } => {
+            layer.set_fill_color(black);
+            layer.add_polygon( Polygon {
+                rings: vec![calculate_points_for_circle(Pt(*radius), Pt(*x), Pt(*y))],
+                mode: PaintMode::Fill,
+                winding_order: WindingOrder::NonZero
+            } );
+        }
+    }
+}
+
+// Цвета как в терминале, но темнее - для белой бумаги
+fn get_color(shade: Shade) -> Color {
+    let (r, g, b) = match shade {
+        Shade::Text => (0.0, 0.0, 0.0),
+        Shade::Title => (0.0, 0.4, 0.0),
+        Shade::Chords => (0.0, 0.3, 0.6),
+        Shade::Rhythm => (0.7, 0.45, 0.0),
+        Shade::Notes => (0.45, 0.45, 0.45)
+    };
+
+    Color::Rgb( Rgb::new(r, g, b, None) )
+}
@@ -0,0 +1,179 @@
+use std::io::{Cursor, Write};
+
+use anyhow::Result;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::Key;
+use crate::song::{
+    Song,
+    block::{Block, Line},
+    row::{Row, ChordPosition}
+};
+use crate::file_reader::sbp_reader::SbpSet;
+use super::chordpro_writer::write_inline_chords;
+
+
+// Эти 3 байта sbp_reader отрезает перед JSON
+const SBP_PREFIX: &str = "1.0";
+
+
+#[derive(serde::Serialize, Debug)]
+struct SongbookPro {
+    songs: Vec<SbpSong>,
+    folders: Vec<SbpFolder>,
+    sets: Vec<SbpSetJson>,
+}
+
+#[allow(non_snake_case)]
+#[derive(serde::Serialize, Debug)]
+struct SbpSong {
+    Id: usize,
+    author: String,
+    name: String,
+    content: String,
+    key: u8,
+    KeyShift: u8,
+    Capo: u8,
+    NotesText: String,
+    TimeSig: String,
+    Tempo: u16,
+    Copyright: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    Folder: Option<usize>,
+}
+
+#[allow(non_snake_case)]
+#[derive(serde::Serialize, Debug)]
+struct SbpFolder {
+    Id: usize,
+    Name: String,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct SbpSetJson {
+    name: String,
+    contents: Vec<SbpSetItem>,
+}
+
+#[allow(non_snake_case)]
+#[derive(serde::Serialize, Debug)]
+struct SbpSetItem {
+    SongId: usize,
+    KeyShift: i32,
+}
+
+
+// Zip-архив с dataFile.txt, как у бэкапа SongbookPro.
+// Песни с папками и номера песен в сетах - как их возвращает sbp_reader
+pub fn write_to_sbp(songs: &[(Song, Option<String>)], sets: &[SbpSet]) -> Result<Vec<u8>> {
+    let mut folders: Vec<SbpFolder> = Vec::new();
+    let mut sbp_songs = Vec::new();
+    for (i, (song, folder)) in songs.iter().enumerate() {
+        let folder_id = folder.as_ref().map(|name| {
+            match folders.iter().find(|f| f.Name == *name) {
+                Some(f) => f.Id,
+                None => {
+                    folders.push( SbpFolder { Id: folders.len() + 1, Name: name.clone() } );
+                    folders.len()
+                }
+            }
+        });
+        sbp_songs.push( SbpSong { Folder: folder_id, ..convert_song(i + 1, song) } );
+    }
+
+    let sbp = SongbookPro {
+        songs: sbp_songs,
+        folders,
+        sets: sets.iter()
+            .map(|set| SbpSetJson {
+                name: set.name.clone(),
+                contents: set.songs.iter()
+                    .filter(|(index, _)| *index < songs.len())
+                    .map(|(index, shift)| SbpSetItem { SongId: index + 1, KeyShift: shift.unwrap_or(0) })
+                    .collect()
+            })
+            .collect()
+    };
+    let data = format!("{SBP_PREFIX}{}", serde_json::to_string(&sbp)?);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("dataFile.txt", SimpleFileOptions::default())?;
+    zip.write_all(data.as_bytes())?;
+
+
+    Ok( zip.finish()?.into_inner() )
+}
+
+
+fn convert_song(id: usize, song: &Song) -> SbpSong {
+    SbpSong {
+        Id: id,
+        author: song.metadata.artist.clone(),
+        name: song.metadata.title.clone(),
+        content: convert_content(&song.blocks),
+        // SongbookPro не знает песен без тональности
+        key: song.metadata.key.map(get_key_index).unwrap_or(0),
+        KeyShift: song.metadata.key_shift.map(|k| k.rem_euclid(12) as u8).unwrap_or(0),
+        Capo: song.metadata.capo.unwrap_or(0),
+        NotesText: song.notes.clone().unwrap_or_default(),
+        TimeSig: song.metadata.time.clone().unwrap_or_default(),
+        Tempo: song.metadata.tempo.unwrap_or(0),
+        Copyright: song.metadata.copyright.clone().unwrap_or_default(),
+        Folder: None
+    }
+}
+
+// 0..11 - мажор от A, 12..23 - минор от A
+fn get_key_index(key: Key) -> u8 {
+    let index = key.get_note() as u8;
+    if key.is_minor() { index + 12 } else { index }
+}
+
+fn convert_content(blocks: &[Block]) -> String {
+    let mut s = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 { s.push('\n') }
+        // Первый блок без заголовка идёт до первого {c:}
+        if i > 0 || block.title.is_some() {
+            s.push_str(&format!("{{c: {}}}\n", block.title.as_deref().unwrap_or("")));
+        }
+        if let Some(notes) = &block.notes {
+            for line in notes.lines() { s.push_str(&format!("({line})\n")) }
+        }
+
+        for line in &block.lines {
+            match line {
+                Line::TextBlock(row) => write_row(row, &mut s),
+                Line::ChordsLine(chords) => {
+                    let line: Vec<String> = chords.iter().map(|c| format!("[{}]", c.text)).collect();
+                    s.push_str(&line.join(" "));
+                    s.push('\n');
+                },
+                Line::PlainText(text) => {
+                    for l in text.lines() {
+                        s.push_str(l);
+                        s.push('\n');
+                    }
+                },
+                Line::Tab(text) => s.push_str(&format!("{{sot}}\n{text}\n{{eot}}\n")),
+                Line::EmptyLine => s.push('\n')
+            }
+        }
+    }
+
+    return s
+}
+
+// Затакт в SongbookPro не записать, такие аккорды ставятся в начало строки
+fn write_row(row: &Row, s: &mut String) {
+    let mut on_index: Vec<(usize, &str)> = row.chords.iter()
+        .flatten()
+        .map(|chord| match chord {
+            ChordPosition::UpBeat(c) => (0, c.text.as_str()),
+            ChordPosition::OnIndex{ index, chord } => (*index, chord.text.as_str())
+        })
+        .collect();
+    on_index.sort_by_key(|(index, _)| *index);
+
+    write_inline_chords(row.text.as_deref().unwrap_or(""), &on_index, s);
+}
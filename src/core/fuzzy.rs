@@ -0,0 +1,152 @@
+// Нечёткий поиск: регистр, ё/е и диакритика не важны, опечатки и пропущенные буквы допускаются
+
+
+// Буквы с диакритикой, которые приводятся к базовой
+const DIACRITICS: [(&str, char); 14] = [
+    ("áàâäãåāăą", 'a'),
+    ("çćčĉ", 'c'),
+    ("ďđ", 'd'),
+    ("éèêëēėęě", 'e'),
+    ("ğĝ", 'g'),
+    ("íìîïīį", 'i'),
+    ("ł", 'l'),
+    ("ñńňņ", 'n'),
+    ("óòôöõøōő", 'o'),
+    ("řŕ", 'r'),
+    ("śšşŝ", 's'),
+    ("úùûüūůű", 'u'),
+    ("ýÿ", 'y'),
+    ("žźż", 'z')
+];
+
+
+// Строка для сравнения: нижний регистр, ё -> е, é -> e, ß -> ss
+pub fn fold(text: &str) -> String {
+    let mut s = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c {
+            'ё' => s.push('е'),
+            'ß' => s.push_str("ss"),
+            c if c.is_ascii() => s.push(c),
+            c => s.push(
+                DIACRITICS.iter()
+                    .find(|(letters, _)| letters.contains(c))
+                    .map(|(_, base)| *base)
+                    .unwrap_or(c)
+            )
+        }
+    }
+
+    return s
+}
+
+// Для коротких строк (название, исполнитель): подстрока, слово с опечаткой
+// или буквы запроса по порядку (nrvn -> Nirvana). Больше - лучше, None - не подходит
+pub fn score(query: &str, text: &str) -> Option<u32> {
+    let (query, text) = (fold(query), fold(text));
+    let words: Vec<&str> = query.split_whitespace().collect();
+    if words.is_empty() { return None }
+
+    if let Some(s) = get_substring_score(&query, &text) { return Some(s) }
+    // Каждое слово запроса должно найтись
+    let mut total = 0;
+    for word in &words {
+        total += get_substring_score(word, &text)
+            .or_else(|| get_typo_score(word, &text))
+            .or_else(|| get_subsequence_score(word, &text))?;
+    }
+
+    Some( total / words.len() as u32 )
+}
+
+// Для длинного текста (слова песни, заметки) буквы по порядку найдутся почти всегда,
+// поэтому только подстрока или слово с опечаткой
+pub fn score_words(query: &str, text: &str) -> Option<u32> {
+    let (query, text) = (fold(query), fold(text));
+    let words: Vec<&str> = query.split_whitespace().collect();
+    if words.is_empty() { return None }
+
+    if let Some(s) = get_substring_score(&query, &text) { return Some(s) }
+    let mut total = 0;
+    for word in &words {
+        total += get_substring_score(word, &text).or_else(|| get_typo_score(word, &text))?;
+    }
+
+    Some( total / words.len() as u32 )
+}
+
+
+fn get_substring_score(query: &str, text: &str) -> Option<u32> {
+    let start = text.find(query)?;
+    if text == query { return Some(1000) }
+    if start == 0 { return Some(900) }
+
+    let is_word_start = text[..start].chars().next_back().is_none_or(|c| !c.is_alphanumeric());
+    Some( if is_word_start { 800 } else { 700 } )
+}
+
+// Слово текста, отличающееся от слова запроса на 1-2 буквы
+fn get_typo_score(word: &str, text: &str) -> Option<u32> {
+    let length = word.chars().count();
+    let max_distance = match length {
+        0..=3 => return None,
+        4..=6 => 1,
+        _ => 2
+    };
+
+    let word: Vec<char> = word.chars().collect();
+    let distance = text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count().abs_diff(length) <= max_distance)
+        .map(|w| get_distance(&word, &w.chars().collect::<Vec<char>>()))
+        .min()
+        .filter(|d| *d <= max_distance)?;
+
+    Some( 600 - 100 * distance as u32 )
+}
+
+// Буквы запроса по порядку, подряд идущие и в начале слов - лучше
+fn get_subsequence_score(word: &str, text: &str) -> Option<u32> {
+    let mut score: i32 = 300;
+    let mut chars = text.chars().enumerate().peekable();
+    let mut last: Option<usize> = None;
+    let mut previous = ' ';
+    for q in word.chars() {
+        loop {
+            let (i, c) = chars.next()?;
+            let before = previous;
+            previous = c;
+            if c != q { continue }
+
+            match last {
+                Some(l) if l + 1 == i => score += 15,
+                Some(l) => score -= (i - l - 1).min(10) as i32 * 3,
+                None => score -= i.min(10) as i32 * 2
+            }
+            if !before.is_alphanumeric() { score += 10 }
+            last = Some(i);
+            break
+        }
+    }
+
+    Some( score.clamp(1, 499) as u32 )
+}
+
+// Расстояние Дамерау-Левенштейна: перестановка соседних букв - одна ошибка
+fn get_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows: Vec<Vec<usize>> = vec![ (0..=b.len()).collect() ];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+
+    return rows[a.len()][b.len()]
+}
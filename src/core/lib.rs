@@ -1,6 +1,9 @@
 mod file_reader;
+mod file_writer;
 pub mod chord_generator;
 pub mod song;
+pub mod setlist;
+pub mod fuzzy;
 
 #[cfg(feature = "song_library")]
 pub mod song_library;
@@ -11,16 +14,23 @@ use serde::{Serialize, Deserialize};
 use crossterm::style::Color;
 
 use crate::Note::*;
-pub use crate::chord_generator::chord_fingerings::Fingering;
+pub use crate::chord_generator::chord_fingerings::{Fingering, Finger};
 pub use crate::chord_generator::chord_fingerings::StringState::{self, *};
 pub use crate::chord_generator::chord_fingerings::sum_text_in_fingerings;
 pub use crate::chord_generator::get_fretboard;
-pub use crate::chord_generator::STRINGS;
-pub use crate::song::{Song, Metadata};
-pub use crate::song::chord::Chord;
+pub use crate::chord_generator::chord_identifier::identify;
+pub use crate::song::{Song, Metadata, ChordDiagnostic};
+pub use crate::file_reader::ChordProSong;
+pub use crate::file_reader::sbp_reader::{SbpBackup, SbpSet};
+pub use crate::setlist::{Setlist, SetlistEntry};
+pub use crate::file_writer::sbp_writer::write_to_sbp;
+#[cfg(feature = "pdf")]
+pub use crate::file_writer::pdf_writer::{PdfOptions, write_to_pdf};
+pub use crate::song::chord::{Chord, ChordParseError};
+pub use uuid::Uuid;
 
 
-pub const STANDART_TUNING: [Note; STRINGS] = [E, B, G, D, A, E];
+pub const STANDART_TUNING: [Note; 6] = [E, B, G, D, A, E];
 
 
 const METADATA_START: &str = "{metadata:}";
@@ -30,7 +40,21 @@ const SONG_TITLE_SYMBOL: &str = "{song_title:} ";
 const SONG_ARTIST_SYMBOL: &str = "{song_artist:} ";
 const SONG_KEY_SYMBOL: &str = "{song_key:} ";
 const SONG_CAPO_SYMBOL: &str = "{song_capo:} ";
+const SONG_TEMPO_SYMBOL: &str = "{song_tempo:} ";
+const SONG_TIME_SYMBOL: &str = "{song_time:} ";
+const SONG_KEY_SHIFT_SYMBOL: &str = "{song_key_shift:} ";
+const SONG_COPYRIGHT_SYMBOL: &str = "{song_copyright:} ";
 const SONG_AUTOSCROLL_SPEED_SYMBOL: &str = "{song_autoscroll_speed:} ";
+const SONG_SPELLING_SYMBOL: &str = "{song_spelling:} ";
+const SONG_TUNING_SYMBOL: &str = "{song_tuning:} ";
+const SONG_ALBUM_SYMBOL: &str = "{song_album:} ";
+const SONG_YEAR_SYMBOL: &str = "{song_year:} ";
+const SONG_COMPOSER_SYMBOL: &str = "{song_composer:} ";
+const SONG_LYRICIST_SYMBOL: &str = "{song_lyricist:} ";
+const SONG_CCLI_SYMBOL: &str = "{song_ccli:} ";
+const SONG_LANGUAGE_SYMBOL: &str = "{song_language:} ";
+const SONG_URL_SYMBOL: &str = "{song_url:} ";
+const SONG_TAGS_SYMBOL: &str = "{song_tags:} ";
 
 
 const BLOCK_START: &str = "{block:}";
@@ -80,110 +104,364 @@ const KEYS: [[Note; 6]; 12] = [
 
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Mode {
+    Major,
+    Minor
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(from = "KeyData")]
 pub struct Key {
-    keynote: Note,
-    is_minor: bool
+    tonic: Note,
+    mode: Mode
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyData {
+    Tonic { tonic: Note, mode: Mode },
+    // Старый формат: keynote - всегда мажорная тоника,
+    // is_minor - значит на самом деле параллельный минор (C + is_minor = Am)
+    Relative { keynote: Note, is_minor: bool }
+}
+
+impl From<KeyData> for Key {
+    fn from(data: KeyData) -> Self {
+        match data {
+            KeyData::Tonic { tonic, mode } => Self { tonic, mode },
+            KeyData::Relative { keynote, is_minor: false } => Self::from_note(keynote),
+            KeyData::Relative { keynote, is_minor: true } =>
+                Self { tonic: keynote.transpose(-3), mode: Mode::Minor }
+        }
+    }
 }
 
 impl Key {
     pub fn new(text: &str) -> Option<Self> {
-        Some( match text.to_lowercase().as_str() {
-            "c" => Self { keynote: Note::new("C")?, is_minor: false },
-            "am" => Self { keynote: Note::new("C")?, is_minor: true },
+        let text = text.trim().to_lowercase();
+        let (note_text, mode) =
+            if let Some(n) = text.strip_suffix("minor") { (n, Mode::Minor) }
+            else if let Some(n) = text.strip_suffix("min") { (n, Mode::Minor) }
+            else if let Some(n) = text.strip_suffix("major") { (n, Mode::Major) }
+            else if let Some(n) = text.strip_suffix("maj") { (n, Mode::Major) }
+            else if let Some(n) = text.strip_suffix('m') { (n, Mode::Minor) }
+            else { (text.as_str(), Mode::Major) };
+
+        // Note::new ждёт "Bb", а не "bb" или "BB"
+        let mut chars = note_text.trim().chars();
+        let note_text: String = chars.next()?.to_uppercase().chain(chars).collect();
+
+        Some( Self { tonic: Note::new(&note_text)?, mode } )
+    }
 
-            "c#" | "db" => Self { keynote: Note::new("C#")?, is_minor: false },
-            "a#m" | "bbm" => Self { keynote: Note::new("C#")?, is_minor: true },
+    pub fn from_note(note: Note) -> Self {
+        Self { tonic: note, mode: Mode::Major }
+    }
 
-            "d" => Self { keynote: Note::new("D")?, is_minor: false },
-            "bm" => Self { keynote: Note::new("D")?, is_minor: true },
+    pub fn with_mode(tonic: Note, mode: Mode) -> Self {
+        Self { tonic, mode }
+    }
 
-            "d#" | "eb" => Self { keynote: Note::new("D#")?, is_minor: false },
-            "cm" => Self { keynote: Note::new("D#")?, is_minor: true },
+    pub fn transpose(&self, steps: i32) -> Self {
+        Self { tonic: self.tonic.transpose(steps), mode: self.mode }
+    }
 
-            "e" => Self { keynote: Note::new("E")?, is_minor: false },
-            "c#m" | "dbm" => Self { keynote: Note::new("E")?, is_minor: true },
+    pub fn get_note(&self) -> Note {
+        self.tonic
+    }
 
-            "f" => Self { keynote: Note::new("F")?, is_minor: false },
-            "dm" => Self { keynote: Note::new("F")?, is_minor: true },
+    pub fn get_mode(&self) -> Mode {
+        self.mode
+    }
 
-            "f#" | "gb" => Self { keynote: Note::new("F#")?, is_minor: false },
-            "d#m" | "ebm" => Self { keynote: Note::new("F#")?, is_minor: true },
+    pub fn is_minor(&self) -> bool {
+        self.mode == Mode::Minor
+    }
 
-            "g" => Self { keynote: Note::new("G")?, is_minor: false },
-            "em" => Self { keynote: Note::new("G")?, is_minor: true },
+    // Для минора - параллельный мажор (Am -> C)
+    pub fn get_relative_major(&self) -> Note {
+        match self.mode {
+            Mode::Major => self.tonic,
+            Mode::Minor => self.tonic.transpose(3)
+        }
+    }
 
-            "g#" | "ab" => Self { keynote: Note::new("G#")?, is_minor: false },
-            "fm"  => Self { keynote: Note::new("G#")?, is_minor: true },
+    // Сколько полутонов нужно транспонировать, чтобы попасть в other,
+    // если лады разные то сравниваются параллельные мажоры
+    pub fn steps_to(&self, other: &Key) -> i32 {
+        let (from, to) = if self.mode == other.mode { (self.tonic, other.tonic) }
+            else { (self.get_relative_major(), other.get_relative_major()) };
 
-            "a" => Self { keynote: Note::new("A")?, is_minor: false },
-            "f#m" | "gbm" => Self { keynote: Note::new("A")?, is_minor: true },
+        let mut steps = 0;
+        while from.transpose(steps) != to { steps += 1 }
+        if steps > 6 { steps - 12 } else { steps }
+    }
 
-            "a#" | "bb" => Self { keynote: Note::new("A#")?, is_minor: false },
-            "gm" => Self { keynote: Note::new("A#")?, is_minor: true },
+    // Ступени лада в полутонах от тоники и являются ли аккорды на них минорными
+    fn get_degrees(&self) -> [(i32, bool); 7] {
+        match self.mode {
+            Mode::Major => [(0, false), (2, true), (4, true), (5, false), (7, false), (9, true), (11, true)],
+            Mode::Minor => [(0, true), (2, true), (3, false), (5, true), (7, true), (8, false), (10, false)]
+        }
+    }
 
-            "b" => Self { keynote: Note::new("B")?, is_minor: false },
-            "g#m" | "abm" => Self { keynote: Note::new("B")?, is_minor: true },
-            _ => return None
-        })
+    // Насколько аккорд подходит тональности: 1.0 - аккорд ступени,
+    // 0.5 - нота из лада, но другое наклонение, 0.0 - чужой аккорд
+    pub fn get_chord_fitness(&self, keynote: Note, is_minor: bool) -> f32 {
+        for (steps, degree_is_minor) in self.get_degrees() {
+            if self.tonic.transpose(steps) != keynote { continue }
+
+            // мажорная доминанта в миноре (гармонический минор)
+            let is_harmonic_dominant = self.mode == Mode::Minor && steps == 7 && !is_minor;
+            return if degree_is_minor == is_minor || is_harmonic_dominant { 1.0 }
+                else { 0.5 }
+        }
+
+        0.0
     }
 
-    pub fn from_note(note: Note) -> Self {
-        Self { keynote: note, is_minor: false }
+    // Бемольные тональности: F, Bb, Eb, Ab, Db и их параллельные минорные
+    pub fn get_spelling(&self) -> Spelling {
+        match self.get_relative_major() {
+            F | ASharp | DSharp | GSharp | CSharp => Spelling::Flats,
+            _ => Spelling::Sharps
+        }
     }
+}
 
-    pub fn transpose(&self, steps: i32) -> Self {
-        Self { keynote: self.keynote.transpose(steps), is_minor: self.is_minor }
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = self.tonic.get_text_in(self.get_spelling());
+        if self.mode == Mode::Minor { s.push('m') }
+
+        write!(f, "{}", s)
     }
+}
 
-    pub fn get_note(&self) -> Note {
-        self.keynote
+
+// Как писать альтерированные ноты: A# или Bb
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Spelling {
+    Sharps,
+    Flats
+}
+
+impl Spelling {
+    pub fn new(text: &str) -> Option<Self> {
+        Some( match text.to_lowercase().as_str() {
+            "sharps" | "sharp" | "#" => Spelling::Sharps,
+            "flats" | "flat" | "b" => Spelling::Flats,
+            _ => return None
+        } )
     }
 }
 
-impl fmt::Display for Key {
+impl fmt::Display for Spelling {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s = match self.keynote {
-            A if self.is_minor => String::from("F#m"),
-            A => self.keynote.get_text(),
+        match self {
+            Spelling::Sharps => write!(f, "sharps"),
+            Spelling::Flats => write!(f, "flats")
+        }
+    }
+}
 
-            ASharp if self.is_minor => String::from("Gm"),
-            ASharp => self.keynote.get_text(),
 
-            B if self.is_minor => String::from("G#m"),
-            B => self.keynote.get_text(),
+// Строй, ноты от первой (тонкой) струны к последней.
+// Пресеты - для шестиструнной гитары, в Custom может быть любое число струн
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum Tuning {
+    Standard,
+    DropD,
+    DoubleDropD,
+    DropC,
+    Dadgad,
+    OpenG,
+    OpenD,
+    OpenE,
+    HalfStepDown,
+    WholeStepDown,
+    Custom(Vec<Note>)
+}
 
-            C if self.is_minor => String::from("Am"),
-            C => self.keynote.get_text(),
+impl Tuning {
+    pub const PRESETS: [Tuning; 10] = [
+        Tuning::Standard,
+        Tuning::DropD,
+        Tuning::DoubleDropD,
+        Tuning::DropC,
+        Tuning::Dadgad,
+        Tuning::OpenG,
+        Tuning::OpenD,
+        Tuning::OpenE,
+        Tuning::HalfStepDown,
+        Tuning::WholeStepDown
+    ];
+
+    // Название пресета (drop-d, Open G, DADGAD) или ноты через запятую: E, B, G, D, A, E
+    pub fn new(text: &str) -> Option<Self> {
+        let name: String = text.chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_lowercase();
+        if let Some(preset) = Self::PRESETS.iter().find(|p| p.to_string().replace('-', "") == name) {
+            return Some(preset.clone())
+        }
 
-            CSharp if self.is_minor => String::from("A#m"),
-            CSharp => self.keynote.get_text(),
+        let mut notes = Vec::new();
+        for n in text.split(',').map(|n| n.trim()) {
+            notes.push(Note::new(n)?);
+        }
+        if Instrument::ALL.iter().all(|i| i.get_strings() != notes.len()) { return None }
+
+        return Some(
+            Self::PRESETS.iter()
+                .find(|p| p.get_notes() == notes)
+                .cloned()
+                .unwrap_or(Tuning::Custom(notes))
+        )
+    }
 
-            D if self.is_minor => String::from("Bm"),
-            D => self.keynote.get_text(),
+    pub fn get_notes(&self) -> Vec<Note> {
+        match self {
+            Tuning::Standard =>      STANDART_TUNING.to_vec(),
+            Tuning::DropD =>         vec![E, B, G, D, A, D],
+            Tuning::DoubleDropD =>   vec![D, B, G, D, A, D],
+            Tuning::DropC =>         vec![D, A, F, C, G, C],
+            Tuning::Dadgad =>        vec![D, A, G, D, A, D],
+            Tuning::OpenG =>         vec![D, B, G, D, G, D],
+            Tuning::OpenD =>         vec![D, A, FSharp, D, A, D],
+            Tuning::OpenE =>         vec![E, B, GSharp, E, B, E],
+            Tuning::HalfStepDown =>  vec![DSharp, ASharp, FSharp, CSharp, GSharp, DSharp],
+            Tuning::WholeStepDown => vec![D, A, F, C, G, D],
+            Tuning::Custom(notes) => notes.clone()
+        }
+    }
+}
 
-            DSharp if self.is_minor => String::from("Cm"),
-            DSharp => self.keynote.get_text(),
+impl fmt::Display for Tuning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tuning::Standard => write!(f, "standard"),
+            Tuning::DropD => write!(f, "drop-d"),
+            Tuning::DoubleDropD => write!(f, "double-drop-d"),
+            Tuning::DropC => write!(f, "drop-c"),
+            Tuning::Dadgad => write!(f, "dadgad"),
+            Tuning::OpenG => write!(f, "open-g"),
+            Tuning::OpenD => write!(f, "open-d"),
+            Tuning::OpenE => write!(f, "open-e"),
+            Tuning::HalfStepDown => write!(f, "half-step-down"),
+            Tuning::WholeStepDown => write!(f, "whole-step-down"),
+            Tuning::Custom(notes) => write!(f, "{}",
+                notes.iter().map(|n| n.get_text()).collect::<Vec<String>>().join(", ")
+            )
+        }
+    }
+}
 
-            E if self.is_minor => String::from("C#m"),
-            E => self.keynote.get_text(),
 
-            F if self.is_minor => String::from("Dm"),
-            F => self.keynote.get_text(),
 
-            FSharp if self.is_minor => String::from("D#m"),
-            FSharp => self.keynote.get_text(),
+// Инструмент, для которого строятся аппликатуры
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Instrument {
+    Guitar,
+    SevenStringGuitar,
+    Ukulele,
+    BaritoneUkulele,
+    Mandolin,
+    Bass,
+    FiveStringBass
+}
 
-            G if self.is_minor => String::from("Em"),
-            G => self.keynote.get_text(),
+impl Instrument {
+    pub const ALL: [Instrument; 7] = [
+        Instrument::Guitar,
+        Instrument::SevenStringGuitar,
+        Instrument::Ukulele,
+        Instrument::BaritoneUkulele,
+        Instrument::Mandolin,
+        Instrument::Bass,
+        Instrument::FiveStringBass
+    ];
 
-            GSharp if self.is_minor =>String::from("Fm"),
-            GSharp => self.keynote.get_text()
-        };
+    pub fn new(text: &str) -> Option<Self> {
+        let name: String = text.chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_lowercase();
+
+        Some( match name.as_str() {
+            "guitar" | "6string" => Instrument::Guitar,
+            "7string" | "sevenstring" | "7stringguitar" | "sevenstringguitar" => Instrument::SevenStringGuitar,
+            "ukulele" | "uke" => Instrument::Ukulele,
+            "baritoneukulele" | "baritoneuke" | "baritone" => Instrument::BaritoneUkulele,
+            "mandolin" => Instrument::Mandolin,
+            "bass" | "4stringbass" => Instrument::Bass,
+            "5stringbass" | "fivestringbass" => Instrument::FiveStringBass,
+            _ => return None
+        } )
+    }
 
-        write!(f, "{}", s)
+    // Стандартный строй, от первой струны к последней
+    pub fn get_tuning(&self) -> Vec<Note> {
+        match self {
+            Instrument::Guitar =>            STANDART_TUNING.to_vec(),
+            Instrument::SevenStringGuitar => vec![E, B, G, D, A, E, B],
+            Instrument::Ukulele =>           vec![A, E, C, G],
+            Instrument::BaritoneUkulele =>   vec![E, B, G, D],
+            Instrument::Mandolin =>          vec![E, A, D, G],
+            Instrument::Bass =>              vec![G, D, A, E],
+            Instrument::FiveStringBass =>    vec![G, D, A, E, B]
+        }
+    }
+
+    pub fn get_strings(&self) -> usize {
+        self.get_tuning().len()
+    }
+
+    pub fn get_frets(&self) -> u8 {
+        match self {
+            Instrument::Ukulele => 15,
+            Instrument::BaritoneUkulele => 18,
+            Instrument::Mandolin => 20,
+            _ => 24
+        }
+    }
+
+    // Сколько ладов можно охватить пальцами в одной аппликатуре
+    pub fn get_max_stretch(&self) -> u8 {
+        match self {
+            Instrument::Mandolin => 5, // лады короткие
+            _ => 4
+        }
+    }
+
+    // У укулеле четвёртая струна высокая, так что самая низкая нота не обязательно на ней
+    pub fn is_reentrant(&self) -> bool {
+        *self == Instrument::Ukulele
+    }
+
+    // Строй песни подходит, только если совпадает количество струн
+    pub fn get_tuning_for(&self, tuning: Option<&Tuning>) -> Vec<Note> {
+        match tuning.map(|t| t.get_notes()) {
+            Some(notes) if notes.len() == self.get_strings() => notes,
+            _ => self.get_tuning()
+        }
     }
 }
 
+impl fmt::Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instrument::Guitar => write!(f, "guitar"),
+            Instrument::SevenStringGuitar => write!(f, "7-string"),
+            Instrument::Ukulele => write!(f, "ukulele"),
+            Instrument::BaritoneUkulele => write!(f, "baritone-ukulele"),
+            Instrument::Mandolin => write!(f, "mandolin"),
+            Instrument::Bass => write!(f, "bass"),
+            Instrument::FiveStringBass => write!(f, "5-string-bass")
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Note {
@@ -241,10 +519,25 @@ impl Note {
         ).to_string()
     }
 
+    pub fn get_text_in(&self, spelling: Spelling) -> String {
+        if spelling == Spelling::Sharps { return self.get_text() }
+
+        (
+            match self {
+                ASharp => "Bb",
+                CSharp => "Db",
+                DSharp => "Eb",
+                FSharp => "Gb",
+                GSharp => "Ab",
+                _ => return self.get_text()
+            }
+        ).to_string()
+    }
+
     pub fn transpose(&self, steps: i32) -> Self {
         let steps = steps % 12;
-        if steps == 0 { return self.clone() }
-        let mut note = self.clone();
+        if steps == 0 { return *self }
+        let mut note = *self;
 
         if steps > 0 {
             for _ in 0..steps { note.increase() }
@@ -291,8 +584,8 @@ impl Note {
 
 
 
-pub fn print_fretboard(tuning: &[Note; STRINGS]) {
-    let fretboard = crate::chord_generator::get_fretboard(tuning);
+pub fn print_fretboard(tuning: &[Note], frets: u8) {
+    let fretboard = crate::chord_generator::get_fretboard(tuning, frets);
     let mut s = String::new();
 
     let note_width = 4;
@@ -345,12 +638,13 @@ pub fn print_circle_of_fifth(needed_key: Option<Key>) {
 
     let mut keys = BTreeMap::new();
     for k in KEYS {
-        let first = k[0].get_text();
-        let second = k[1].get_text() + "m";
-        let third = k[2].get_text() + "m";
-        let fourth = k[3].get_text();
-        let fifth = k[4].get_text();
-        let sixth = k[5].get_text() + "m";
+        let spelling = Key::from_note(k[0]).get_spelling();
+        let first = k[0].get_text_in(spelling);
+        let second = k[1].get_text_in(spelling) + "m";
+        let third = k[2].get_text_in(spelling) + "m";
+        let fourth = k[3].get_text_in(spelling);
+        let fifth = k[4].get_text_in(spelling);
+        let sixth = k[5].get_text_in(spelling) + "m";
         
         let width: usize = 5;
 
@@ -417,7 +711,7 @@ pub fn print_circle_of_fifth(needed_key: Option<Key>) {
 
 
     if let Some(needed_k) = needed_key {
-        if let Some( (f_line, s_line) ) = keys.get(&needed_k.get_note()) {
+        if let Some( (f_line, s_line) ) = keys.get(&needed_k.get_relative_major()) {
             println!("| {f_line}|\n| {s_line}|");
         }
     } else {
@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{Song, Key, Uuid};
+
+
+// Упорядоченный список песен для выступления
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Setlist {
+    pub name: String,
+    pub songs: Vec<SetlistEntry>
+}
+
+// Ссылка на песню библиотеки и настройки только для этого сет-листа
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetlistEntry {
+    pub path: PathBuf, // относительно библиотеки
+    #[serde(default)]
+    pub id: Option<Uuid>, // по нему песня находится, если путь изменился не через songbook
+    pub key_shift: Option<i32>, // транспонирование только в этом сет-листе
+    #[serde(default)]
+    pub key: Option<Key>, // тональность, важнее key_shift
+    #[serde(default)]
+    pub capo: Option<u8>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub autoscroll_speed: Option<u64> // in milliseconds
+}
+
+
+impl Setlist {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            songs: Vec::new()
+        }
+    }
+
+    // Номера с 1, как при показе
+    pub fn reorder(&mut self, from: usize, to: usize) -> bool {
+        if from == 0 || to == 0 || from > self.songs.len() || to > self.songs.len() { return false }
+
+        let entry = self.songs.remove(from - 1);
+        self.songs.insert(to - 1, entry);
+
+        return true
+    }
+}
+
+impl SetlistEntry {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            id: None,
+            key_shift: None,
+            key: None,
+            capo: None,
+            notes: None,
+            autoscroll_speed: None
+        }
+    }
+
+    // Настройки сет-листа для копии песни, аккорды меняются только при показе (key_shift)
+    pub fn apply(&self, song: &mut Song) {
+        let mut shift = song.metadata.key_shift.unwrap_or(0);
+        if let Some(key) = self.key && let Some(song_key) = song.metadata.key {
+            shift = song_key.steps_to(&key);
+        } else if let Some(s) = self.key_shift {
+            shift += s;
+        }
+        // С другим каподастром песня звучит так же, меняются только аккорды
+        if let Some(capo) = self.capo {
+            shift -= i32::from(capo) - i32::from(song.metadata.capo.unwrap_or(0));
+            song.metadata.capo = if capo == 0 { None } else { Some(capo) };
+        }
+        song.metadata.key_shift = if shift == 0 { None } else { Some(shift) };
+
+        if let Some(notes) = &self.notes {
+            song.notes = Some( match &song.notes {
+                Some(n) => format!("{notes}\n\n{n}"),
+                None => notes.clone()
+            } );
+        }
+        if let Some(speed) = self.autoscroll_speed {
+            song.metadata.autoscroll_speed = Some(speed);
+        }
+    }
+}
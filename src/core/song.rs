@@ -4,6 +4,7 @@ pub mod chord;
 
 use serde::{Serialize, Deserialize};
 use crossterm::style::Stylize;
+use uuid::Uuid;
 
 use crate::Fingering;
 use crate::{
@@ -13,12 +14,25 @@ use crate::{
     SONG_ARTIST_SYMBOL,
     SONG_KEY_SYMBOL,
     SONG_CAPO_SYMBOL,
+    SONG_TEMPO_SYMBOL,
+    SONG_TIME_SYMBOL,
+    SONG_KEY_SHIFT_SYMBOL,
+    SONG_COPYRIGHT_SYMBOL,
     SONG_AUTOSCROLL_SPEED_SYMBOL,
+    SONG_SPELLING_SYMBOL,
+    SONG_TUNING_SYMBOL,
+    SONG_ALBUM_SYMBOL,
+    SONG_YEAR_SYMBOL,
+    SONG_COMPOSER_SYMBOL,
+    SONG_LYRICIST_SYMBOL,
+    SONG_CCLI_SYMBOL,
+    SONG_LANGUAGE_SYMBOL,
+    SONG_URL_SYMBOL,
+    SONG_TAGS_SYMBOL,
 
     BLOCK_START,
     BLOCK_END,
-    STANDART_TUNING,
-    
+
     TITLE_COLOR,
     NOTES_COLOR,
 
@@ -27,15 +41,20 @@ use crate::{
 
     KEYS
 };
-use crate::{Note, Key};
+use crate::{Note, Key, Mode, Spelling, Tuning, Instrument};
 use crate::sum_text_in_fingerings;
-use crate::song::chord::Chord;
+use crate::fuzzy::fold;
+use crate::song::chord::{Chord, ChordParseError};
 use crate::song::block::{Block, Line};
 use crate::song::row::ChordPosition;
 
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Song {
+    // Не меняется при переименовании и переносе. У песен, сохранённых до появления id, - nil,
+    // пока индекс библиотеки их не перечитает
+    #[serde(default)]
+    pub id: Uuid,
     pub metadata: Metadata,
     pub chord_list: Vec<Chord>,
     pub blocks: Vec<Block>,
@@ -55,13 +74,45 @@ pub struct Song {
 // Gm - A#
 // G#m - B
 
+// Аккорд, который не удалось разобрать, и где он находится в песне
+#[derive(Debug, Clone)]
+pub struct ChordDiagnostic {
+    pub block: usize,  // номер блока, с нуля
+    pub line: usize,   // номер строки в блоке, с нуля
+    pub column: usize, // позиция аккорда в строке, с нуля
+    pub text: String,
+    pub error: ChordParseError
+}
+
+impl std::fmt::Display for ChordDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Block {}, line {}, column {}: '{}' - {}",
+            self.block + 1, self.line + 1, self.column + 1, self.text, self.error)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Metadata {
     pub title: String,
     pub artist: String,
     pub key: Option<Key>,
     pub capo: Option<u8>,
+    pub tempo: Option<u16>, // BPM
+    pub time: Option<String>, // размер, например 3/4
+    pub key_shift: Option<i32>, // транспонирование при показе, аккорды остаются в исходной тональности
+    pub copyright: Option<String>,
     pub autoscroll_speed: Option<u64>, // in milliseconds
+    pub spelling: Option<Spelling>, // если не указано, то берётся из тональности
+    pub tuning: Option<Tuning>, // если не указано, то стандартный строй
+    pub album: Option<String>,
+    pub year: Option<u16>,
+    pub composer: Option<String>,
+    pub lyricist: Option<String>,
+    pub ccli: Option<String>, // номер песни в CCLI
+    pub language: Option<String>,
+    pub url: Option<String>, // откуда взята песня
+    #[serde(default)]
+    pub tags: Vec<String> // свои метки: worship, slow, needs-practice
 }
 
 impl Metadata {
@@ -90,12 +141,95 @@ impl Metadata {
         }
         s.push('\n');
 
+        s.push_str(SONG_TEMPO_SYMBOL);
+        if let Some(tempo) = self.tempo {
+            s.push_str(&tempo.to_string())
+        }
+        s.push('\n');
+
+        s.push_str(SONG_TIME_SYMBOL);
+        if let Some(time) = &self.time {
+            s.push_str(time)
+        }
+        s.push('\n');
+
+        s.push_str(SONG_KEY_SHIFT_SYMBOL);
+        if let Some(shift) = self.key_shift {
+            s.push_str(&shift.to_string())
+        }
+        s.push('\n');
+
+        s.push_str(SONG_COPYRIGHT_SYMBOL);
+        if let Some(copyright) = &self.copyright {
+            s.push_str(copyright)
+        }
+        s.push('\n');
+
         s.push_str(SONG_AUTOSCROLL_SPEED_SYMBOL);
         if let Some(speed) = self.autoscroll_speed {
             s.push_str(&speed.to_string())
         }
         s.push('\n');
 
+        s.push_str(SONG_SPELLING_SYMBOL);
+        if let Some(spelling) = self.spelling {
+            s.push_str(&spelling.to_string())
+        }
+        s.push('\n');
+
+        s.push_str(SONG_TUNING_SYMBOL);
+        if let Some(tuning) = &self.tuning {
+            s.push_str(&tuning.to_string())
+        }
+        s.push('\n');
+
+        s.push_str(SONG_ALBUM_SYMBOL);
+        if let Some(album) = &self.album {
+            s.push_str(album)
+        }
+        s.push('\n');
+
+        s.push_str(SONG_YEAR_SYMBOL);
+        if let Some(year) = self.year {
+            s.push_str(&year.to_string())
+        }
+        s.push('\n');
+
+        s.push_str(SONG_COMPOSER_SYMBOL);
+        if let Some(composer) = &self.composer {
+            s.push_str(composer)
+        }
+        s.push('\n');
+
+        s.push_str(SONG_LYRICIST_SYMBOL);
+        if let Some(lyricist) = &self.lyricist {
+            s.push_str(lyricist)
+        }
+        s.push('\n');
+
+        s.push_str(SONG_CCLI_SYMBOL);
+        if let Some(ccli) = &self.ccli {
+            s.push_str(ccli)
+        }
+        s.push('\n');
+
+        s.push_str(SONG_LANGUAGE_SYMBOL);
+        if let Some(language) = &self.language {
+            s.push_str(language)
+        }
+        s.push('\n');
+
+        s.push_str(SONG_URL_SYMBOL);
+        if let Some(url) = &self.url {
+            s.push_str(url)
+        }
+        s.push('\n');
+
+        // Метки через запятую
+        s.push_str(SONG_TAGS_SYMBOL);
+        s.push_str(&self.tags.join(", "));
+        s.push('\n');
+
 
         s.push_str(METADATA_END);
         s.push('\n');
@@ -109,7 +243,25 @@ impl Metadata {
         let mut artist = String::new();
         let mut key: Option<Key> = None;
         let mut capo: Option<u8> = None;
+        let mut tempo: Option<u16> = None;
+        let mut time: Option<String> = None;
+        let mut key_shift: Option<i32> = None;
+        let mut copyright: Option<String> = None;
         let mut autoscroll_speed: Option<u64> = None;
+        let mut spelling: Option<Spelling> = None;
+        let mut tuning: Option<Tuning> = None;
+        let mut album: Option<String> = None;
+        let mut year: Option<u16> = None;
+        let mut composer: Option<String> = None;
+        let mut lyricist: Option<String> = None;
+        let mut ccli: Option<String> = None;
+        let mut language: Option<String> = None;
+        let mut url: Option<String> = None;
+        let mut tags: Vec<String> = Vec::new();
+        let get_text = |line: &str, symbol: &str| -> Option<String> {
+            let t = line[symbol.len()..].trim();
+            if t.is_empty() { None } else { Some(t.to_string()) }
+        };
         for line in text.lines() {
             if line.starts_with(SONG_TITLE_SYMBOL) {
                 title = line[SONG_TITLE_SYMBOL.len()..].trim().to_string();
@@ -122,10 +274,44 @@ impl Metadata {
                 if let Ok(c) = line[SONG_CAPO_SYMBOL.len()..].trim().parse::<u8>() {
                     capo = Some(c)
                 }
+            } else if line.starts_with(SONG_TEMPO_SYMBOL) {
+                if let Ok(t) = line[SONG_TEMPO_SYMBOL.len()..].trim().parse::<u16>() {
+                    tempo = Some(t)
+                }
+            } else if line.starts_with(SONG_TIME_SYMBOL) {
+                let t = line[SONG_TIME_SYMBOL.len()..].trim();
+                if !t.is_empty() { time = Some(t.to_string()) }
+            } else if line.starts_with(SONG_KEY_SHIFT_SYMBOL) {
+                if let Ok(k) = line[SONG_KEY_SHIFT_SYMBOL.len()..].trim().parse::<i32>() && k != 0 {
+                    key_shift = Some(k)
+                }
+            } else if line.starts_with(SONG_COPYRIGHT_SYMBOL) {
+                let c = line[SONG_COPYRIGHT_SYMBOL.len()..].trim();
+                if !c.is_empty() { copyright = Some(c.to_string()) }
             } else if line.starts_with(SONG_AUTOSCROLL_SPEED_SYMBOL) {
                 if let Ok(s) = line[SONG_AUTOSCROLL_SPEED_SYMBOL.len()..].trim().parse::<u64>() {
                     autoscroll_speed = Some(s)
                 }
+            } else if line.starts_with(SONG_SPELLING_SYMBOL) {
+                spelling = Spelling::new(line[SONG_SPELLING_SYMBOL.len()..].trim());
+            } else if line.starts_with(SONG_TUNING_SYMBOL) {
+                tuning = Tuning::new(line[SONG_TUNING_SYMBOL.len()..].trim());
+            } else if line.starts_with(SONG_ALBUM_SYMBOL) {
+                album = get_text(line, SONG_ALBUM_SYMBOL);
+            } else if line.starts_with(SONG_YEAR_SYMBOL) {
+                year = line[SONG_YEAR_SYMBOL.len()..].trim().parse::<u16>().ok();
+            } else if line.starts_with(SONG_COMPOSER_SYMBOL) {
+                composer = get_text(line, SONG_COMPOSER_SYMBOL);
+            } else if line.starts_with(SONG_LYRICIST_SYMBOL) {
+                lyricist = get_text(line, SONG_LYRICIST_SYMBOL);
+            } else if line.starts_with(SONG_CCLI_SYMBOL) {
+                ccli = get_text(line, SONG_CCLI_SYMBOL);
+            } else if line.starts_with(SONG_LANGUAGE_SYMBOL) {
+                language = get_text(line, SONG_LANGUAGE_SYMBOL);
+            } else if line.starts_with(SONG_URL_SYMBOL) {
+                url = get_text(line, SONG_URL_SYMBOL);
+            } else if line.starts_with(SONG_TAGS_SYMBOL) {
+                tags = Self::parse_tags(&line[SONG_TAGS_SYMBOL.len()..]);
             }
         }
 
@@ -133,7 +319,65 @@ impl Metadata {
         if !artist.is_empty() { self.artist = artist }
         self.key = key;
         self.capo = capo;
+        self.tempo = tempo;
+        self.time = time;
+        self.key_shift = key_shift;
+        self.copyright = copyright;
         self.autoscroll_speed = autoscroll_speed;
+        self.spelling = spelling;
+        self.tuning = tuning;
+        self.album = album;
+        self.year = year;
+        self.composer = composer;
+        self.lyricist = lyricist;
+        self.ccli = ccli;
+        self.language = language;
+        self.url = url;
+        self.tags = tags;
+    }
+
+    // worship, slow , worship -> [worship, slow]
+    pub fn parse_tags(text: &str) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in text.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            if tags.iter().all(|t| fold(t) != fold(tag)) { tags.push(tag.to_string()) }
+        }
+
+        return tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| fold(t) == fold(tag.trim()))
+    }
+
+    // Сведения о песне для показа, по строке на поле
+    pub fn get_details(&self) -> Vec<String> {
+        let mut details = Vec::new();
+        match (&self.album, self.year) {
+            (Some(album), Some(year)) => details.push(format!("Album: {album} ({year})")),
+            (Some(album), None) => details.push(format!("Album: {album}")),
+            (None, Some(year)) => details.push(format!("Year: {year}")),
+            (None, None) => {}
+        }
+        if let Some(composer) = &self.composer { details.push(format!("Music: {composer}")) }
+        if let Some(lyricist) = &self.lyricist { details.push(format!("Lyrics: {lyricist}")) }
+        if let Some(copyright) = &self.copyright { details.push(format!("© {copyright}")) }
+        if let Some(ccli) = &self.ccli { details.push(format!("CCLI: {ccli}")) }
+        if let Some(language) = &self.language { details.push(format!("Language: {language}")) }
+        if let Some(url) = &self.url { details.push(format!("Source: {url}")) }
+        if !self.tags.is_empty() { details.push(format!("Tags: {}", self.tags.join(", "))) }
+
+        return details
+    }
+
+    pub fn get_spelling(&self) -> Spelling {
+        if let Some(spelling) = self.spelling { spelling }
+        else if let Some(key) = self.key { key.get_spelling() }
+        else { Spelling::Sharps }
+    }
+
+    pub fn get_tuning(&self, instrument: Instrument) -> Vec<Note> {
+        instrument.get_tuning_for(self.tuning.as_ref())
     }
 }
 
@@ -141,12 +385,27 @@ impl Metadata {
 impl Song {
     pub fn new(title: &str, artist: &str) -> Self {
         Self {
+            id: Uuid::new_v4(),
             metadata: Metadata {
                 title: title.to_string(), 
                 artist: artist.to_string(),
                 key: None,
                 capo: None,
-                autoscroll_speed: None
+                tempo: None,
+                time: None,
+                key_shift: None,
+                copyright: None,
+                autoscroll_speed: None,
+                spelling: None,
+                tuning: None,
+                album: None,
+                year: None,
+                composer: None,
+                lyricist: None,
+                ccli: None,
+                language: None,
+                url: None,
+                tags: Vec::new()
             },
             chord_list: Vec::new(),
             blocks: Vec::new(),
@@ -158,8 +417,20 @@ impl Song {
         &self,
         chords: bool,
         rhythm: bool,
-        fingerings: bool,
+        fingerings: Option<Instrument>, // для какого инструмента показывать аппликатуры
         notes: bool
+    ) -> String {
+        self.get_song_as_text_with_width(chords, rhythm, fingerings, notes, None)
+    }
+
+    // width - ширина для аппликатур, без неё берётся ширина терминала
+    pub fn get_song_as_text_with_width(
+        &self,
+        chords: bool,
+        rhythm: bool,
+        fingerings: Option<Instrument>,
+        notes: bool,
+        width: Option<usize>
     ) -> String {
         let mut s = String::new();
 
@@ -174,25 +445,9 @@ impl Song {
             s.push('\n');
         }
 
-        if chords && fingerings {
-            let mut fings = Vec::new();
-            
-            #[cfg(not(feature = "song_library"))]
-            for f in self.get_fingerings() {
-                fings.push(f[0].clone());
-            }
-            
-            #[cfg(feature = "song_library")]
-            for chord in &self.chord_list {
-                if let Ok(Some(f)) = crate::song_library::get_fingering(&chord.text) {
-                    fings.push(f)
-                } else {
-                    fings.push( chord.get_fingerings(&STANDART_TUNING)[0].clone() )
-                }
-            }
-
-            
-            if let Some(text) = sum_text_in_fingerings(&fings, None) {
+        if chords && let Some(instrument) = fingerings {
+            let fings = self.get_fingerings_for_showing(instrument);
+            if let Some(text) = sum_text_in_fingerings(&fings, width) {
                 s.push_str(&text);
             }
         }
@@ -203,7 +458,7 @@ impl Song {
         return s
     }
 
-    pub fn print(&self, chords: bool, rhythm: bool, fingerings: bool, notes: bool) {
+    pub fn print(&self, chords: bool, rhythm: bool, fingerings: Option<Instrument>, notes: bool) {
         println!("{}", self.get_song_as_text(chords, rhythm, fingerings, notes));
     }
 
@@ -251,7 +506,7 @@ impl Song {
         &self,
         chords: bool,
         rhythm: bool,
-        fingerings: bool,
+        fingerings: Option<Instrument>, // для какого инструмента показывать аппликатуры
         notes: bool
     ) -> String {
 
@@ -266,24 +521,8 @@ impl Song {
             }
         }
 
-        if chords && fingerings {
-            let mut fings = Vec::new();
-            
-            #[cfg(not(feature = "song_library"))]
-            for f in self.get_fingerings() {
-                fings.push(f[0].clone());
-            }
-            
-            #[cfg(feature = "song_library")]
-            for chord in &self.chord_list {
-                if let Ok(Some(f)) = crate::song_library::get_fingering(&chord.text) {
-                    fings.push(f)
-                } else {
-                    fings.push( chord.get_fingerings(&STANDART_TUNING)[0].clone() )
-                }
-            }
-
-            
+        if chords && let Some(instrument) = fingerings {
+            let fings = self.get_fingerings_for_showing(instrument);
             if let Some(text) = sum_text_in_fingerings(&fings, None) {
                 s.push_str(&text);
             }
@@ -316,36 +555,67 @@ impl Song {
         return s
     }
 
-    pub fn detect_key(&mut self) -> Note {
-        let this_keys: Vec<Note> = self.chord_list
-            .iter()
-            .map(|c| c.get_keynote() )
+    // Возвращает тональность и уверенность в ней (от 0.0 до 1.0)
+    pub fn detect_key(&self) -> Option<(Key, f32)> {
+        let chords: Vec<&Chord> = self.get_chords_in_order()
+            .into_iter()
+            .filter(|c| c.is_valid())
             .collect();
+        let (first, last) = match (chords.first(), chords.last()) {
+            (Some(f), Some(l)) => (*f, *l),
+            _ => return None
+        };
+
+        // сколько раз встречается каждый аккорд (тоника, минорный ли)
+        let mut counts: Vec<(Note, bool, f32)> = Vec::new();
+        for chord in &chords {
+            let (keynote, minor) = (chord.get_keynote(), chord.is_minor());
+            if let Some(c) = counts.iter_mut().find(|c| c.0 == keynote && c.1 == minor) {
+                c.2 += 1.0
+            } else {
+                counts.push( (keynote, minor, 1.0) )
+            }
+        }
+        let total = chords.len() as f32;
+        let max_count = counts.iter().map(|c| c.2).fold(0.0, f32::max);
 
-        let total: f32 = this_keys.len() as f32;
         let mut key: Option<Key> = None;
-        let mut similarity: f32 = 0.0; // Значение в процентах
-
-        for key_block in KEYS {
-            let keynote = key_block[0];
-            let mut matches = 0.0;
-            for key in &this_keys {
-                if key_block.iter().any(|k| k == key) {
-                    matches += 1.0
+        let mut confidence: f32 = 0.0;
+        for mode in [Mode::Major, Mode::Minor] {
+            for tonic in KEYS.iter().map(|k| k[0]) {
+                let k = Key::with_mode(tonic, mode);
+                let is_tonic = |c: &Chord| c.get_keynote() == tonic && c.is_minor() == k.is_minor();
+
+                // насколько аккорды песни подходят ладу
+                let fitness: f32 = counts.iter()
+                    .map(|(keynote, minor, count)| k.get_chord_fitness(*keynote, *minor) * count)
+                    .sum::<f32>() / total;
+
+                // тоника обычно в начале, в конце и встречается чаще всего
+                let mut tonic_weight: f32 = 0.0;
+                if is_tonic(first) { tonic_weight += 0.3 }
+                if is_tonic(last) { tonic_weight += 0.4 }
+                if let Some( (_, _, count) ) = counts.iter()
+                    .find(|(keynote, minor, _)| *keynote == tonic && *minor == k.is_minor()) {
+                    tonic_weight += 0.3 * count / max_count;
+                }
+
+                let this_confidence = fitness * 0.7 + tonic_weight * 0.3;
+                if this_confidence > confidence {
+                    confidence = this_confidence;
+                    key = Some(k);
                 }
-            }
-            let this_precent: f32 = (matches * 100.0) / total;
-            if this_precent > similarity {
-                similarity = this_precent;
-                key = Some(Key::from_note(keynote));
             }
         }
 
-        self.metadata.key = key;
-        if let Some(k) = key {
-            k.get_note()
-        } else {
-            Note::C
+        key.map(|k| (k, confidence))
+    }
+
+    // Переносит транспонирование для показа в сами аккорды,
+    // для копий песни, которые показываются или экспортируются
+    pub fn apply_key_shift(&mut self) {
+        if let Some(steps) = self.metadata.key_shift.take() {
+            self.transpose(steps)
         }
     }
 
@@ -353,7 +623,13 @@ impl Song {
         if let Some(key) = self.metadata.key {
             self.metadata.key = Some(key.transpose(steps))
         }
-        for chord in &mut self.chord_list { *chord = chord.transpose(steps) }
+        let spelling = self.metadata.get_spelling();
+        let transpose = |chord: &mut Chord| {
+            *chord = chord.transpose(steps);
+            chord.set_spelling(spelling);
+        };
+
+        self.chord_list.iter_mut().for_each(transpose);
         for block in &mut self.blocks {
             for line in &mut block.lines {
                 match line {
@@ -361,29 +637,56 @@ impl Song {
                         if let Some(chords) = &mut row.chords {
                             for chord in chords {
                                 match chord {
-                                    ChordPosition::UpBeat(chord) => *chord = chord.transpose(steps),
-                                    ChordPosition::OnIndex{chord, ..} => *chord = chord.transpose(steps)
+                                    ChordPosition::UpBeat(chord) => transpose(chord),
+                                    ChordPosition::OnIndex{chord, ..} => transpose(chord)
                                 }
                             }
                         }
                     },
-                    Line::ChordsLine(chords) =>
-                        chords.iter_mut().for_each(|c| *c = c.transpose(steps)),
+                    Line::ChordsLine(chords) => chords.iter_mut().for_each(transpose),
                     _ => {}
                 }
             }
         }
     }
 
-    pub fn get_fingerings(&self) -> Vec<Vec<Fingering>> {
+    pub fn get_fingerings(&self, instrument: Instrument) -> Vec<Vec<Fingering>> {
+        let tuning = self.metadata.get_tuning(instrument);
         let mut fings = Vec::new();
         for chord in &self.chord_list {
-            fings.push(chord.get_fingerings(&STANDART_TUNING));
+            fings.push(chord.get_fingerings(instrument, &tuning));
         }
 
         return fings
     }
 
+    // По одной аппликатуре на аккорд, свои аппликатуры в приоритете
+    pub fn get_fingerings_for_showing(&self, instrument: Instrument) -> Vec<Fingering> {
+        let tuning = self.metadata.get_tuning(instrument);
+        let mut fings = Vec::new();
+        for chord in &self.chord_list {
+            if let Some(f) = self.get_user_fingering(chord, instrument) {
+                fings.push(f)
+            } else if let Some(f) = chord.get_fingerings(instrument, &tuning).first() {
+                fings.push(f.clone())
+            }
+        }
+
+        return fings
+    }
+
+    // Свои аппликатуры записаны для стандартного строя инструмента
+    #[cfg(feature = "song_library")]
+    fn get_user_fingering(&self, chord: &Chord, instrument: Instrument) -> Option<Fingering> {
+        if self.metadata.get_tuning(instrument) != instrument.get_tuning() { return None }
+
+        crate::song_library::get_fingering(&chord.text, instrument).ok().flatten()
+    }
+    #[cfg(not(feature = "song_library"))]
+    fn get_user_fingering(&self, _chord: &Chord, _instrument: Instrument) -> Option<Fingering> {
+        None
+    }
+
     pub fn get_for_editing(&self) -> String {
         let mut s = String::new();
 
@@ -469,28 +772,62 @@ impl Song {
         self.chord_list = self.get_chord_list();
     }
 
-    fn get_chord_list(&self) -> Vec<Chord> {
-        let mut list = Vec::new();
+    // Все аккорды песни по порядку, с повторами
+    fn get_chords_in_order(&self) -> Vec<&Chord> {
+        let mut chords = Vec::new();
         for block in &self.blocks {
             for line in &block.lines {
                 match line {
-                    Line::TextBlock(row) => {
-                        if let Some(chords) = &row.chords {
-                            for chord in chords {
-                                match chord {
-                                    ChordPosition::UpBeat(chord) => if list.iter().all(|c| c != chord) {
-                                        list.push(chord.clone());
-                                    },
-                                    ChordPosition::OnIndex{chord, ..} => if list.iter().all(|c| c != chord) {
-                                        list.push(chord.clone());
-                                    },
-                                }
+                    Line::TextBlock(row) => if let Some(row_chords) = &row.chords {
+                        for chord in row_chords {
+                            match chord {
+                                ChordPosition::UpBeat(chord) => chords.push(chord),
+                                ChordPosition::OnIndex{chord, ..} => chords.push(chord)
                             }
                         }
                     },
-                    Line::ChordsLine(chords) => for chord in chords {
-                        if list.iter().all(|c| c != chord) {
-                            list.push(chord.clone());
+                    Line::ChordsLine(line_chords) => chords.extend(line_chords),
+                    _ => {}
+                }
+            }
+        }
+
+        return chords
+    }
+
+    // Все аккорды с ошибками и их позиции
+    pub fn validate(&self) -> Vec<ChordDiagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut check = |block: usize, line: usize, column: usize, chord: &Chord| {
+            if let Some(error) = chord.get_error() {
+                diagnostics.push(ChordDiagnostic {
+                    block, line, column,
+                    text: chord.text.clone(),
+                    error: error.clone()
+                });
+            }
+        };
+
+        for (b, block) in self.blocks.iter().enumerate() {
+            for (l, line) in block.lines.iter().enumerate() {
+                match line {
+                    Line::TextBlock(row) => if let Some(row_chords) = &row.chords {
+                        let mut up_beat_column = 0;
+                        for chord in row_chords {
+                            match chord {
+                                ChordPosition::UpBeat(chord) => {
+                                    check(b, l, up_beat_column, chord);
+                                    up_beat_column += chord.text.chars().count() + 1;
+                                },
+                                ChordPosition::OnIndex{index, chord} => check(b, l, *index, chord)
+                            }
+                        }
+                    },
+                    Line::ChordsLine(line_chords) => {
+                        let mut column = 0;
+                        for chord in line_chords {
+                            check(b, l, column, chord);
+                            column += chord.text.chars().count() + 1;
                         }
                     },
                     _ => {}
@@ -498,6 +835,18 @@ impl Song {
             }
         }
 
+        return diagnostics
+    }
+
+    // Уникальные аккорды песни, аккорды с ошибками не попадают
+    pub(crate) fn get_chord_list(&self) -> Vec<Chord> {
+        let mut list: Vec<Chord> = Vec::new();
+        for chord in self.get_chords_in_order() {
+            if chord.is_valid() && list.iter().all(|c| c != chord) {
+                list.push(chord.clone());
+            }
+        }
+
         return list;
     }
 }
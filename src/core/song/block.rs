@@ -22,14 +22,14 @@ use crate::{
 };
 
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Block {
     pub title: Option<String>,
     pub lines: Vec<Line>,
     pub notes: Option<String>
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Line {
     TextBlock(Row),
     ChordsLine(Vec<Chord>),
@@ -174,10 +174,8 @@ impl Block {
                 lines.push(Line::EmptyLine);
             } else if line.starts_with(CHORDS_LINE_SYMBOL) {
                 let mut chords: Vec<Chord> = Vec::new();
-                for maybe_chord in line.split_whitespace() {
-                    if let Some(chord) = Chord::new(maybe_chord) {
-                        chords.push(chord);
-                    }
+                for maybe_chord in line[CHORDS_LINE_SYMBOL.len()..].split_whitespace() {
+                    chords.push(Chord::from_text(maybe_chord));
                 }
                 lines.push( Line::ChordsLine(chords) );
             } else if line.starts_with(CHORDS_SYMBOL) || line.starts_with(RHYTHM_SYMBOL) {
@@ -1,218 +1,174 @@
+mod parser;
+
+use std::fmt;
 use serde::{Serialize, Deserialize};
-use crate::Note;
+use crate::{Note, Spelling, Instrument};
 use crate::Note::*;
 use crate::Fingering;
-use crate::STRINGS;
 use crate::chord_generator::get_fingerings;
 
 
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-enum ChordType {
-    Norm,
-    Power,
-    Sixth,
-    SixthMinus,
-    Seventh,
-    MajSeventh,
-    Nineth,
-    Eleventh,
-    Thirteenth
+// Нота аккорда относительно тоники
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Tone {
+    pub interval: u8,  // в полутонах, 0..12
+    pub optional: bool // можно выбросить из аппликатуры (например квинту в септаккорде)
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-enum FifthState {
-    Dim,
-    Norm,
-    Aug
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChordParseError {
+    Empty,
+    UnknownRoot(String),
+    UnknownSymbol { index: usize, symbol: String }, // index - номер символа в тексте аккорда
+    UnknownBass(String)
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-enum SusOrAdd {
-    No,
-    Sus2,
-    Sus4,
-    Sus4Plus,
-    Add2,
-    Add4
+impl fmt::Display for ChordParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChordParseError::Empty => write!(f, "Empty chord"),
+            ChordParseError::UnknownRoot(text) => write!(f, "Unknown root note: {}", text),
+            ChordParseError::UnknownSymbol { index, symbol } =>
+                write!(f, "Unknown symbol '{}' at position {}", symbol, index + 1),
+            ChordParseError::UnknownBass(text) => write!(f, "Unknown bass note: {}", text)
+        }
+    }
 }
 
+impl std::error::Error for ChordParseError {}
+
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "ChordData")]
 pub struct Chord {
     pub text: String,
     keynote: Note,
-    minor: bool,
-    chord_type: ChordType,
-    fifth_state: FifthState,
-    sus_or_add: SusOrAdd,
+    bass: Option<Note>, // нота в басу для аккордов вида C/G
+    #[serde(skip_serializing)] // всё равно восстанавливается из текста
+    tones: Vec<Tone>,
+    #[serde(skip_serializing)]
+    error: Option<ChordParseError> // аккорд с опечаткой, хранится только текст
 }
 
+// Аккорд всегда восстанавливается из текста, так что старые файлы
+// (без поля bass) читаются правильно
+#[derive(Deserialize)]
+struct ChordData {
+    text: String
+}
+
+impl From<ChordData> for Chord {
+    fn from(data: ChordData) -> Self {
+        Chord::from_text(&data.text)
+    }
+}
+
+
 impl Chord {
     pub fn new(text: &str) -> Option<Self> {
-        let (keynote, key_text) = match text {
-            text if text.starts_with("A#") || text.starts_with("Bb") => (ASharp, "A#"),
-            text if text.starts_with("C#") || text.starts_with("Db") => (CSharp, "C#"),
-            text if text.starts_with("D#") || text.starts_with("Eb") => (DSharp, "D#"),
-            text if text.starts_with("F#") || text.starts_with("Gb") => (FSharp, "F#"),
-            text if text.starts_with("G#") || text.starts_with("Ab") => (GSharp, "G#"),
-            text if text.starts_with('A') =>  (A, "A"),
-            text if text.starts_with('B') =>  (B, "B"),
-            text if text.starts_with('C') =>  (C, "C"),
-            text if text.starts_with('D') =>  (D, "D"),
-            text if text.starts_with('E') =>  (E, "E"),
-            text if text.starts_with('F') =>  (F, "F"),
-            text if text.starts_with('G') =>  (G, "G"),
-            _ => return None
-        };
+        Self::parse(text).ok()
+    }
 
-        let text_after_key = &text[key_text.len()..];
-        let text = text.to_string();
-        let minor = text_after_key.starts_with('m') && !text_after_key.starts_with("maj");
-
-        let fifth_state =
-            if text_after_key.contains("aug") ||
-                text_after_key.contains("5#") ||
-                text_after_key.contains("5+") ||
-                text_after_key.contains("+5") { FifthState::Aug }
-
-            else if text_after_key.contains("dim") ||
-                text_after_key.contains("5b") ||
-                text_after_key.contains("5-") ||
-                text_after_key.contains("-5") { FifthState::Dim }
-
-            else { FifthState::Norm };
-
-
-        let sus_or_add =
-            // если третью ступень поднять ещё выше
-            if text_after_key.starts_with("sus4+") ||
-                text_after_key.starts_with("sus4#") { SusOrAdd::Sus4Plus }
-
-            else if text_after_key.starts_with("sus2") { SusOrAdd::Sus2 }
-            else if text_after_key.starts_with("sus4") { SusOrAdd::Sus4 }
-            else if text_after_key.contains("add2") { SusOrAdd::Add2 }
-            else if text_after_key.contains("add4") { SusOrAdd::Add4 }
-            else { SusOrAdd::No };
-
-
-        if text_after_key == "5" {
-            return Some( Self { text, keynote, fifth_state, sus_or_add, 
-                minor: false,
-                chord_type: ChordType::Power,
-            } )
-        } else if text_after_key.contains("9") {
-            return Some( Self { text, keynote, minor, fifth_state, sus_or_add,
-                chord_type: ChordType::Nineth
-            } )
-        } else if text_after_key.contains("11") {
-            return Some( Self { text, keynote, minor, fifth_state, sus_or_add,
-                chord_type: ChordType::Eleventh
-            } )
-        } else if text_after_key.contains("13") {
-            return Some( Self { text, keynote, minor, fifth_state, sus_or_add,
-                chord_type: ChordType::Thirteenth
-            } )
-        } else if text_after_key.contains("maj") {
-            return Some( Self { text, keynote, minor, fifth_state, sus_or_add,
-                chord_type: ChordType::MajSeventh
-            } )
-        } else if text_after_key.contains('7') {
-            return Some( Self { text, keynote, minor, fifth_state, sus_or_add,
-                chord_type: ChordType::Seventh
-            } )
-        } else if text_after_key.contains("6-") || text_after_key.contains("6b") {
-            return Some( Self { text, keynote, minor, fifth_state, sus_or_add,
-                chord_type: ChordType::SixthMinus
-            } )
-        } else if text_after_key.contains('6') {
-            return Some( Self { text, keynote, minor, fifth_state, sus_or_add,
-                chord_type: ChordType::Sixth
-            } )
-        } else {
-            return Some( Self { text, keynote, minor, fifth_state, sus_or_add,
-                chord_type: ChordType::Norm
-            } )
+    // Неразобранный аккорд не теряется, а сохраняется как есть с ошибкой,
+    // чтобы его можно было найти через Song::validate
+    pub fn from_text(text: &str) -> Self {
+        match Self::parse(text) {
+            Ok(chord) => chord,
+            Err(error) => Self {
+                text: text.to_string(),
+                keynote: C,
+                bass: None,
+                tones: Vec::new(),
+                error: Some(error)
+            }
         }
     }
 
+    pub fn parse(text: &str) -> Result<Self, ChordParseError> {
+        if text.is_empty() { return Err(ChordParseError::Empty) }
+
+        let (body, bass) = split_bass(text)?;
+        let (keynote, key_text) = match body {
+            body if body.starts_with("A#") || body.starts_with("Bb") => (ASharp, &body[..2]),
+            body if body.starts_with("C#") || body.starts_with("Db") => (CSharp, &body[..2]),
+            body if body.starts_with("D#") || body.starts_with("Eb") => (DSharp, &body[..2]),
+            body if body.starts_with("F#") || body.starts_with("Gb") => (FSharp, &body[..2]),
+            body if body.starts_with("G#") || body.starts_with("Ab") => (GSharp, &body[..2]),
+            body if body.starts_with('A') =>  (A, "A"),
+            body if body.starts_with('B') =>  (B, "B"),
+            body if body.starts_with('C') =>  (C, "C"),
+            body if body.starts_with('D') =>  (D, "D"),
+            body if body.starts_with('E') =>  (E, "E"),
+            body if body.starts_with('F') =>  (F, "F"),
+            body if body.starts_with('G') =>  (G, "G"),
+            _ => return Err(ChordParseError::UnknownRoot(text.to_string()))
+        };
+
+        let tones = parser::parse_suffix(&body[key_text.len()..], key_text.len())?;
+
+        Ok( Self { text: text.to_string(), keynote, bass, tones, error: None } )
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn get_error(&self) -> Option<&ChordParseError> {
+        self.error.as_ref()
+    }
+
     pub fn get_keynote(&self) -> Note {
         self.keynote
     }
 
-    pub fn get_fingerings(&self, tuning: &[Note; STRINGS]) -> Vec<Fingering> {
-        let mut notes: Vec<Note> = Vec::new();
-        let key = self.keynote;
-        // добавление первой ступени
-        notes.push(key);
-
+    pub fn is_minor(&self) -> bool {
+        self.tones.iter().any(|t| t.interval == 3) &&
+            !self.tones.iter().any(|t| t.interval == 4)
+    }
 
-        // третья ступень
-        if self.chord_type == ChordType::Power {
-            notes.push( key.transpose(7) );
-            return get_fingerings( tuning, &notes, Some(self.text.clone()) )
-        }
-        
-        if self.sus_or_add == SusOrAdd::Sus2 {
-            notes.push( key.transpose(2) );
-        } else if self.sus_or_add == SusOrAdd::Sus4 {
-            notes.push( key.transpose(5) );
-        } else if self.sus_or_add == SusOrAdd::Sus4Plus {
-            notes.push( key.transpose(6) );
-        } else if self.minor {
-            notes.push( key.transpose(3) );
-        } else {
-            notes.push( key.transpose(4) );
-        }
+    pub fn get_bass(&self) -> Option<Note> {
+        self.bass
+    }
 
+    pub fn get_tones(&self) -> &Vec<Tone> {
+        &self.tones
+    }
 
-        // пятая ступень
-        match self.fifth_state {
-            FifthState::Dim => notes.push( key.transpose(6) ),
-            FifthState::Norm => notes.push( key.transpose(7) ),
-            FifthState::Aug => notes.push( key.transpose(8) )
-        }
+    // Один аккорд в разной записи: F#m и Gbm, Cmaj7 и CΔ
+    pub fn is_same(&self, other: &Chord) -> bool {
+        let get_intervals = |chord: &Chord| {
+            let mut intervals: Vec<u8> = chord.tones.iter().map(|t| t.interval).collect();
+            intervals.sort();
+            intervals
+        };
 
+        self.keynote == other.keynote && self.bass == other.bass
+            && get_intervals(self) == get_intervals(other)
+    }
 
-        // дополнительные ноты
-        if self.sus_or_add == SusOrAdd::Add2 {
-            notes.push( key.transpose(2) );
-        } else if self.sus_or_add == SusOrAdd::Add4 {
-            notes.push( key.transpose(5) );
-        } else if self.chord_type != ChordType::Norm {
-            match self.chord_type {
-                ChordType::SixthMinus => notes.push( key.transpose(8) ),
-                ChordType::Sixth => notes.push( key.transpose(9) ),
-                ChordType::Seventh => notes.push( key.transpose(10) ),
-                ChordType::MajSeventh => notes.push( key.transpose(11) ),
-                ChordType::Nineth => {
-                    notes.push( key.transpose(2) );
-                    notes.push( key.transpose(10) );
-                },
-                ChordType::Eleventh => {
-                    notes.push( key.transpose(2) );
-                    notes.push( key.transpose(5) );
-                    notes.push( key.transpose(10) );
-                },
-
-                ChordType::Thirteenth => {
-                    notes.push( key.transpose(2) );
-                    notes.push( key.transpose(5) );
-                    notes.push( key.transpose(9) );
-                    notes.push( key.transpose(10) );
-                },
-                _ => {}
+    pub fn get_notes(&self) -> Vec<Note> {
+        self.tones.iter()
+            .map(|t| self.keynote.transpose(t.interval.into()))
+            .collect()
+    }
 
-            }
-        }
+    pub fn get_fingerings(&self, instrument: Instrument, tuning: &[Note]) -> Vec<Fingering> {
+        if !self.is_valid() { return Vec::new() }
 
+        // первая нота - всегда тоника
+        let notes = self.get_notes();
+        let required: Vec<Note> = self.tones.iter()
+            .filter(|t| !t.optional)
+            .map(|t| self.keynote.transpose(t.interval.into()))
+            .collect();
 
-        return get_fingerings( tuning, &notes, Some(self.text.clone()) )
+        return get_fingerings( instrument, tuning, &notes, &required, self.bass, Some(self.text.clone()) )
     }
 
     pub fn transpose(&self, steps: i32) -> Self {
         let steps = steps % 12;
-        if steps == 0 { return self.clone() }
+        if steps == 0 || !self.is_valid() { return self.clone() }
         let mut chord = self.clone();
 
         if steps > 0 {
@@ -238,6 +194,9 @@ impl Chord {
             FSharp => (G, format!("G{}", &self.text[2..]) ),
             G =>      (GSharp, format!("G#{}", &self.text[1..]) ),
             GSharp => (A, format!("A{}", &self.text[2..]) )
+        };
+        if let Some(bass) = self.bass {
+            self.set_bass(bass.transpose(1));
         }
     }
     fn do_step_left(&mut self) {
@@ -254,6 +213,52 @@ impl Chord {
             FSharp => (F, format!("F{}", &self.text[2..]) ),
             G =>      (FSharp, format!("F#{}", &self.text[1..]) ),
             GSharp => (G, format!("G{}", &self.text[2..]) )
+        };
+        if let Some(bass) = self.bass {
+            self.set_bass(bass.transpose(-1));
+        }
+    }
+
+    // Переписывает текст аккорда с диезами или бемолями (A#m/C# -> Bbm/Db)
+    pub fn set_spelling(&mut self, spelling: Spelling) {
+        if !self.is_valid() { return }
+
+        let root_len = if ["A#", "Bb", "C#", "Db", "D#", "Eb", "F#", "Gb", "G#", "Ab"]
+            .iter()
+            .any(|r| self.text.starts_with(r)) { 2 } else { 1 };
+
+        let mut text = self.keynote.get_text_in(spelling);
+        let rest = &self.text[root_len..];
+        if let Some(bass) = self.bass && let Some(index) = rest.rfind('/') {
+            text.push_str(&rest[..=index]);
+            text.push_str(&bass.get_text_in(spelling));
+        } else {
+            text.push_str(rest);
+        }
+
+        self.text = text;
+    }
+
+    fn set_bass(&mut self, bass: Note) {
+        if let Some(index) = self.text.rfind('/') {
+            self.text.truncate(index + 1);
+            self.text.push_str(&bass.get_text());
         }
+        self.bass = Some(bass);
     }
 }
+
+
+// Отделяет бас (C/G -> C, G), если после '/' стоят цифры, то это не бас (C6/9)
+fn split_bass(text: &str) -> Result<(&str, Option<Note>), ChordParseError> {
+    if let Some(index) = text.rfind('/') {
+        let after = &text[index + 1..];
+        if let Some(bass) = Note::new(after) {
+            return Ok( (&text[..index], Some(bass)) )
+        } else if after.is_empty() || !after.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ChordParseError::UnknownBass(after.to_string()))
+        }
+    }
+
+    Ok( (text, None) )
+}
@@ -0,0 +1,310 @@
+// Разбор того, что идёт после тоники: m7b5, maj9, 7sus4, add9, 6/9, 7#9 и т.д.
+use crate::song::chord::{Tone, ChordParseError};
+
+
+// Ступень аккорда: номер ступени (3, 5, 7, 9...) и её высота в полутонах
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Degree {
+    number: u8,
+    interval: u8,
+    altered: bool
+}
+
+struct ChordBuilder {
+    third: Option<u8>,
+    fifth: Option<u8>,
+    seventh: Option<u8>,
+    extension: u8, // 7, 9, 11, 13 - до какой ступени строится аккорд
+    major_seventh: bool,
+    diminished: bool,
+    sixth: bool,
+    added: Vec<Degree>
+}
+
+
+pub fn parse_suffix(suffix: &str, offset: usize) -> Result<Vec<Tone>, ChordParseError> {
+    // квинтаккорд
+    if suffix == "5" {
+        return Ok(vec![
+            Tone { interval: 0, optional: false },
+            Tone { interval: 7, optional: false }
+        ])
+    }
+
+    let mut chord = ChordBuilder {
+        third: Some(4),
+        fifth: Some(7),
+        seventh: None,
+        extension: 0,
+        major_seventh: false,
+        diminished: false,
+        sixth: false,
+        added: Vec::new()
+    };
+
+    let mut rest = parse_quality(suffix, &mut chord);
+    let mut has_number = chord.extension != 0;
+    while !rest.is_empty() {
+        let index = offset + suffix.chars().count() - rest.chars().count();
+        let unknown = || ChordParseError::UnknownSymbol {
+            index,
+            symbol: rest.chars().next().map(|c| c.to_string()).unwrap_or_default()
+        };
+
+        // C/9 - добавленная нона, а не C9
+        if let Some(r) = rest.strip_prefix('/') && starts_with_digit(r) && !has_number {
+            let (number, r) = take_number(r);
+            chord.add(number.and_then(|n| get_degree(n, 0)).ok_or_else(unknown)?);
+            rest = r;
+
+        // скобки и разделители: C7(b9), C7(#9,b13), C7/9
+        } else if let Some(r) = rest.strip_prefix(['(', ')', ',', '/']) {
+            rest = r;
+
+        } else if let Some(r) = strip_any(rest, &["maj", "Maj", "MAJ", "ma", "Δ", "M"]) {
+            chord.major_seventh = true;
+            let (number, r) = take_number(r);
+            match number {
+                Some(n) => if !chord.set_extension(n) { return Err(unknown()) },
+                // "CM" - просто мажор, а "Cmaj" и "CΔ" - мажорный септаккорд
+                None if !rest.starts_with('M') => { chord.set_extension(7); },
+                None => chord.major_seventh = false
+            }
+            has_number = true;
+            rest = r;
+
+        } else if let Some(r) = strip_any(rest, &["sus4#", "sus4+", "sus#4"]) {
+            chord.third = Some(6);
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("sus2") {
+            chord.third = Some(2);
+            rest = r;
+        } else if let Some(r) = strip_any(rest, &["sus4", "sus"]) {
+            chord.third = Some(5);
+            rest = r;
+
+        } else if let Some(r) = rest.strip_prefix("add") {
+            let (accidental, r) = take_accidental(r);
+            let (number, r) = take_number(r);
+            let degree = number
+                .and_then(|n| get_degree(n, accidental))
+                .ok_or_else(unknown)?;
+            chord.add(degree);
+            rest = r;
+
+        } else if let Some(r) = strip_any(rest, &["omit", "no"]) {
+            let (number, r) = take_number(r);
+            match number {
+                Some(3) => chord.third = None,
+                Some(5) => chord.fifth = None,
+                _ => return Err(unknown())
+            }
+            rest = r;
+
+        } else if let Some(r) = rest.strip_prefix("alt") {
+            if chord.extension < 7 { chord.set_extension(7); }
+            chord.fifth = None;
+            for (number, accidental) in [(9, -1), (9, 1), (13, -1)] {
+                if let Some(degree) = get_degree(number, accidental) { chord.add(degree) }
+            }
+            has_number = true;
+            rest = r;
+
+        // старая запись: C5-, A#5b, C5+
+        } else if let Some(r) = strip_any(rest, &["5-", "5b"]) && !starts_with_digit(r) {
+            chord.fifth = Some(6);
+            rest = r;
+        } else if let Some(r) = strip_any(rest, &["5+", "5#"]) && !starts_with_digit(r) {
+            chord.fifth = Some(8);
+            rest = r;
+        } else if let Some(r) = strip_any(rest, &["6-", "6b"]) && !starts_with_digit(r) {
+            chord.add(Degree { number: 6, interval: 8, altered: true });
+            rest = r;
+
+        } else if let Some(r) = strip_any(rest, &["69", "6/9"]) && !has_number {
+            chord.sixth = true;
+            chord.add(Degree { number: 9, interval: 2, altered: false });
+            has_number = true;
+            rest = r;
+
+        } else if starts_with_digit(rest) {
+            let (number, r) = take_number(rest);
+            let number = number.ok_or_else(unknown)?;
+            if has_number {
+                // C7(9), C7/11 - просто добавленная ступень
+                chord.add(get_degree(number, 0).ok_or_else(unknown)?);
+            } else {
+                match number {
+                    2 => chord.add(Degree { number: 2, interval: 2, altered: false }),
+                    4 => chord.third = Some(5),
+                    6 => chord.sixth = true,
+                    n => if !chord.set_extension(n) { return Err(unknown()) }
+                }
+                has_number = true;
+            }
+            rest = r;
+
+        // альтерации: b5, #5, b9, #9, #11, b13, -5, +5
+        } else if rest.starts_with(['b', '-', '#', '+']) {
+            let (accidental, r) = take_accidental(rest);
+            let (number, r) = take_number(r);
+            match number.and_then(|n| get_degree(n, accidental)) {
+                Some(Degree { number: 5, interval, .. }) => chord.fifth = Some(interval),
+                Some(degree) => chord.alter(degree),
+                // C7+, C7# - повышенная квинта без номера
+                None if number.is_none() && accidental > 0 => chord.fifth = Some(8),
+                None => return Err(unknown())
+            }
+            rest = r;
+
+        } else {
+            return Err(unknown())
+        }
+    }
+
+    Ok(chord.get_tones())
+}
+
+
+// Минор, уменьшенный, увеличенный или полууменьшенный
+fn parse_quality<'a>(suffix: &'a str, chord: &mut ChordBuilder) -> &'a str {
+    // "madd9" - минор, а "maj7" - мажорный септаккорд
+    if suffix.starts_with("maj") || suffix.starts_with("ma") && !suffix.starts_with("madd") {
+        return suffix
+    }
+
+    if let Some(r) = strip_any(suffix, &["dim", "°", "o"]) {
+        chord.third = Some(3);
+        chord.fifth = Some(6);
+        chord.diminished = true;
+        r
+    } else if let Some(r) = suffix.strip_prefix("ø") {
+        chord.third = Some(3);
+        chord.fifth = Some(6);
+        chord.set_extension(7);
+        r.strip_prefix('7').unwrap_or(r)
+    } else if let Some(r) = strip_any(suffix, &["aug", "+"]) {
+        chord.fifth = Some(8);
+        if suffix.starts_with('+') { r.strip_prefix('5').unwrap_or(r) } else { r }
+    } else if let Some(r) = strip_any(suffix, &["min", "mi", "m"]) {
+        chord.third = Some(3);
+        r
+    // C-7 - минорный септаккорд, а C-5 - уменьшенная квинта
+    } else if let Some(r) = suffix.strip_prefix('-') && !r.starts_with('5') {
+        chord.third = Some(3);
+        r
+    } else {
+        suffix
+    }
+}
+
+
+impl ChordBuilder {
+    fn set_extension(&mut self, number: u8) -> bool {
+        if ![7, 9, 11, 13].contains(&number) { return false }
+
+        self.extension = number;
+        self.seventh = Some(
+            if self.major_seventh { 11 }
+            else if self.diminished { 9 }
+            else { 10 }
+        );
+
+        true
+    }
+
+    fn add(&mut self, degree: Degree) {
+        if !self.added.contains(&degree) { self.added.push(degree) }
+    }
+
+    // Альтерированная ступень заменяет натуральную (C13b9 - без обычной ноны)
+    fn alter(&mut self, degree: Degree) {
+        if self.extension < 7 && degree.number > 7 { self.set_extension(7); }
+        self.added.retain(|d| d.number != degree.number || d.altered);
+        self.add(degree);
+    }
+
+    fn get_tones(&self) -> Vec<Tone> {
+        let mut tones = vec![ Tone { interval: 0, optional: false } ];
+        let is_altered = |number: u8| self.added.iter().any(|d| d.number == number && d.altered);
+
+        if let Some(third) = self.third {
+            // в ундецимаккорде терция обычно выбрасывается
+            tones.push( Tone { interval: third, optional: self.extension == 11 } );
+        }
+        if let Some(fifth) = self.fifth {
+            let is_big = self.extension != 0 || self.sixth || !self.added.is_empty();
+            tones.push( Tone { interval: fifth, optional: fifth == 7 && is_big } );
+        }
+        if self.sixth {
+            tones.push( Tone { interval: 9, optional: false } );
+        }
+        if let Some(seventh) = self.seventh {
+            tones.push( Tone { interval: seventh, optional: false } );
+        }
+
+        // натуральные ступени из обозначения (9, 11, 13)
+        for (number, interval) in [(9, 2), (11, 5), (13, 9)] {
+            if number > self.extension || is_altered(number) { continue }
+            tones.push( Tone { interval, optional: number < self.extension } );
+        }
+
+        for degree in &self.added {
+            tones.push( Tone { interval: degree.interval, optional: false } );
+        }
+
+        // одна и та же нота дважды (например 13 и 6) - оставить обязательную
+        let mut result: Vec<Tone> = Vec::new();
+        for tone in tones {
+            if let Some(t) = result.iter_mut().find(|t| t.interval == tone.interval) {
+                t.optional = t.optional && tone.optional;
+            } else {
+                result.push(tone)
+            }
+        }
+
+        result
+    }
+}
+
+
+fn get_degree(number: u8, accidental: i8) -> Option<Degree> {
+    let interval: i8 = match number {
+        2 | 9 => 2,
+        3 => 4,
+        4 | 11 => 5,
+        5 => 7,
+        6 | 13 => 9,
+        7 => 10,
+        _ => return None
+    };
+
+    Some( Degree {
+        number,
+        interval: (interval + accidental).rem_euclid(12) as u8,
+        altered: accidental != 0
+    } )
+}
+
+fn take_number(text: &str) -> (Option<u8>, &str) {
+    let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 { return (None, text) }
+
+    // 13 и 11 - двузначные, остальные ступени однозначные (C79 не бывает, а 6/9 обработано отдельно)
+    let len = if digits >= 2 && (text.starts_with("11") || text.starts_with("13")) { 2 } else { 1 };
+    (text[..len].parse().ok(), &text[len..])
+}
+
+fn take_accidental(text: &str) -> (i8, &str) {
+    if let Some(r) = text.strip_prefix(['b', '-']) { (-1, r) }
+    else if let Some(r) = text.strip_prefix(['#', '+']) { (1, r) }
+    else { (0, text) }
+}
+
+fn strip_any<'a>(text: &'a str, prefixes: &[&str]) -> Option<&'a str> {
+    prefixes.iter().find_map(|p| text.strip_prefix(p))
+}
+
+fn starts_with_digit(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_digit())
+}
@@ -351,13 +351,12 @@ fn chords_from_edited(line: &str, whitespaces: usize) -> Option<Vec<ChordPositio
         } else if chord.is_empty() { continue }
 
 
-        if let Some(c) = Chord::new(&chord) {
-            let index = indent - 1 - chord.chars().count();
-            if whitespaces > index {
-                chords.push(ChordPosition::UpBeat(c))
-            } else {
-                chords.push( ChordPosition::OnIndex {index: (index - whitespaces), chord: c} )
-            }
+        let c = Chord::from_text(&chord);
+        let index = indent - 1 - chord.chars().count();
+        if whitespaces > index {
+            chords.push(ChordPosition::UpBeat(c))
+        } else {
+            chords.push( ChordPosition::OnIndex {index: (index - whitespaces), chord: c} )
         }
 
         chord.clear();
@@ -1,4 +1,5 @@
 pub mod lib_functions;
+pub mod index;
 
 use std::path::{PathBuf, Path};
 use std::fs::{self, File};
@@ -13,7 +14,7 @@ use crossterm::{
     style::{Color, Print, ResetColor, SetForegroundColor}
 };
 
-use crate::{Song, Fingering};
+use crate::{Song, Fingering, Instrument, Setlist, SetlistEntry, Uuid};
 
 
 const FORBIDDEN_CHARS: [char; 9] = ['<', '>', ':', '/', '\\', '|', '?', '*', '`'];
@@ -31,27 +32,78 @@ pub fn init() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+// Путь относительно библиотеки по id песни
+pub fn get_path_by_id(id: &Uuid) -> Result<PathBuf> {
+    let index = index::LibraryIndex::load()?;
+    match index.get_path(id) {
+        Some(path) => Ok( path.to_path_buf() ),
+        None => Err( Error::new(ErrorKind::NotFound, format!("There's no song with id {id}")).into() )
+    }
+}
+
+// Песню можно указать путём или id, путь проверяется первым
+pub fn get_song_path(path_or_id: &Path) -> Result<PathBuf> {
+    if get_lib_path()?.join(path_or_id).exists() { return Ok( path_or_id.to_path_buf() ) }
+
+    match path_or_id.to_str().and_then(|s| Uuid::parse_str(s.trim()).ok()) {
+        Some(id) => get_path_by_id(&id),
+        None => Ok( path_or_id.to_path_buf() )
+    }
+}
+
+pub fn get_id(song_path: &Path) -> Result<Uuid> {
+    let path = get_song_path(song_path)?;
+    let index = index::LibraryIndex::load()?;
+    match index.songs.iter().find(|e| e.path == path) {
+        Some(entry) if entry.id.is_nil() => Err( Error::new(
+            ErrorKind::NotFound, "The song has no id yet, run `songbook assign-ids`"
+        ).into() ),
+        Some(entry) => Ok(entry.id),
+        None => Err( Error::new(ErrorKind::NotFound, format!("There's no such song: {}", song_path.display())).into() )
+    }
+}
+
+// Даёт id песням без него и копиям файлов с чужим id, возвращает изменённые пути
+pub fn assign_ids() -> Result<Vec<PathBuf>> {
+    let lib_path = get_lib_path()?;
+    let mut lib_index = index::LibraryIndex::load()?;
+    lib_index.songs.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut ids = std::collections::HashSet::new();
+    let mut changed = Vec::new();
+    for entry in &lib_index.songs {
+        if !entry.id.is_nil() && ids.insert(entry.id) { continue }
+
+        let path = lib_path.join(&entry.path);
+        let mut song: Song = serde_yaml::from_reader(BufReader::new(File::open(&path)?))?;
+        song.id = Uuid::new_v4();
+        ids.insert(song.id);
+        serde_yaml::to_writer(BufWriter::new(File::create(&path)?), &song)?;
+        changed.push(entry.path.clone());
+    }
+
+    Ok(changed)
+}
+
 pub fn show(
     song_path: &Path,
     key: Option<crate::Key>,
     chords: bool,     // show chords
     rhythm: bool,     // show rhythm
-    fingerings: bool, // show fingerings
+    fingerings: Option<Instrument>, // show fingerings for an instrument
     notes: bool,      // show notes
     is_colored: bool
 ) -> Result<()> {
     let mut path = get_lib_path()?;
-    path = path.join(song_path);
+    path = path.join(get_song_path(song_path)?);
 
     let file = File::open(path)?;
     let reader = BufReader::new(file);
     let mut song: Song = serde_yaml::from_reader(reader)?;
+    song.apply_key_shift();
     if let Some(k) = key {
-        if let Some(mut m_key) = song.metadata.key {
-            while m_key.get_note() != k.get_note() {
-                song.transpose(1);
-                m_key = song.metadata.key.unwrap();
-            }
+        if let Some(m_key) = song.metadata.key {
+            song.transpose(m_key.steps_to(&k));
         } else { println!("Add a key before transposing, try 'songbook edit <song_name>'") }
     }
 
@@ -70,7 +122,7 @@ pub fn show(
 
 pub fn edit(added_path: &Path) -> Result<()> {
     let mut path = get_lib_path()?;
-    path = path.join(added_path);
+    path = path.join(get_song_path(added_path)?);
     if !path.exists() {
         return Err( Error::new(ErrorKind::NotFound, "There's no such file!").into() )
     }
@@ -78,10 +130,14 @@ pub fn edit(added_path: &Path) -> Result<()> {
     let file = File::open(&path)?;
     let reader = BufReader::new(file);
     let mut song: Song = serde_yaml::from_reader(reader)?;
+    if song.id.is_nil() { song.id = Uuid::new_v4() }
 
     let mut text = song.get_for_editing();
     text = edit::edit(text)?;
     song.change_from_edited_str(&text);
+    for diagnostic in song.validate() {
+        println!("Warning: {}", diagnostic);
+    }
 
     let file = File::create(path)?;
     let writer = BufWriter::new(file);
@@ -93,39 +149,672 @@ pub fn edit(added_path: &Path) -> Result<()> {
 }
 
 
-pub fn add(song: &Song) -> Result<()> {
-    let mut path = get_lib_path()?;
-    if !path.exists() { fs::create_dir_all(&path)? }
+// Проверяет песню или все песни в папке (без пути - всю библиотеку)
+pub fn check(added_path: Option<&Path>) -> Result<()> {
+    let lib_path = get_lib_path()?;
+    let path = match added_path {
+        Some(p) => lib_path.join(p),
+        None => lib_path.clone()
+    };
+    if !path.exists() {
+        return Err( Error::new(ErrorKind::NotFound, "There's no such file or dir!").into() )
+    }
+
+    let mut problems = 0;
+    recursive_check(&path, &lib_path, &mut problems)?;
+    if problems == 0 { println!("No problems found") }
+    else { println!("\nFound {} problem(s)", problems) }
+
+    Ok(())
+}
+fn recursive_check(path: &Path, lib_path: &Path, problems: &mut usize) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            recursive_check(&entry?.path(), lib_path, problems)?;
+        }
+        return Ok(())
+    }
+
+    let name = path.strip_prefix(lib_path).unwrap_or(path).display();
+    let file = File::open(path)?;
+    let song: Song = match serde_yaml::from_reader(BufReader::new(file)) {
+        Ok(song) => song,
+        Err(err) => {
+            println!("{}: cannot read song: {}", name, err);
+            *problems += 1;
+            return Ok(())
+        }
+    };
+
+    for diagnostic in song.validate() {
+        println!("{}: {}", name, diagnostic);
+        *problems += 1;
+    }
+
+    Ok(())
+}
+
+
+
+// Экспорт в ChordPro, без output - в stdout
+pub fn export_chordpro(song_path: &Path, output: Option<&Path>) -> Result<()> {
+    let song = lib_functions::get_song(song_path)?;
+    write_export(&song.to_chordpro(), output)
+}
+
+// В OpenSong и OpenLyrics нет key_shift, поэтому аккорды транспонируются
+pub fn export_opensong(song_path: &Path, output: Option<&Path>) -> Result<()> {
+    let mut song = lib_functions::get_song(song_path)?;
+    song.apply_key_shift();
+    write_export(&song.to_opensong(), output)
+}
+
+pub fn export_openlyrics(song_path: &Path, output: Option<&Path>) -> Result<()> {
+    let mut song = lib_functions::get_song(song_path)?;
+    song.apply_key_shift();
+    write_export(&song.to_openlyrics(), output)
+}
+
+// Песня - в файл или stdout, папка или вся библиотека - в папку с index.html
+pub fn export_html(added_path: Option<&Path>, output: Option<&Path>) -> Result<()> {
+    let lib_path = get_lib_path()?;
+    let path = match added_path {
+        Some(p) => lib_path.join(p),
+        None => lib_path.clone()
+    };
+    if !path.exists() {
+        return Err( Error::new(ErrorKind::NotFound, "There's no such file or dir!").into() )
+    }
+
+    if path.is_file() {
+        let mut song = lib_functions::get_song(&path)?;
+        song.apply_key_shift();
+        return write_export(&song.to_html(), output)
+    }
+
+    let Some(output) = output else {
+        return Err( Error::new(ErrorKind::InvalidInput, "Use '-o dir' for several songs!").into() )
+    };
+    let mut songs = Vec::new();
+    recursive_export_html(&path, &path, output, &mut songs)?;
+    songs.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let title = match added_path.and_then(|p| p.file_name()) {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => "Songbook".to_string()
+    };
+    fs::write(output.join("index.html"), crate::file_writer::html_writer::write_index_to_html(&title, &songs))?;
+
+    Ok(())
+}
+// Структура папок сохраняется, songs - (ссылка от index.html, название)
+fn recursive_export_html(path: &Path, root: &Path, output: &Path, songs: &mut Vec<(String, String)>) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            recursive_export_html(&entry?.path(), root, output, songs)?;
+        }
+        return Ok(())
+    }
+
+    let mut song = lib_functions::get_song(path)?;
+    song.apply_key_shift();
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut file_path = output.join(relative);
+    file_path.set_file_name(format!("{}.html", relative.file_name().unwrap_or_default().to_string_lossy()));
+    if let Some(parent) = file_path.parent() { fs::create_dir_all(parent)? }
+
+    let depth = relative.components().count() - 1;
+    let index = format!("{}index.html", "../".repeat(depth));
+    fs::write(&file_path, crate::file_writer::html_writer::write_to_html(&song, Some(&index)))?;
+
+    let link = file_path.strip_prefix(output).unwrap_or(&file_path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("/");
+    songs.push( (link, format!("{} - {}", song.metadata.artist, song.metadata.title)) );
+
+    Ok(())
+}
+
+// Песня, папка или вся библиотека (без пути) в один .sbp
+pub fn export_sbp(added_path: Option<&Path>, output: &Path) -> Result<()> {
+    let lib_path = get_lib_path()?;
+    let path = match added_path {
+        Some(p) => lib_path.join(p),
+        None => lib_path.clone()
+    };
+    if !path.exists() {
+        return Err( Error::new(ErrorKind::NotFound, "There's no such file or dir!").into() )
+    }
+
+    let mut songs = Vec::new();
+    collect_songs_with_folders(&path, &lib_path, &mut songs)?;
+    if songs.is_empty() {
+        return Err( Error::new(ErrorKind::NotFound, "There are no songs!").into() )
+    }
+    songs.sort_by(|(a, _), (b, _)| (&a.metadata.artist, &a.metadata.title).cmp(&(&b.metadata.artist, &b.metadata.title)));
+    fs::write(output, crate::write_to_sbp(&songs, &[])?)?;
+
+    Ok(())
+}
+// Папка песни - путь относительно библиотеки, для песен в корне её нет
+fn collect_songs_with_folders(path: &Path, lib_path: &Path, songs: &mut Vec<(Song, Option<String>)>) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            collect_songs_with_folders(&entry?.path(), lib_path, songs)?;
+        }
+        return Ok(())
+    }
+
+    let song: Song = serde_yaml::from_reader(BufReader::new(File::open(path)?))?;
+    let folder = path.parent()
+        .and_then(|p| p.strip_prefix(lib_path).ok())
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(|p| p.components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"));
+    songs.push( (song, folder) );
+
+    Ok(())
+}
+
+pub struct TextExportOptions {
+    pub markdown: bool,
+    pub key: Option<crate::Key>,
+    pub chords: bool,
+    pub rhythm: bool,
+    pub fingerings: Option<Instrument>,
+    pub notes: bool
+}
+
+// Без output - в stdout, папка или вся библиотека - в такое же дерево папок в output
+pub fn export_text(added_path: Option<&Path>, output: Option<&Path>, options: &TextExportOptions) -> Result<()> {
+    let lib_path = get_lib_path()?;
+    let path = match added_path {
+        Some(p) => lib_path.join(p),
+        None => lib_path.clone()
+    };
+    if !path.exists() {
+        return Err( Error::new(ErrorKind::NotFound, "There's no such file or dir!").into() )
+    }
+
+    if path.is_file() {
+        let text = get_text_for_export(&path, options)?;
+        let output = output.map(|o|
+            if o.is_dir() { o.join(get_export_name(&path, options)) } else { o.to_path_buf() }
+        );
+        return write_export(&text, output.as_deref())
+    }
+
+    recursive_export_text(&path, &path, output, options)
+}
+fn recursive_export_text(path: &Path, root: &Path, output: Option<&Path>, options: &TextExportOptions) -> Result<()> {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .collect();
+        entries.sort();
+        for entry in &entries {
+            recursive_export_text(entry, root, output, options)?;
+        }
+        return Ok(())
+    }
+
+    let text = get_text_for_export(path, options)?;
+    let Some(output) = output else {
+        println!("{text}");
+        return Ok(())
+    };
+
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let file_path = output.join(relative).with_file_name(get_export_name(path, options));
+    if let Some(parent) = file_path.parent() { fs::create_dir_all(parent)? }
+    fs::write(file_path, text)?;
+
+    Ok(())
+}
+
+fn get_text_for_export(path: &Path, options: &TextExportOptions) -> Result<String> {
+    let mut song = lib_functions::get_song(path)?;
+    song.apply_key_shift();
+    if let Some(key) = options.key {
+        if let Some(m_key) = song.metadata.key { song.transpose(m_key.steps_to(&key)) }
+        else {
+            eprintln!("{} - {}: add a key before transposing, try 'songbook edit <song_name>'",
+                song.metadata.artist,
+                song.metadata.title
+            );
+        }
+    }
+
+    let (chords, rhythm, notes) = (options.chords, options.rhythm, options.notes);
+    let text = if options.markdown { song.to_markdown(chords, rhythm, options.fingerings, notes) }
+        else { format!("{}\n", song.to_text(chords, rhythm, options.fingerings, notes)) };
+
+    return Ok(text)
+}
+
+// Имя файла песни с расширением .txt или .md
+fn get_export_name(path: &Path, options: &TextExportOptions) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if options.markdown { format!("{name}.md") } else { format!("{name}.txt") }
+}
+
+// Песня, папка или вся библиотека (без пути), для нескольких песен - с оглавлением
+#[cfg(feature = "pdf")]
+pub fn export_pdf(
+    added_path: Option<&Path>,
+    output: &Path,
+    options: &crate::PdfOptions,
+    font_path: Option<&Path>
+) -> Result<()> {
+    let lib_path = get_lib_path()?;
+    let path = match added_path {
+        Some(p) => lib_path.join(p),
+        None => lib_path.clone()
+    };
+    if !path.exists() {
+        return Err( Error::new(ErrorKind::NotFound, "There's no such file or dir!").into() )
+    }
+
+    let mut songs = Vec::new();
+    collect_songs(&path, &mut songs)?;
+    if songs.is_empty() {
+        return Err( Error::new(ErrorKind::NotFound, "There are no songs!").into() )
+    }
+    songs.iter_mut().for_each(Song::apply_key_shift);
+    if path.is_dir() {
+        songs.sort_by(|a, b| (&a.metadata.artist, &a.metadata.title).cmp(&(&b.metadata.artist, &b.metadata.title)));
+    }
+
+    let title = match added_path.and_then(|p| p.file_name()) {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => "Songbook".to_string()
+    };
+    let font = get_font(font_path)?;
+    let bytes = crate::write_to_pdf(&songs, &title, options, &font)?;
+    fs::write(output, bytes)?;
+
+    Ok(())
+}
+#[cfg(feature = "pdf")]
+fn collect_songs(path: &Path, songs: &mut Vec<Song>) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            collect_songs(&entry?.path(), songs)?;
+        }
+        return Ok(())
+    }
+
+    let file = File::open(path)?;
+    songs.push( serde_yaml::from_reader(BufReader::new(file))? );
+
+    Ok(())
+}
+
+// Моноширинный TTF: указанный, из data_dir/songbook/fonts или один из системных
+#[cfg(feature = "pdf")]
+fn get_font(font_path: Option<&Path>) -> Result<Vec<u8>> {
+    if let Some(path) = font_path { return Ok( fs::read(path)? ) }
+
+    if let Some(mut path) = dirs::data_dir() {
+        path.push("songbook");
+        path.push("fonts");
+        if let Ok(entries) = fs::read_dir(&path) {
+            let mut fonts: Vec<PathBuf> = entries.flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("ttf")))
+                .collect();
+            fonts.sort();
+            if let Some(font) = fonts.first() { return Ok( fs::read(font)? ) }
+        }
+    }
+
+    let system_fonts = [
+        "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+        "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+        "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+        "/usr/share/fonts/dejavu-sans-mono-fonts/DejaVuSansMono.ttf",
+        "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+        "/usr/share/fonts/liberation-mono/LiberationMono-Regular.ttf",
+        "/usr/share/fonts/TTF/LiberationMono-Regular.ttf",
+        "/usr/share/fonts/noto/NotoSansMono-Regular.ttf",
+        "/usr/share/fonts/truetype/noto/NotoSansMono-Regular.ttf",
+        "/Library/Fonts/Courier New.ttf",
+        "/System/Library/Fonts/Supplemental/Courier New.ttf",
+        "C:\\Windows\\Fonts\\consola.ttf",
+        "C:\\Windows\\Fonts\\cour.ttf"
+    ];
+    for font in system_fonts {
+        if let Ok(bytes) = fs::read(font) { return Ok(bytes) }
+    }
+
+    Err( Error::new(ErrorKind::NotFound, "Cannot find a monospace font, use '--font path/to/font.ttf'!").into() )
+}
+
+fn write_export(text: &str, output: Option<&Path>) -> Result<()> {
+    if let Some(path) = output { fs::write(path, text)? }
+    else { print!("{text}") }
+
+    Ok(())
+}
+
+
+// Возвращает путь песни относительно библиотеки
+pub fn add(song: &Song) -> Result<PathBuf> {
+    add_to_dir(song, None)
+}
+// dir - папка внутри библиотеки, создаётся если её нет
+fn add_to_dir(song: &Song, dir: Option<&str>) -> Result<PathBuf> {
+    let lib_path = get_lib_path()?;
+    let dir_path = match dir {
+        // "a/b" - вложенные папки, "." и ".." из архива не выводят за пределы библиотеки
+        Some(d) => d.split('/')
+            .filter(|part| !part.is_empty())
+            .map(|part| get_without_forbidden_chars(part.to_string()))
+            .fold(lib_path.clone(), |path, part|
+                if part.chars().all(|c| c == '.') { path.join(part.replace('.', "_")) }
+                else { path.join(part) }
+            ),
+        None => lib_path.clone()
+    };
+    if !dir_path.exists() { fs::create_dir_all(&dir_path)? }
 
     let song_name = get_without_forbidden_chars(
         format!("{} - {}", song.metadata.artist, song.metadata.title)
     );
-    path.push(&song_name);
+    let mut path = dir_path.join(&song_name);
     path = get_free_path(path, &song_name);
 
-    let file = File::create(path)?;
+    let mut song = song.clone();
+    if song.id.is_nil() { song.id = Uuid::new_v4() }
+
+    let file = File::create(&path)?;
     let writer = BufWriter::new(file);
 
     serde_yaml::to_writer(writer, &song)?;
 
 
-    Ok(())
+    Ok( path.strip_prefix(&lib_path).unwrap_or(&path).to_path_buf() )
+}
+
+
+// Итог импорта: что добавлено, что пропущено и что не получилось
+#[derive(Default)]
+pub struct ImportReport {
+    pub imported: Vec<PathBuf>,
+    pub skipped: Vec<(String, String)>, // файл - причина
+    pub failed: Vec<(String, String)>,
+    pub warnings: Vec<(String, String)>, // песня добавлена, но что-то из неё не прочитано
+    pub setlists: Vec<String>
+}
+
+impl std::fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Imported: {}", self.imported.len())?;
+        for path in &self.imported {
+            writeln!(f, "    {}", path.display())?;
+        }
+        if !self.skipped.is_empty() {
+            writeln!(f, "Skipped: {}", self.skipped.len())?;
+            for (name, reason) in &self.skipped {
+                writeln!(f, "    {}: {}", name, reason)?;
+            }
+        }
+        if !self.failed.is_empty() {
+            writeln!(f, "Failed: {}", self.failed.len())?;
+            for (name, reason) in &self.failed {
+                writeln!(f, "    {}: {}", name, reason)?;
+            }
+        }
+        if !self.warnings.is_empty() {
+            writeln!(f, "Warnings: {}", self.warnings.len())?;
+            for (name, warning) in &self.warnings {
+                writeln!(f, "    {}: {}", name, warning)?;
+            }
+        }
+        if !self.setlists.is_empty() {
+            writeln!(f, "Setlists: {}", self.setlists.len())?;
+            for name in &self.setlists {
+                writeln!(f, "    {}", name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Папки SongbookPro становятся папками библиотеки, сеты - сет-листами
+pub fn import_sbp(path: &Path) -> Result<ImportReport> {
+    let backup = Song::read_sbp_backup(path)?;
+    let mut report = ImportReport::default();
+
+    let mut paths: Vec<Option<PathBuf>> = Vec::new();
+    for (song, folder) in &backup.songs {
+        match add_to_dir(song, folder.as_deref()) {
+            Ok(p) => {
+                report.imported.push(p.clone());
+                paths.push(Some(p));
+            },
+            Err(err) => {
+                report.failed.push( (format!("{} - {}", song.metadata.artist, song.metadata.title), err.to_string()) );
+                paths.push(None);
+            }
+        }
+    }
+
+    for set in &backup.sets {
+        let mut setlist = Setlist::new(&set.name);
+        for (index, key_shift) in &set.songs {
+            if let Some(Some(path)) = paths.get(*index) {
+                setlist.songs.push( SetlistEntry {
+                    id: Some(backup.songs[*index].0.id),
+                    key_shift: *key_shift,
+                    ..SetlistEntry::new(path.clone())
+                } );
+            }
+        }
+        match add_setlist(&setlist) {
+            Ok(name) => report.setlists.push(name),
+            Err(err) => report.failed.push( (set.name.clone(), err.to_string()) )
+        }
+    }
+
+    Ok(report)
+}
+
+const CHORDPRO_EXTENSIONS: [&str; 6] = ["cho", "chordpro", "chopro", "crd", "pro", "chord"];
+const TXT_EXTENSIONS: [&str; 1] = ["txt"];
+// Файлы OpenSong обычно без расширения
+const OPENSONG_EXTENSIONS: [&str; 2] = ["xml", ""];
+const OPENLYRICS_EXTENSIONS: [&str; 1] = ["xml"];
+
+
+// Пути могут быть файлами, папками или шаблонами (songs/*.cho)
+pub fn import_chordpro(paths: &[String]) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    for path in collect_files(paths, &CHORDPRO_EXTENSIONS, &mut report) {
+        let name = path.display().to_string();
+        let songs = match Song::from_chordpro(&path) {
+            Ok(songs) => songs,
+            Err(err) => {
+                report.failed.push( (name, err.to_string()) );
+                continue
+            }
+        };
+
+        let count = songs.len();
+        for (i, result) in songs.into_iter().enumerate() {
+            let name = if count > 1 { format!("{} (song {})", name, i + 1) } else { name.clone() };
+            let (song, fingerings, warnings) = match result {
+                Ok(s) => s,
+                Err(err) => {
+                    report.failed.push( (name, err.to_string()) );
+                    continue
+                }
+            };
+
+            match add(&song) {
+                Ok(song_path) => report.imported.push(song_path),
+                Err(err) => {
+                    report.failed.push( (name, err.to_string()) );
+                    continue
+                }
+            }
+            report.warnings.extend( warnings.into_iter().map(|w| (name.clone(), w)) );
+            for (instrument, fing) in &fingerings {
+                if let Err(err) = add_fingering(fing, *instrument) {
+                    report.failed.push( (
+                        format!("{} ({})", name, fing.get_title().unwrap_or_default()),
+                        err.to_string()
+                    ) );
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+pub fn import_opensong(paths: &[String]) -> Result<ImportReport> {
+    import_songs(paths, &OPENSONG_EXTENSIONS, Song::from_opensong)
+}
+
+pub fn import_openlyrics(paths: &[String]) -> Result<ImportReport> {
+    import_songs(paths, &OPENLYRICS_EXTENSIONS, Song::from_openlyrics)
+}
+
+// Для форматов, где в файле одна песня
+fn import_songs(paths: &[String], extensions: &[&str], read: fn(&Path) -> Result<Song>) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    for path in collect_files(paths, extensions, &mut report) {
+        let result = read(&path).and_then(|song| add(&song));
+        match result {
+            Ok(song_path) => report.imported.push(song_path),
+            Err(err) => report.failed.push( (path.display().to_string(), err.to_string()) )
+        }
+    }
+
+    Ok(report)
+}
+
+// Без artist и title они берутся из имени файла: "Artist - Title.txt",
+// title из аргумента подходит только для одного файла
+pub fn import_txt(paths: &[String], artist: Option<&str>, title: Option<&str>) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let files = collect_files(paths, &TXT_EXTENSIONS, &mut report);
+    let title = if files.len() == 1 { title } else { None };
+    for path in files {
+        let name = path.display().to_string();
+        let stem = path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (stem_artist, stem_title) = match stem.split_once(" - ") {
+            Some( (a, t) ) => (Some(a.trim().to_string()), t.trim().to_string()),
+            None => (None, stem.trim().to_string())
+        };
+
+        let Some(song_artist) = artist.map(|a| a.to_string()).or(stem_artist) else {
+            report.skipped.push( (name, "cannot get the artist, use '-a' or 'Artist - Title.txt'".to_string()) );
+            continue
+        };
+        let song_title = title.map(|t| t.to_string()).unwrap_or(stem_title);
+
+        let result = Song::from_txt_with_key_guess(&path, &song_title, &song_artist)
+            .and_then(|(song, guess)| Ok( (add(&song)?, guess) ));
+        match result {
+            Ok( (song_path, guess) ) => {
+                // Угаданную тональность стоит проверить
+                if let Some( (key, confidence) ) = guess {
+                    report.warnings.push( (
+                        song_path.display().to_string(),
+                        format!("the key isn't written, guessed {key} (confidence {:.0}%)", confidence * 100.0)
+                    ) );
+                }
+                report.imported.push(song_path);
+            },
+            Err(err) => report.failed.push( (name, err.to_string()) )
+        }
+    }
+
+    Ok(report)
+}
+
+fn collect_files(paths: &[String], extensions: &[&str], report: &mut ImportReport) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for text in paths {
+        let is_pattern = text.contains(['*', '?', '[']) && !Path::new(text).exists();
+        if !is_pattern {
+            let path = PathBuf::from(text);
+            if path.is_dir() { collect_from_dir(&path, extensions, &mut files, report) }
+            else if path.is_file() { files.push(path) }
+            else { report.failed.push( (text.clone(), "there's no such file or dir".to_string()) ) }
+            continue
+        }
+
+        let entries = match glob::glob(text) {
+            Ok(entries) => entries,
+            Err(err) => {
+                report.failed.push( (text.clone(), err.to_string()) );
+                continue
+            }
+        };
+        let count = files.len();
+        for path in entries.flatten() {
+            if path.is_dir() { collect_from_dir(&path, extensions, &mut files, report) }
+            else { files.push(path) }
+        }
+        if files.len() == count {
+            report.skipped.push( (text.clone(), "nothing matches".to_string()) );
+        }
+    }
+
+    return files
+}
+fn collect_from_dir(path: &Path, extensions: &[&str], files: &mut Vec<PathBuf>, report: &mut ImportReport) {
+    let Ok(entries) = fs::read_dir(path) else {
+        report.failed.push( (path.display().to_string(), "cannot read the dir".to_string()) );
+        return
+    };
+
+    // Скрытые файлы (.DS_Store, .gitignore) и папки (.git) - не песни
+    let mut paths: Vec<PathBuf> = entries.flatten()
+        .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .map(|e| e.path())
+        .collect();
+    paths.sort();
+    for p in paths {
+        if p.is_dir() { collect_from_dir(&p, extensions, files, report) }
+        else if p.extension()
+            .map(|e| e.to_str().unwrap_or_default())
+            .or(Some(""))
+            .is_some_and(|e| extensions.iter().any(|ext| e.eq_ignore_ascii_case(ext))) {
+            files.push(p)
+        } else {
+            report.skipped.push( (p.display().to_string(), "unsupported file type".to_string()) );
+        }
+    }
 }
 
 
 pub fn sort() -> Result<()> {
     let path = get_lib_path()?;
-    recursive_sort(&path, &path)?;
+    let mut renames = Vec::new();
+    recursive_sort(&path, &path, &mut renames)?;
+    update_setlists(&renames)?;
     loop {
         if !remove_empty_folders(&path)? { break }
     }
 
     Ok(())
 }
-fn recursive_sort(path: &Path, lib_path: &Path) -> Result<()> {
+// renames - старый и новый путь перемещённых песен
+fn recursive_sort(path: &Path, lib_path: &Path, renames: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
     for entry in fs::read_dir(&path)? {
         let entry = entry?;
-        if entry.path().is_dir() { recursive_sort(&entry.path(), lib_path)?; continue }
+        if entry.path().is_dir() { recursive_sort(&entry.path(), lib_path, renames)?; continue }
 
         let file = File::open(entry.path())?;
         let reader = BufReader::new(file);
@@ -147,6 +836,7 @@ fn recursive_sort(path: &Path, lib_path: &Path) -> Result<()> {
 
         if !is_the_same {
             fs::remove_file(entry.path())?;
+            renames.push( (entry.path(), new_path) );
         }
     }
 
@@ -206,7 +896,8 @@ pub fn mv(input_path: &Path, output_path: &Path) -> Result<()> {
     if o_path.is_dir() { o_path = o_path.join( i_path.file_name()
         .expect("Cannot get input_path file name!") ) }
 
-    fs::rename(i_path, o_path)?;
+    fs::rename(&i_path, &o_path)?;
+    update_setlists(&[(i_path, o_path)])?;
 
     Ok(())
 }
@@ -255,13 +946,26 @@ fn cp_recursive(in_dir: &Path, out_dir: &Path) -> Result<()> {
 }
 
 
-pub fn ls(added_path: Option<&Path>) -> Result<()> {
+pub fn ls(added_path: Option<&Path>, tags: &[String]) -> Result<()> {
     let mut path = get_lib_path()?;
     if let Some(p) = added_path { path = path.join(p) }
     if !path.exists() {
         return Err( Error::new(ErrorKind::NotFound, "There's no such dir!").into() )
     }
 
+    // С метками - песни из индекса во всех вложенных папках
+    if !tags.is_empty() {
+        let query = index::Query {
+            terms: tags.iter().map(|t| (index::Field::Tag, t.trim().to_string())).collect()
+        };
+        let index = index::LibraryIndex::load()?;
+        let dir = added_path.unwrap_or(Path::new(""));
+        for entry in index.search(&query).into_iter().filter(|e| e.path.starts_with(dir)) {
+            println!("{} ({})", entry.get_name(), entry.path.display());
+        }
+        return Ok(())
+    }
+
     for entry in fs::read_dir(path)? {
         let entry = entry?;
         if let Some(name) = entry.file_name().to_str() {
@@ -283,6 +987,24 @@ pub fn ls(added_path: Option<&Path>) -> Result<()> {
 }
 
 
+// Запрос как в index::Query, каждый аргумент - одно условие
+pub fn search(args: &[String], is_fuzzy: bool) -> Result<()> {
+    let query = index::Query::from_args(args);
+    if query.is_empty() {
+        return Err( Error::new(ErrorKind::InvalidInput, "The query is empty!").into() )
+    }
+
+    let index = index::LibraryIndex::load()?;
+    let results = if is_fuzzy { index.search_fuzzy(&query) } else { index.search(&query) };
+    if results.is_empty() { println!("Nothing found") }
+    for entry in results {
+        println!("{} ({})", entry.get_name(), entry.path.display());
+    }
+
+    Ok(())
+}
+
+
 pub fn tree(added_path: Option<&Path>) -> Result<()> {
     let mut path = get_lib_path()?;
     if let Some(p) = added_path { path = path.join(p) }
@@ -350,11 +1072,8 @@ pub fn mkdir(added_path: &Path) -> Result<()> {
     Ok(())
 }
 
-pub fn add_fingering(fing: &Fingering) -> Result<(), Box<dyn std::error::Error>> {
-    let mut path: PathBuf = dirs::data_dir()
-        .ok_or("Cannot get path for data!")?;
-    path.push("songbook");
-    path.push("fingerings");
+pub fn add_fingering(fing: &Fingering, instrument: Instrument) -> Result<(), Box<dyn std::error::Error>> {
+    let mut path = get_fingerings_path(instrument)?;
     if !path.exists() { fs::create_dir_all(&path)? }
 
     let fing_name = get_without_forbidden_chars(
@@ -372,12 +1091,9 @@ pub fn add_fingering(fing: &Fingering) -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
-pub fn get_fingering(chord_name: &str) -> Result<Option<Fingering>, Box<dyn std::error::Error>> {
-    let mut path: PathBuf = dirs::data_dir()
-        .ok_or("Cannot get path for data!")?;
-    path.push("songbook");
-    path.push("fingerings");
-    path.push(&chord_name);
+pub fn get_fingering(chord_name: &str, instrument: Instrument) -> Result<Option<Fingering>, Box<dyn std::error::Error>> {
+    let mut path = get_fingerings_path(instrument)?;
+    path.push(get_without_forbidden_chars(chord_name.to_string()));
     
     if !path.exists() { return Ok(None) }
 
@@ -389,6 +1105,18 @@ pub fn get_fingering(chord_name: &str) -> Result<Option<Fingering>, Box<dyn std:
     Ok(Some(fing))
 }
 
+// Гитарные аппликатуры лежат прямо в fingerings (как и раньше),
+// для остальных инструментов - в fingerings/<instrument>
+fn get_fingerings_path(instrument: Instrument) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut path: PathBuf = dirs::data_dir()
+        .ok_or("Cannot get path for data!")?;
+    path.push("songbook");
+    path.push("fingerings");
+    if instrument != Instrument::Guitar { path.push(instrument.to_string()) }
+
+    Ok(path)
+}
+
 
 fn print(text: &str) -> Result<()> {
     if let Ok(mut child) = Command::new("less").arg("-R").stdin(Stdio::piped()).spawn() {
@@ -420,6 +1148,283 @@ fn get_free_path(mut path: PathBuf, name: &str) -> PathBuf {
     return path
 }
 
+pub fn get_setlists_path() -> Result<PathBuf> {
+    if let Some(mut path) = dirs::data_dir() {
+        path.push("songbook");
+        path.push("setlists");
+
+        Ok(path)
+    }
+    else { Err( Error::new(ErrorKind::NotFound, "Cannot get data directory!").into() ) }
+}
+
+// Сохраняет новый сет-лист, не перезаписывая существующие, возвращает имя файла
+pub fn add_setlist(setlist: &Setlist) -> Result<String> {
+    let setlists_path = get_setlists_path()?;
+    if !setlists_path.exists() { fs::create_dir_all(&setlists_path)? }
+
+    let name = get_without_forbidden_chars(setlist.name.clone());
+    let path = get_free_path(setlists_path.join(&name), &name);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_yaml::to_writer(writer, setlist)?;
+
+    Ok( path.file_name().unwrap_or_default().to_string_lossy().to_string() )
+}
+
+// Путь к файлу сет-листа: имя без запрещённых символов ("a/b" - это "a_b") и без выхода из папки
+fn get_setlist_path(name: &str) -> Result<PathBuf> {
+    let file_name = get_without_forbidden_chars(name.to_string());
+    if file_name.is_empty() || file_name == "." || file_name == ".." {
+        return Err( Error::new(ErrorKind::InvalidInput, format!("Wrong setlist name: {name}")).into() )
+    }
+
+    Ok( get_setlists_path()?.join(file_name) )
+}
+
+pub fn get_setlist(name: &str) -> Result<Setlist> {
+    let path = get_setlist_path(name)?;
+    if !path.is_file() {
+        return Err( Error::new(ErrorKind::NotFound, format!("There's no such setlist: {name}")).into() )
+    }
+    let mut setlist: Setlist = serde_yaml::from_reader(BufReader::new(File::open(path)?))?;
+
+    // Песни, переименованные в обход songbook, находятся по id
+    let lib_path = get_lib_path()?;
+    if setlist.songs.iter().any(|e| e.id.is_some() && !lib_path.join(&e.path).is_file()) {
+        let index = index::LibraryIndex::load()?;
+        let mut is_changed = false;
+        for entry in &mut setlist.songs {
+            if lib_path.join(&entry.path).is_file() { continue }
+            if let Some(id) = entry.id && let Some(path) = index.get_path(&id) {
+                entry.path = path.to_path_buf();
+                is_changed = true;
+            }
+        }
+        if is_changed { save_setlist(name, &setlist)? }
+    }
+
+    Ok(setlist)
+}
+
+pub fn save_setlist(name: &str, setlist: &Setlist) -> Result<()> {
+    let setlists_path = get_setlists_path()?;
+    if !setlists_path.exists() { fs::create_dir_all(&setlists_path)? }
+    serde_yaml::to_writer(BufWriter::new(File::create(get_setlist_path(name)?)?), setlist)?;
+
+    Ok(())
+}
+
+// Имена файлов сет-листов по алфавиту
+pub fn get_setlist_names() -> Result<Vec<String>> {
+    let path = get_setlists_path()?;
+    if !path.exists() { return Ok(Vec::new()) }
+
+    let mut names: Vec<String> = fs::read_dir(path)?
+        .flatten()
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.file_name().to_str().map(|n| n.to_string()))
+        .collect();
+    names.sort();
+
+    Ok(names)
+}
+
+// Песни сет-листа по порядку, с его настройками
+pub fn get_setlist_songs(setlist: &Setlist) -> Result<Vec<Song>> {
+    let mut songs = Vec::new();
+    for entry in &setlist.songs {
+        let mut song = lib_functions::get_song(&entry.path)
+            .map_err(|err| Error::new(ErrorKind::NotFound, format!("{}: {err}", entry.path.display())))?;
+        entry.apply(&mut song);
+        songs.push(song);
+    }
+
+    Ok(songs)
+}
+
+// Пути в сет-листах меняются вместе с песнями и папками (mv, sort)
+fn update_setlists(renames: &[(PathBuf, PathBuf)]) -> Result<()> {
+    if renames.is_empty() { return Ok(()) }
+    let lib_path = get_lib_path()?;
+    let relative = |p: &Path| p.strip_prefix(&lib_path).unwrap_or(p).to_path_buf();
+    let renames: Vec<(PathBuf, PathBuf)> = renames.iter()
+        .map(|(from, to)| (relative(from), relative(to)))
+        .collect();
+
+    for name in get_setlist_names()? {
+        // Чужие файлы в папке сет-листов не мешают переименованию
+        let Ok(mut setlist) = get_setlist(&name) else { continue };
+        let mut is_changed = false;
+        for entry in &mut setlist.songs {
+            for (from, to) in &renames {
+                if let Ok(rest) = entry.path.strip_prefix(from) {
+                    entry.path = if rest.as_os_str().is_empty() { to.clone() } else { to.join(rest) };
+                    is_changed = true;
+                    break
+                }
+            }
+        }
+        if is_changed { save_setlist(&name, &setlist)? }
+    }
+
+    Ok(())
+}
+
+
+pub fn setlist_new(name: &str) -> Result<()> {
+    let path = get_setlist_path(name)?;
+    if path.exists() {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        return Err( Error::new(ErrorKind::AlreadyExists, format!("There's already a setlist: {file_name}")).into() )
+    }
+
+    save_setlist(name, &Setlist::new(name))
+}
+
+// entry - настройки для всех добавляемых песен, position - номер с 1, без него - в конец
+pub fn setlist_add(name: &str, songs: &[PathBuf], entry: &SetlistEntry, position: Option<usize>) -> Result<()> {
+    let mut setlist = get_setlist(name)?;
+    let lib_path = get_lib_path()?;
+    let index = match position {
+        Some(p) if p >= 1 && p <= setlist.songs.len() + 1 => p - 1,
+        Some(p) => return Err( Error::new(ErrorKind::InvalidInput, format!("There's no position {p} in the setlist!")).into() ),
+        None => setlist.songs.len()
+    };
+
+    let lib_index = index::LibraryIndex::load()?;
+    for (i, song) in songs.iter().enumerate() {
+        let path = get_song_path(song.strip_prefix(&lib_path).unwrap_or(song))?;
+        if !lib_path.join(&path).is_file() {
+            return Err( Error::new(ErrorKind::NotFound, format!("There's no such song: {}", song.display())).into() )
+        }
+        let id = lib_index.songs.iter().find(|e| e.path == path).map(|e| e.id).filter(|id| !id.is_nil());
+        setlist.songs.insert(index + i, SetlistEntry { path, id, ..entry.clone() });
+    }
+
+    save_setlist(name, &setlist)
+}
+
+// Без номера удаляется весь сет-лист
+pub fn setlist_rm(name: &str, position: Option<usize>) -> Result<()> {
+    let Some(position) = position else {
+        get_setlist(name)?;
+        fs::remove_file(get_setlist_path(name)?)?;
+        return Ok(())
+    };
+
+    let mut setlist = get_setlist(name)?;
+    if position == 0 || position > setlist.songs.len() {
+        return Err( Error::new(ErrorKind::InvalidInput, format!("There's no position {position} in the setlist!")).into() )
+    }
+    setlist.songs.remove(position - 1);
+
+    save_setlist(name, &setlist)
+}
+
+pub fn setlist_reorder(name: &str, from: usize, to: usize) -> Result<()> {
+    let mut setlist = get_setlist(name)?;
+    if !setlist.reorder(from, to) {
+        return Err( Error::new(ErrorKind::InvalidInput, "There's no such position in the setlist!").into() )
+    }
+
+    save_setlist(name, &setlist)
+}
+
+// Без имени - список сет-листов
+pub fn setlist_show(name: Option<&str>) -> Result<()> {
+    let Some(name) = name else {
+        for name in get_setlist_names()? {
+            match get_setlist(&name) {
+                Ok(setlist) => println!("{} ({} songs)", name, setlist.songs.len()),
+                Err(_) => println!("{}: cannot read the setlist", name)
+            }
+        }
+        return Ok(())
+    };
+
+    let setlist = get_setlist(name)?;
+    println!("{}\n", setlist.name);
+    for (i, entry) in setlist.songs.iter().enumerate() {
+        let title = match lib_functions::get_song(&entry.path) {
+            Ok(song) => format!("{} - {}", song.metadata.artist, song.metadata.title),
+            Err(_) => "(missing)".to_string()
+        };
+
+        let mut settings = Vec::new();
+        if let Some(key) = entry.key { settings.push(format!("key: {key}")) }
+        if let Some(shift) = entry.key_shift { settings.push(format!("transpose: {shift:+}")) }
+        if let Some(capo) = entry.capo { settings.push(format!("capo: {capo}")) }
+        if let Some(speed) = entry.autoscroll_speed { settings.push(format!("autoscroll: {speed}ms")) }
+        let settings = if settings.is_empty() { String::new() } else { format!(" [{}]", settings.join(", ")) };
+
+        println!("{}. {} ({}){}", i + 1, title, entry.path.display(), settings);
+        if let Some(notes) = &entry.notes {
+            for line in notes.lines() { println!("    {line}") }
+        }
+    }
+
+    Ok(())
+}
+
+// Формат по расширению output: .sbp, .md, .pdf, остальное - текст, без output - в stdout
+// Песни сет-листа и сет с ними в один .sbp
+pub fn setlist_export_sbp(name: &str, output: &Path) -> Result<()> {
+    let setlist = get_setlist(name)?;
+    let songs: Vec<(Song, Option<String>)> = get_setlist_songs(&setlist)?
+        .into_iter()
+        .map(|song| (song, None))
+        .collect();
+    let set = crate::SbpSet {
+        name: setlist.name.clone(),
+        songs: setlist.songs.iter()
+            .enumerate()
+            .map(|(i, _)| (i, None))
+            .collect()
+    };
+    // key_shift сет-листа остаётся в песне, в SongbookPro он будет транспонированием песни
+    fs::write(output, crate::write_to_sbp(&songs, &[set])?)?;
+
+    Ok(())
+}
+
+pub fn setlist_export(
+    name: &str,
+    output: Option<&Path>,
+    options: &TextExportOptions,
+    #[cfg(feature = "pdf")] pdf_options: (&crate::PdfOptions, Option<&Path>)
+) -> Result<()> {
+    let extension = output
+        .and_then(|o| o.extension())
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if extension == "sbp" && let Some(output) = output {
+        return setlist_export_sbp(name, output)
+    }
+
+    let setlist = get_setlist(name)?;
+    let mut songs = get_setlist_songs(&setlist)?;
+    songs.iter_mut().for_each(Song::apply_key_shift);
+
+    #[cfg(feature = "pdf")]
+    if extension == "pdf" && let Some(output) = output {
+        let (pdf_options, font_path) = pdf_options;
+        let font = get_font(font_path)?;
+        fs::write(output, crate::write_to_pdf(&songs, &setlist.name, pdf_options, &font)?)?;
+        return Ok(())
+    }
+
+    let markdown = options.markdown || extension == "md";
+    let (chords, rhythm, notes) = (options.chords, options.rhythm, options.notes);
+    let texts: Vec<String> = songs.iter()
+        .map(|song| if markdown { song.to_markdown(chords, rhythm, options.fingerings, notes) }
+            else { format!("{}\n", song.to_text(chords, rhythm, options.fingerings, notes)) })
+        .collect();
+    let separator = if markdown { "\n---\n\n" } else { "\n\n" };
+
+    write_export(&texts.join(separator), output)
+}
+
+
 pub fn get_lib_path() -> Result<PathBuf> {
     if let Some(mut path) = dirs::data_dir() {
         path.push("songbook");
@@ -0,0 +1,377 @@
+use std::path::{PathBuf, Path};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Error, ErrorKind};
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+use crate::{Song, Key, Chord, Uuid};
+use crate::fuzzy::{self, fold};
+use crate::song::block::Line;
+use crate::song_library::get_lib_path;
+
+
+// Меняется вместе с IndexEntry, старый индекс тогда строится заново
+const INDEX_VERSION: u32 = 2;
+
+// Индекс библиотеки: то, по чему ищут, без разбора YAML каждой песни
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LibraryIndex {
+    #[serde(default)]
+    pub version: u32,
+    pub songs: Vec<IndexEntry>,
+    // Файлы, которые не читаются как песня, и их mtime, чтобы не перечитывать их каждый раз
+    #[serde(default)]
+    pub skipped: Vec<(PathBuf, u64)>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IndexEntry {
+    pub id: Uuid,
+    pub path: PathBuf, // относительно библиотеки
+    pub modified: u64, // mtime файла в миллисекундах
+    pub title: String,
+    pub artist: String,
+    pub key: Option<String>,
+    pub capo: Option<u8>,
+    pub chords: Vec<String>,
+    pub lyrics: String,
+    pub notes: String,
+    pub tags: Vec<String>,
+    pub album: Option<String>,
+    pub year: Option<u16>,
+    pub composer: Option<String>,
+    pub lyricist: Option<String>,
+    pub language: Option<String>,
+    pub ccli: Option<String>,
+    pub tempo: Option<u16>,
+    pub time: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Any, // название, исполнитель, текст и заметки
+    Title,
+    Artist,
+    Key,
+    Capo,
+    Chord,
+    Lyrics,
+    Notes,
+    Tag,
+    Album,
+    Year,
+    Composer,
+    Lyricist,
+    Language,
+    Ccli,
+    Tempo,
+    Time
+}
+
+// Все условия должны выполняться
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub terms: Vec<(Field, String)>
+}
+
+
+impl IndexEntry {
+    fn new(path: PathBuf, modified: u64, song: &Song) -> Self {
+        let mut lyrics = String::new();
+        for line in song.blocks.iter().flat_map(|b| &b.lines) {
+            let text = match line {
+                Line::TextBlock(row) => row.text.as_deref(),
+                Line::PlainText(text) => Some(text.as_str()),
+                _ => None
+            };
+            if let Some(t) = text {
+                lyrics.push_str(t);
+                lyrics.push('\n');
+            }
+        }
+
+        Self {
+            id: song.id,
+            path,
+            modified,
+            title: song.metadata.title.clone(),
+            artist: song.metadata.artist.clone(),
+            key: song.metadata.key.map(|k| k.to_string()),
+            capo: song.metadata.capo,
+            chords: song.get_chord_list().into_iter().map(|c| c.text).collect(),
+            lyrics,
+            notes: song.notes.clone().unwrap_or_default(),
+            tags: song.metadata.tags.clone(),
+            album: song.metadata.album.clone(),
+            year: song.metadata.year,
+            composer: song.metadata.composer.clone(),
+            lyricist: song.metadata.lyricist.clone(),
+            language: song.metadata.language.clone(),
+            ccli: song.metadata.ccli.clone(),
+            tempo: song.metadata.tempo,
+            time: song.metadata.time.clone()
+        }
+    }
+
+    pub fn is_match(&self, query: &Query) -> bool {
+        query.terms.iter().all(|(field, value)| self.is_field_match(*field, value))
+    }
+
+    fn is_field_match(&self, field: Field, value: &str) -> bool {
+        let contains = |text: &str| fold(text).contains(&fold(value));
+        let contains_opt = |text: &Option<String>| text.as_deref().is_some_and(contains);
+        match field {
+            Field::Any => contains(&self.title) || contains(&self.artist)
+                || contains(&self.lyrics) || contains(&self.notes)
+                || contains_opt(&self.album) || self.is_field_match(Field::Tag, value),
+            Field::Title => contains(&self.title),
+            Field::Artist => contains(&self.artist),
+            // G и Gmajor - одна тональность
+            Field::Key => match (Key::new(value), self.key.as_deref().and_then(Key::new)) {
+                (Some(k), Some(song_key)) => k == song_key,
+                _ => false
+            },
+            Field::Capo => match value.parse::<u8>() {
+                Ok(0) => self.capo.is_none(),
+                Ok(c) => self.capo == Some(c),
+                Err(_) => false
+            },
+            // F#m и Gbm - один аккорд, неразобранные сравниваются без учёта регистра
+            Field::Chord => {
+                let chord = Chord::new(value);
+                self.chords.iter().any(|c| match (&chord, Chord::new(c)) {
+                    (Some(chord), Some(c)) => chord.is_same(&c),
+                    _ => c.to_lowercase() == value.to_lowercase()
+                })
+            },
+            Field::Lyrics => contains(&self.lyrics),
+            Field::Notes => contains(&self.notes),
+            // Метка - целиком, worship не найдёт worship-band
+            Field::Tag => self.tags.iter().any(|t| fold(t) == fold(value)),
+            Field::Album => contains_opt(&self.album),
+            Field::Year => value.parse::<u16>().is_ok_and(|y| self.year == Some(y)),
+            Field::Composer => contains_opt(&self.composer),
+            Field::Lyricist => contains_opt(&self.lyricist),
+            Field::Language => contains_opt(&self.language),
+            Field::Ccli => self.ccli.as_deref() == Some(value),
+            Field::Tempo => value.parse::<u16>().is_ok_and(|t| self.tempo == Some(t)),
+            Field::Time => self.time.as_deref().is_some_and(|t| t.trim() == value)
+        }
+    }
+
+    // Сумма очков по всем условиям, None - если какое-то не выполняется
+    pub fn get_fuzzy_score(&self, query: &Query) -> Option<u32> {
+        let mut total = 0;
+        for (field, value) in &query.terms {
+            total += match field {
+                Field::Any => [
+                    fuzzy::score(value, &self.title),
+                    fuzzy::score(value, &self.artist),
+                    fuzzy::score(value, &self.get_name()),
+                    // Совпадение в тексте песни важнее, чем в заметках, но не важнее названия
+                    fuzzy::score_words(value, &self.lyrics).map(|s| s / 2),
+                    fuzzy::score_words(value, &self.notes).map(|s| s / 3),
+                    self.album.as_deref().and_then(|a| fuzzy::score(value, a)).map(|s| s / 2),
+                    self.is_field_match(Field::Tag, value).then_some(500)
+                ].into_iter().flatten().max()?,
+                Field::Title => fuzzy::score(value, &self.title)?,
+                Field::Artist => fuzzy::score(value, &self.artist)?,
+                Field::Lyrics => fuzzy::score_words(value, &self.lyrics)?,
+                Field::Notes => fuzzy::score_words(value, &self.notes)?,
+                Field::Album => fuzzy::score(value, self.album.as_deref()?)?,
+                Field::Composer => fuzzy::score(value, self.composer.as_deref()?)?,
+                Field::Lyricist => fuzzy::score(value, self.lyricist.as_deref()?)?,
+                // Тональность, каподастр, аккорды, метки и числа - только точно
+                field => if self.is_field_match(*field, value) { 1000 } else { return None }
+            };
+        }
+
+        Some(total)
+    }
+
+    pub fn get_name(&self) -> String {
+        format!("{} - {}", self.artist, self.title)
+    }
+}
+
+
+impl Field {
+    fn new(name: &str) -> Option<Self> {
+        Some( match name.to_lowercase().as_str() {
+            "title" => Field::Title,
+            "artist" => Field::Artist,
+            "key" => Field::Key,
+            "capo" => Field::Capo,
+            "chord" | "chords" => Field::Chord,
+            "lyrics" | "text" => Field::Lyrics,
+            "notes" | "note" => Field::Notes,
+            "tag" | "tags" => Field::Tag,
+            "album" => Field::Album,
+            "year" => Field::Year,
+            "composer" | "music" => Field::Composer,
+            "lyricist" | "words" => Field::Lyricist,
+            "language" | "lang" => Field::Language,
+            "ccli" => Field::Ccli,
+            "tempo" | "bpm" => Field::Tempo,
+            "time" => Field::Time,
+            _ => return None
+        } )
+    }
+}
+
+
+impl Query {
+    // artist:beatles key:G chord:F#m "let it be", значения тоже можно взять в кавычки
+    pub fn new(text: &str) -> Self {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut in_quotes = false;
+        for c in text.chars() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                c if c.is_whitespace() && !in_quotes => if !word.is_empty() {
+                    words.push(std::mem::take(&mut word))
+                },
+                c => word.push(c)
+            }
+        }
+        if !word.is_empty() { words.push(word) }
+
+        Self::from_args(&words)
+    }
+
+    // Аргументы командной строки уже разделены шеллом
+    pub fn from_args(args: &[String]) -> Self {
+        let terms = args.iter()
+            .map(|arg| match arg.split_once(':') {
+                Some( (name, value) ) if !value.is_empty() && let Some(field) = Field::new(name) =>
+                    (field, value.trim().to_string()),
+                _ => (Field::Any, arg.trim().to_string())
+            })
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+
+        Self { terms }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+
+impl LibraryIndex {
+    // Читает сохранённый индекс и перечитывает только изменённые песни
+    pub fn load() -> Result<Self> {
+        let lib_path = get_lib_path()?;
+        if !lib_path.is_dir() {
+            return Err( Error::new(ErrorKind::NotFound, "There's no library, try 'songbook init'!").into() )
+        }
+        let index_path = get_index_path()?;
+        // Сломанный индекс просто строится заново
+        let old: LibraryIndex = File::open(&index_path).ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .filter(|i: &LibraryIndex| i.version == INDEX_VERSION)
+            .unwrap_or_default();
+
+        let mut files = Vec::new();
+        collect_files(&lib_path, &mut files)?;
+
+        let old_songs: HashMap<&Path, &IndexEntry> = old.songs.iter().map(|e| (e.path.as_path(), e)).collect();
+        let old_skipped: HashMap<&Path, u64> = old.skipped.iter().map(|(p, m)| (p.as_path(), *m)).collect();
+
+        let mut is_changed = old.songs.len() + old.skipped.len() != files.len();
+        let mut songs = Vec::new();
+        let mut skipped = Vec::new();
+        for path in files {
+            let relative = path.strip_prefix(&lib_path).unwrap_or(&path).to_path_buf();
+            let modified = get_modified(&path);
+            if let Some(entry) = old_songs.get(relative.as_path()) && entry.modified == modified {
+                songs.push( (*entry).clone() );
+                continue
+            }
+            if old_skipped.get(relative.as_path()) == Some(&modified) {
+                skipped.push( (relative, modified) );
+                continue
+            }
+
+            is_changed = true;
+            // Файлы, которые не читаются как песня, в поиск не попадают
+            let song = File::open(&path).ok()
+                .and_then(|f| serde_yaml::from_reader::<_, Song>(BufReader::new(f)).ok());
+            let Some(song) = song else {
+                skipped.push( (relative, modified) );
+                continue
+            };
+            songs.push( IndexEntry::new(relative, modified, &song) );
+        }
+
+        let index = Self { version: INDEX_VERSION, songs, skipped };
+        if is_changed {
+            if let Some(parent) = index_path.parent() { fs::create_dir_all(parent)? }
+            serde_json::to_writer(BufWriter::new(File::create(&index_path)?), &index)?;
+        }
+
+        Ok(index)
+    }
+
+    pub fn get_path(&self, id: &Uuid) -> Option<&Path> {
+        // Старые песни без id (nil) по id не находятся
+        if id.is_nil() { return None }
+        self.songs.iter().find(|e| e.id == *id).map(|e| e.path.as_path())
+    }
+
+    pub fn search(&self, query: &Query) -> Vec<&IndexEntry> {
+        self.songs.iter().filter(|e| e.is_match(query)).collect()
+    }
+
+    // Лучшие совпадения - первыми
+    pub fn search_fuzzy(&self, query: &Query) -> Vec<&IndexEntry> {
+        let mut results: Vec<(u32, &IndexEntry)> = self.songs.iter()
+            .filter_map(|e| Some( (e.get_fuzzy_score(query)?, e) ))
+            .collect();
+        results.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.path.cmp(&b.1.path)));
+
+        results.into_iter().map(|(_, e)| e).collect()
+    }
+
+    // Для TUI - (название, полный путь), как у find
+    pub fn get_list(entries: &[&IndexEntry]) -> Result<Vec<(String, PathBuf)>> {
+        let lib_path = get_lib_path()?;
+        Ok( entries.iter().map(|e| (e.get_name(), lib_path.join(&e.path))).collect() )
+    }
+}
+
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() { collect_files(&path, files)? }
+        else { files.push(path) }
+    }
+
+    Ok(())
+}
+
+fn get_modified(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn get_index_path() -> Result<PathBuf> {
+    if let Some(mut path) = dirs::data_dir() {
+        path.push("songbook");
+        path.push("index.json");
+
+        Ok(path)
+    }
+    else { Err( Error::new(ErrorKind::NotFound, "Cannot get data directory!").into() ) }
+}
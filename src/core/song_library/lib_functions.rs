@@ -4,7 +4,8 @@ use std::io::{Error, ErrorKind, BufReader, BufWriter};
 
 use anyhow::Result;
 
-use crate::Song;
+use crate::{Song, Uuid};
+use crate::fuzzy::fold;
 use crate::song_library::get_lib_path;
 
 
@@ -49,11 +50,21 @@ pub fn get_song(song_path: &Path) -> Result<Song> {
 }
 
 
+// Старая песня без id получает его при сохранении, id уже записанный в файл сохраняется
 pub fn save(song: &Song, path: &Path) -> Result<()> {
+    let mut song = song.clone();
+    if song.id.is_nil() {
+        song.id = File::open(path).ok()
+            .and_then(|f| serde_yaml::from_reader::<_, Song>(BufReader::new(f)).ok())
+            .map(|s| s.id)
+            .filter(|id| !id.is_nil())
+            .unwrap_or_else(Uuid::new_v4);
+    }
+
     let file = File::create(path)?;
     let writer = BufWriter::new(file);
 
-    serde_yaml::to_writer(writer, song)?;
+    serde_yaml::to_writer(writer, &song)?;
 
     Ok(())
 }
@@ -67,7 +78,21 @@ pub fn edit(song: &mut Song) -> Result<()> {
         SONG_ARTIST_SYMBOL,
         SONG_KEY_SYMBOL,
         SONG_CAPO_SYMBOL,
+        SONG_TEMPO_SYMBOL,
+        SONG_TIME_SYMBOL,
+        SONG_KEY_SHIFT_SYMBOL,
+        SONG_COPYRIGHT_SYMBOL,
         SONG_AUTOSCROLL_SPEED_SYMBOL,
+        SONG_SPELLING_SYMBOL,
+        SONG_TUNING_SYMBOL,
+        SONG_ALBUM_SYMBOL,
+        SONG_YEAR_SYMBOL,
+        SONG_COMPOSER_SYMBOL,
+        SONG_LYRICIST_SYMBOL,
+        SONG_CCLI_SYMBOL,
+        SONG_LANGUAGE_SYMBOL,
+        SONG_URL_SYMBOL,
+        SONG_TAGS_SYMBOL,
 
         BLOCK_START,
         BLOCK_END,
@@ -94,7 +119,22 @@ pub fn edit(song: &mut Song) -> Result<()> {
  {SONG_ARTIST_SYMBOL} - Song's artist
  {SONG_KEY_SYMBOL} - Song's key
  {SONG_CAPO_SYMBOL} - Song's capo
+ {SONG_TEMPO_SYMBOL} - Song's tempo (BPM)
+ {SONG_TIME_SYMBOL} - Song's time signature, example: 3/4
+ {SONG_KEY_SHIFT_SYMBOL} - Show the song transposed by this number of semitones, chords aren't changed
+ {SONG_COPYRIGHT_SYMBOL} - Song's copyright
  {SONG_AUTOSCROLL_SPEED_SYMBOL} - Autoscroll speed (in milliseconds)
+ {SONG_SPELLING_SYMBOL} - sharps/flats, empty - depends on the key
+ {SONG_TUNING_SYMBOL} - standard, drop-d, double-drop-d, drop-c, dadgad, open-g, open-d, open-e,
+    half-step-down, whole-step-down or notes from the first string: E, B, G, D, A, E
+ {SONG_ALBUM_SYMBOL} - Song's album
+ {SONG_YEAR_SYMBOL} - Song's year
+ {SONG_COMPOSER_SYMBOL} - Song's composer
+ {SONG_LYRICIST_SYMBOL} - Song's lyricist
+ {SONG_CCLI_SYMBOL} - Song's CCLI number
+ {SONG_LANGUAGE_SYMBOL} - Song's language
+ {SONG_URL_SYMBOL} - Song's source URL
+ {SONG_TAGS_SYMBOL} - Song's tags separated by commas, example: worship, slow, needs-practice
 
  {BLOCK_START} - Start of block (verse, chorus, bridge, etc.)
  {BLOCK_END} - End of block
@@ -140,7 +180,7 @@ fn recursive_find(dir: &Path, files: &mut Vec<(String, PathBuf)>, query: &str) -
         if path.is_dir() {
             recursive_find(&path, files, query)?;
         } else if let Some(name) = path.file_name().and_then(|n: &std::ffi::OsStr| n.to_str()) {
-            if name.contains(query) { files.push( (name.to_string(), path.to_path_buf()) ) }
+            if fold(name).contains(&fold(query)) { files.push( (name.to_string(), path.to_path_buf()) ) }
         }
     }
 
@@ -4,9 +4,9 @@ mod tui;
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
-use songbook::{Song, Note, Key, STRINGS};
-use songbook::song_library;
-use songbook::{Fingering, StringState};
+use songbook::{Song, Note, Key, Tuning, Instrument, SetlistEntry, Uuid};
+use songbook::song_library::{self, TextExportOptions};
+use songbook::{Fingering, Finger, StringState, PdfOptions};
 
 
 #[derive(Parser, Debug)]
@@ -24,8 +24,13 @@ enum Command {
 
     /// Print fretboard, for non-standart tuning use '-t'
     Fret {
-        #[arg(short, long, default_value = "E, B, G, D, A, E", value_name = "TUNING")]
-        tuning: String
+        /// Preset name (drop-d, open-g, dadgad...) or notes: E, B, G, D, A, E
+        #[arg(short, long, value_name = "TUNING")]
+        tuning: Option<String>,
+
+        /// guitar, 7-string, ukulele, baritone-ukulele, mandolin, bass, 5-string-bass
+        #[arg(short, long, default_value = "guitar")]
+        instrument: String
     },
 
     /// Print circle of fifth, alias - cof
@@ -36,9 +41,42 @@ enum Command {
         key: Option<String>
     },
 
-    /// Print chord's fingerings
-    Chord { chord: String },
+    /// Print the most playable fingering for a chord, use '--all' or '--limit' for alternatives
+    Chord {
+        chord: String,
+
+        /// Print all fingerings with their playability scores
+        #[arg(short, long)]
+        all: bool,
+
+        /// Print this many best fingerings with their playability scores
+        #[arg(short, long, conflicts_with = "all")]
+        limit: Option<usize>,
+
+        /// Preset name (drop-d, open-g, dadgad...) or notes: E, B, G, D, A, E
+        #[arg(short, long, value_name = "TUNING")]
+        tuning: Option<String>,
+
+        /// guitar, 7-string, ukulele, baritone-ukulele, mandolin, bass, 5-string-bass
+        #[arg(short, long, default_value = "guitar")]
+        instrument: String
+    },
     
+    /// Name a chord by its fingering, strings from the lowest like in tabs: x 3 2 0 1 0
+    Identify {
+        /// Strings' conditions (x - muted, 0 - open, 1-24 - fretted)
+        #[arg(num_args = 1.., required = true)]
+        strings: Vec<String>,
+
+        /// Preset name (drop-d, open-g, dadgad...) or notes: E, B, G, D, A, E
+        #[arg(short, long, value_name = "TUNING")]
+        tuning: Option<String>,
+
+        /// guitar, 7-string, ukulele, baritone-ukulele, mandolin, bass, 5-string-bass
+        #[arg(short, long, default_value = "guitar")]
+        instrument: String
+    },
+
     /// Manage your fingerings
     Fingering {
         /// Your chord name
@@ -48,11 +86,20 @@ enum Command {
         /// Strings' conditions (x - muted, 0 - open, 1-24 - fretted)
         #[arg(long, short, num_args = 1..)]
         fingering: Vec<String>,
+
+        /// Fingers for strings (1-4, T - thumb, '-' for open and muted strings), by default they're assigned automatically
+        #[arg(long, num_args = 1.., allow_hyphen_values = true)]
+        fingers: Vec<String>,
+
+        /// guitar, 7-string, ukulele, baritone-ukulele, mandolin, bass, 5-string-bass
+        #[arg(short, long, default_value = "guitar")]
+        instrument: String
     },
 
 
     /// Show song
     Show {
+        /// Path in the library or song's id
         path: PathBuf,
         
         /// Show in certain key
@@ -71,6 +118,10 @@ enum Command {
         #[arg(short, long)]
         fingerings: bool,
 
+        /// Instrument for fingerings: guitar, 7-string, ukulele, baritone-ukulele, mandolin, bass, 5-string-bass
+        #[arg(short, long, default_value = "guitar")]
+        instrument: String,
+
         /// Show notes
         #[arg(short, long)]
         notes: bool,
@@ -81,12 +132,32 @@ enum Command {
     },
 
     /// Edit song
-    Edit { path: PathBuf },
+    Edit {
+        /// Path in the library or song's id
+        path: PathBuf
+    },
+
+    /// Print song's id, or the path of the song with the id
+    Id { path: PathBuf },
+
+    /// Give ids to songs that have none or share an id with another song
+    AssignIds,
+
+    /// Check songs for chords that cannot be parsed
+    Check { path: Option<PathBuf> },
 
     /// Add a song to the library
     #[command(subcommand)]
     Add(AddSubcommand),
 
+    /// Export a song from the library to another format
+    #[command(subcommand)]
+    Export(ExportSubcommand),
+
+    /// Ordered lists of songs for a gig, stored in the data dir (songbook/setlists)
+    #[command(subcommand)]
+    Setlist(SetlistSubcommand),
+
     /// Sort songs in folders: artist/song
     Sort,
 
@@ -102,7 +173,25 @@ enum Command {
     },
 
     /// Print songs from the library
-    Ls { path: Option<PathBuf> },
+    Ls {
+        path: Option<PathBuf>,
+
+        /// Only songs with the tag, searched in subdirs too (can be repeated)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>
+    },
+
+    /// Search songs by title, artist, lyrics and notes: artist:beatles key:G capo:2 chord:F#m "let it be"
+    Search {
+        /// Words or field:value (title, artist, key, capo, chord, lyrics, notes, tag, album, year,
+        /// composer, lyricist, language, ccli, tempo, time)
+        #[arg(num_args = 1.., required = true)]
+        query: Vec<String>,
+
+        /// Allow typos and skipped letters, the best matches are printed first
+        #[arg(long)]
+        fuzzy: bool
+    },
 
     /// Print tree
     Tree { path: Option<PathBuf> },
@@ -114,19 +203,41 @@ enum Command {
 
 #[derive(Subcommand, Debug, Clone)]
 enum AddSubcommand {
+    /// Add songs from text files, without '-a' and '-t' they're taken from file names: Artist - Title.txt
     FromTxt {
-        path: PathBuf,
+        /// Files, dirs or patterns (songs/*.txt)
+        #[arg(num_args = 1.., required = true)]
+        paths: Vec<String>,
 
         /// Song's artist
         #[arg(long, short)]
-        artist: String,
+        artist: Option<String>,
 
-        /// Song's title
+        /// Song's title, only for one file
         #[arg(long, short)]
-        title: String,
+        title: Option<String>,
     },
     
-    FromChordpro { path: PathBuf },
+    /// Add songs from ChordPro files, a file can contain several songs split by {new_song}
+    FromChordpro {
+        /// Files, dirs or patterns (songs/*.cho)
+        #[arg(num_args = 1.., required = true)]
+        paths: Vec<String>
+    },
+
+    /// Add songs from OpenSong files (without an extension or .xml)
+    FromOpensong {
+        /// Files, dirs or patterns (songs/*)
+        #[arg(num_args = 1.., required = true)]
+        paths: Vec<String>
+    },
+
+    /// Add songs from OpenLyrics .xml files
+    FromOpenlyrics {
+        /// Files, dirs or patterns (songs/*.xml)
+        #[arg(num_args = 1.., required = true)]
+        paths: Vec<String>
+    },
 
     FromSbp { path: PathBuf },
     
@@ -141,6 +252,228 @@ enum AddSubcommand {
     }
 }
 
+#[derive(Subcommand, Debug, Clone)]
+enum ExportSubcommand {
+    Chordpro {
+        path: PathBuf,
+
+        /// Output file, without it the song is printed
+        #[arg(long, short)]
+        output: Option<PathBuf>
+    },
+
+    /// Export a song to OpenSong XML
+    Opensong {
+        path: PathBuf,
+
+        /// Output file, without it the song is printed
+        #[arg(long, short)]
+        output: Option<PathBuf>
+    },
+
+    /// Export a song to OpenLyrics XML
+    Openlyrics {
+        path: PathBuf,
+
+        /// Output file, without it the song is printed
+        #[arg(long, short)]
+        output: Option<PathBuf>
+    },
+
+    /// Export a song, a dir or the whole library (without a path) with a table of contents
+    Pdf {
+        path: Option<PathBuf>,
+
+        /// Output file
+        #[arg(long, short)]
+        output: PathBuf,
+
+        /// Show chords
+        #[arg(short, long)]
+        chords: bool,
+
+        /// Show rhythm
+        #[arg(short, long)]
+        rhythm: bool,
+        
+        /// Show fingerings
+        #[arg(short, long)]
+        fingerings: bool,
+
+        /// Instrument for fingerings: guitar, 7-string, ukulele, baritone-ukulele, mandolin, bass, 5-string-bass
+        #[arg(short, long, default_value = "guitar")]
+        instrument: String,
+
+        /// Show notes
+        #[arg(short, long)]
+        notes: bool,
+
+        /// Columns on a page
+        #[arg(long, default_value_t = 2)]
+        columns: usize,
+
+        /// Monospace TTF font, by default it's searched in the data dir (songbook/fonts) and in the system
+        #[arg(long)]
+        font: Option<PathBuf>
+    },
+
+    /// Export a song, a dir or the whole library (without a path) to SongbookPro, or a setlist as a set
+    Sbp {
+        path: Option<PathBuf>,
+
+        /// Export a setlist with its settings instead, the same as 'setlist export NAME -o file.sbp'
+        #[arg(long, short, conflicts_with = "path")]
+        setlist: Option<String>,
+
+        /// Output file
+        #[arg(long, short)]
+        output: PathBuf
+    },
+
+    /// Export a song, a dir or the whole library (without a path) to plain text
+    Txt(TextExportArgs),
+
+    /// Export a song, a dir or the whole library (without a path) to Markdown
+    Md(TextExportArgs),
+
+    /// Export a song to a page, a dir or the whole library (without a path) to a static site
+    Html {
+        path: Option<PathBuf>,
+
+        /// Output file for a song (without it the page is printed) or a dir for several songs
+        #[arg(long, short)]
+        output: Option<PathBuf>
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum SetlistSubcommand {
+    /// Create an empty setlist
+    New { name: String },
+
+    /// Add songs (paths relative to the library) with settings only for this setlist
+    Add {
+        name: String,
+
+        #[arg(num_args = 1.., required = true)]
+        songs: Vec<PathBuf>,
+
+        /// Play in certain key
+        #[arg(short, long)]
+        key: Option<String>,
+
+        /// Transpose by semitones, ignored with '--key'
+        #[arg(short, long, allow_hyphen_values = true)]
+        transpose: Option<i32>,
+
+        /// Play with a capo, the song sounds the same, chords are changed
+        #[arg(short, long)]
+        capo: Option<u8>,
+
+        /// Notes shown before the song's notes
+        #[arg(short, long)]
+        notes: Option<String>,
+
+        /// Autoscroll speed in milliseconds
+        #[arg(short, long)]
+        speed: Option<u64>,
+
+        /// Position in the setlist starting from 1, by default songs are added to the end
+        #[arg(short, long)]
+        position: Option<usize>
+    },
+
+    /// Remove a song by its position, without a position the whole setlist is removed
+    Rm {
+        name: String,
+        position: Option<usize>
+    },
+
+    /// Move a song from one position to another
+    Reorder {
+        name: String,
+        from: usize,
+        to: usize
+    },
+
+    /// Print a setlist, without a name all setlists are printed
+    Show { name: Option<String> },
+
+    /// Export songs with the setlist's settings, the format is taken from the extension: .txt, .md, .pdf, .sbp
+    Export {
+        name: String,
+
+        /// Output file, without it the songs are printed as text
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Show chords
+        #[arg(short, long)]
+        chords: bool,
+
+        /// Show rhythm
+        #[arg(short, long)]
+        rhythm: bool,
+
+        /// Show fingerings
+        #[arg(short, long)]
+        fingerings: bool,
+
+        /// Instrument for fingerings: guitar, 7-string, ukulele, baritone-ukulele, mandolin, bass, 5-string-bass
+        #[arg(short, long, default_value = "guitar")]
+        instrument: String,
+
+        /// Show notes
+        #[arg(short, long)]
+        notes: bool,
+
+        /// Columns on a page for pdf
+        #[arg(long, default_value_t = 2)]
+        columns: usize,
+
+        /// Monospace TTF font for pdf
+        #[arg(long)]
+        font: Option<PathBuf>
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct TextExportArgs {
+    path: Option<PathBuf>,
+
+    /// Output file or dir, a dir is exported to the same tree of dirs (the current dir by default)
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Print instead of writing files
+    #[arg(long, conflicts_with = "output")]
+    stdout: bool,
+
+    /// Export in certain key
+    #[arg(short, long)]
+    key: Option<String>,
+
+    /// Show chords
+    #[arg(short, long)]
+    chords: bool,
+
+    /// Show rhythm
+    #[arg(short, long)]
+    rhythm: bool,
+
+    /// Show fingerings
+    #[arg(short, long)]
+    fingerings: bool,
+
+    /// Instrument for fingerings: guitar, 7-string, ukulele, baritone-ukulele, mandolin, bass, 5-string-bass
+    #[arg(short, long, default_value = "guitar")]
+    instrument: String,
+
+    /// Show notes
+    #[arg(short, long)]
+    notes: bool
+}
+
 
 fn main() {
     let args = Args::parse();
@@ -148,35 +481,38 @@ fn main() {
     if let Some(command) = args.command {
         match command {
             Command::Init => song_library::init().expect("Error during initialisation!"),
-            Command::Fret { tuning } => {
-                // check tuning
-                let mut notes = [Note::A; STRINGS];
-                let mut counter = 0;
-                for n in tuning.split(", ") {
-                    if let Some(note) = Note::new(&n) {
-                        notes[counter] = note;
-                    } else {
-                        println!("Unknown note: {n}!");
-                        return
-                    }
-                    counter += 1;
-                    if counter == 6 { break }
-                }
-                if counter != STRINGS {
-                    println!("Notes must be {STRINGS}!");
-                    return
-                }
-
-                songbook::print_fretboard(&notes);
+            Command::Fret { tuning, instrument } => {
+                let Some(instrument) = parse_instrument(&instrument) else { return };
+                let Some(tuning) = get_tuning(tuning.as_deref(), instrument) else { return };
+                songbook::print_fretboard(&tuning, instrument.get_frets());
             },
             Command::CircleOfFifth{key} => {
                 let key = if let Some(k) = key.as_deref() { Key::new(k) }
                 else { None };
                 songbook::print_circle_of_fifth(key);
             },
-            Command::Chord { chord } => {
+            Command::Chord { chord, all, limit, tuning, instrument } => {
+                let Some(instrument) = parse_instrument(&instrument) else { return };
+                let Some(tuning) = get_tuning(tuning.as_deref(), instrument) else { return };
                 if let Some(chord) = songbook::Chord::new(&chord) {
-                    let fings = chord.get_fingerings(&songbook::STANDART_TUNING);
+                    let mut fings = chord.get_fingerings(instrument, &tuning);
+                    if fings.is_empty() {
+                        println!("Cannot find fingerings for this chord!");
+                        return
+                    }
+
+                    // аппликатуры уже отсортированы по удобству
+                    if all || limit.is_some() {
+                        fings.truncate(limit.unwrap_or(fings.len()));
+                        for f in &mut fings {
+                            if let Some(score) = f.get_score() {
+                                f.set_title( Some(format!("{} ({})", chord.text, score)) );
+                            }
+                        }
+                    } else {
+                        fings.truncate(1);
+                    }
+
                     if let Some(text) = songbook::sum_text_in_fingerings(&fings, None) {
                         println!("{text}");
                     }
@@ -184,9 +520,37 @@ fn main() {
                     println!("Unknown chord!");
                 }
             },
-            Command::Fingering { chord, fingering } => {
-                if fingering.len() != STRINGS {
-                    println!("Len --fingering must be {}!", STRINGS);
+            Command::Identify { strings, tuning, instrument } => {
+                let Some(instrument) = parse_instrument(&instrument) else { return };
+                let Some(tuning) = get_tuning(tuning.as_deref(), instrument) else { return };
+                if strings.len() != tuning.len() {
+                    println!("There must be {} strings!", tuning.len());
+                    return
+                }
+
+                // в табах первой идёт самая низкая струна, а в Fingering - первая (тонкая)
+                let mut states = Vec::new();
+                for s in strings.iter().rev() {
+                    let Some(state) = parse_string_state(s) else {
+                        println!("Unknown string state: {s}!");
+                        return
+                    };
+                    states.push(state);
+                }
+
+                let chords = songbook::identify(instrument, &tuning, &states);
+                if chords.is_empty() {
+                    println!("Cannot name this chord!");
+                }
+                for chord in chords {
+                    println!("{}", chord.text);
+                }
+            },
+            Command::Fingering { chord, fingering, fingers, instrument } => {
+                let Some(instrument) = parse_instrument(&instrument) else { return };
+                let strings_count = instrument.get_strings();
+                if fingering.len() != strings_count {
+                    println!("Len --fingering must be {}!", strings_count);
                     return
                 }
                 let allowed = ["x", "0", 
@@ -200,7 +564,7 @@ fn main() {
                     return
                 }
                 
-                let mut strings = [StringState::Muted; STRINGS];
+                let mut strings = vec![StringState::Muted; strings_count];
                 for (i, f) in fingering.iter().enumerate() {
                     match f {
                         c if c == "x" => {},
@@ -212,12 +576,24 @@ fn main() {
                     }
                 }
                 
-                let fing = Fingering::new(strings, Some(chord)).unwrap();
-                song_library::add_fingering(&fing).expect("Error during saving a fingering!");
+                let Some(mut fing) = Fingering::new(strings, Some(chord)) else {
+                    println!("This fingering needs more than 4 fingers!");
+                    return
+                };
+                if !fingers.is_empty() {
+                    let fingers: Vec<Option<Finger>> = fingers.iter().map(|f| Finger::new(f)).collect();
+                    if !fing.set_fingers(fingers) {
+                        println!("Every fretted string needs a finger (1-4 or T), open and muted strings - '-'!");
+                        return
+                    }
+                }
+                song_library::add_fingering(&fing, instrument).expect("Error during saving a fingering!");
             },
-            Command::Show { path, key, chords, rhythm, fingerings, notes, colored } => {
+            Command::Show { path, key, chords, rhythm, fingerings, notes, colored, instrument } => {
                 let key = if let Some(k) = key.as_deref() { Key::new(k) }
                 else { None };
+                let Some(instrument) = parse_instrument(&instrument) else { return };
+                let fingerings = if fingerings { Some(instrument) } else { None };
 
                 song_library::show(&path, key, chords, rhythm, fingerings, notes, colored)
                     .expect("Error during geting song!");
@@ -226,33 +602,49 @@ fn main() {
                 song_library::edit(&path)
                     .expect("Error during editing song!");
             },
+            Command::Id { path } => {
+                match path.to_str().and_then(|p| Uuid::parse_str(p.trim()).ok()) {
+                    Some(id) => {
+                        let path = song_library::get_path_by_id(&id)
+                            .expect("Error during searching the song!");
+                        println!("{}", path.display());
+                    },
+                    None => {
+                        let id = song_library::get_id(&path)
+                            .expect("Error during getting the id!");
+                        println!("{id}");
+                    }
+                }
+            },
+            Command::Check { path } => {
+                song_library::check(path.as_deref())
+                    .expect("Error during checking songs!");
+            },
             Command::Add(subcommand) => match subcommand {
-                AddSubcommand::FromTxt { path, title, artist } => {
-                    let song = Song::from_txt( &path, &title, &artist)
-                        .expect("Error during adding a song!");
-
-                    song_library::add(&song)
-                        .expect("Error during adding a song!");
+                AddSubcommand::FromTxt { paths, title, artist } => {
+                    let report = song_library::import_txt(&paths, artist.as_deref(), title.as_deref())
+                        .expect("Error during adding songs!");
+                    print!("{report}");
                 },
-                AddSubcommand::FromChordpro { path } => {
-                    let song = Song::from_chordpro(&path)
-                        .expect("Error during adding a song!");
-
-                    song_library::add(&song)
-                        .expect("Error during adding a song!");
+                AddSubcommand::FromChordpro { paths } => {
+                    let report = song_library::import_chordpro(&paths)
+                        .expect("Error during adding songs!");
+                    print!("{report}");
+                },
+                AddSubcommand::FromOpensong { paths } => {
+                    let report = song_library::import_opensong(&paths)
+                        .expect("Error during adding songs!");
+                    print!("{report}");
+                },
+                AddSubcommand::FromOpenlyrics { paths } => {
+                    let report = song_library::import_openlyrics(&paths)
+                        .expect("Error during adding songs!");
+                    print!("{report}");
                 },
                 AddSubcommand::FromSbp { path } => {
-                    let songs = Song::from_sbp(&path)
-                        .expect("Error during adding a song!");
-                    for song in &songs {
-                        if let Err(msg) = song_library::add(song) {
-                            println!("Cannot add song: {} - {}! {}",
-                                song.metadata.artist,
-                                song.metadata.title,
-                                msg
-                            );
-                        }
-                    }
+                    let report = song_library::import_sbp(&path)
+                        .expect("Error during adding songs!");
+                    print!("{report}");
                 },
                 AddSubcommand::Empty { title, artist } => {
                     let song = Song::new(&title, &artist);
@@ -260,6 +652,49 @@ fn main() {
                         .expect("Error during adding a song!");
                 }
             },
+            Command::Export(subcommand) => match subcommand {
+                ExportSubcommand::Chordpro { path, output } => {
+                    song_library::export_chordpro(&path, output.as_deref())
+                        .expect("Error during exporting a song!");
+                },
+                ExportSubcommand::Opensong { path, output } => {
+                    song_library::export_opensong(&path, output.as_deref())
+                        .expect("Error during exporting a song!");
+                },
+                ExportSubcommand::Openlyrics { path, output } => {
+                    song_library::export_openlyrics(&path, output.as_deref())
+                        .expect("Error during exporting a song!");
+                },
+                ExportSubcommand::Pdf { path, output, chords, rhythm, fingerings, instrument, notes, columns, font } => {
+                    let Some(instrument) = parse_instrument(&instrument) else { return };
+                    let options = PdfOptions {
+                        columns,
+                        chords,
+                        rhythm,
+                        fingerings: if fingerings { Some(instrument) } else { None },
+                        notes
+                    };
+                    song_library::export_pdf(path.as_deref(), &output, &options, font.as_deref())
+                        .expect("Error during exporting songs!");
+                },
+                ExportSubcommand::Sbp { path, setlist, output } => {
+                    match setlist {
+                        Some(name) => song_library::setlist_export_sbp(&name, &output),
+                        None => song_library::export_sbp(path.as_deref(), &output)
+                    }.expect("Error during exporting songs!");
+                },
+                ExportSubcommand::Txt(args) => export_text(args, false),
+                ExportSubcommand::Md(args) => export_text(args, true),
+                ExportSubcommand::Html { path, output } => {
+                    song_library::export_html(path.as_deref(), output.as_deref())
+                        .expect("Error during exporting songs!");
+                }
+            },
+            Command::AssignIds => {
+                let paths = song_library::assign_ids()
+                    .expect("Error during assigning ids!");
+                for path in paths { println!("{}", path.display()) }
+            },
             Command::Sort => song_library::sort()
                 .expect("Error during sorting!"),
             Command::Rm { paths } => {
@@ -274,10 +709,62 @@ fn main() {
                         .expect("Error during moving!");
                 }
             },
-            Command::Ls { path } => {
-                song_library::ls(path.as_deref())
+            Command::Ls { path, tags } => {
+                song_library::ls(path.as_deref(), &tags)
                     .expect("Error during reading a dir!");
             },
+            Command::Setlist(subcommand) => match subcommand {
+                SetlistSubcommand::New { name } => {
+                    song_library::setlist_new(&name)
+                        .expect("Error during creating a setlist!");
+                },
+                SetlistSubcommand::Add { name, songs, key, transpose, capo, notes, speed, position } => {
+                    let key = match key.as_deref() {
+                        Some(k) => match Key::new(k) {
+                            Some(key) => Some(key),
+                            None => {
+                                println!("Unknown key: {k}!");
+                                return
+                            }
+                        },
+                        None => None
+                    };
+                    let entry = SetlistEntry {
+                        key,
+                        key_shift: transpose.filter(|t| *t != 0),
+                        capo,
+                        notes,
+                        autoscroll_speed: speed,
+                        ..SetlistEntry::new(PathBuf::new())
+                    };
+                    song_library::setlist_add(&name, &songs, &entry, position)
+                        .expect("Error during adding songs to a setlist!");
+                },
+                SetlistSubcommand::Rm { name, position } => {
+                    song_library::setlist_rm(&name, position)
+                        .expect("Error during removing from a setlist!");
+                },
+                SetlistSubcommand::Reorder { name, from, to } => {
+                    song_library::setlist_reorder(&name, from, to)
+                        .expect("Error during reordering a setlist!");
+                },
+                SetlistSubcommand::Show { name } => {
+                    song_library::setlist_show(name.as_deref())
+                        .expect("Error during reading setlists!");
+                },
+                SetlistSubcommand::Export { name, output, chords, rhythm, fingerings, instrument, notes, columns, font } => {
+                    let Some(instrument) = parse_instrument(&instrument) else { return };
+                    let fingerings = if fingerings { Some(instrument) } else { None };
+                    let options = TextExportOptions { markdown: false, key: None, chords, rhythm, fingerings, notes };
+                    let pdf_options = PdfOptions { columns, chords, rhythm, fingerings, notes };
+                    song_library::setlist_export(&name, output.as_deref(), &options, (&pdf_options, font.as_deref()))
+                        .expect("Error during exporting a setlist!");
+                }
+            },
+            Command::Search { query, fuzzy } => {
+                song_library::search(&query, fuzzy)
+                    .expect("Error during searching songs!");
+            },
             Command::Tree { path } => {
                 song_library::tree(path.as_deref())
                     .expect("Error during reading a dir!");
@@ -296,3 +783,65 @@ fn main() {
             .expect("Error in TUI!");
     }
 }
+
+
+fn parse_instrument(text: &str) -> Option<Instrument> {
+    let instrument = Instrument::new(text);
+    if instrument.is_none() {
+        let all: Vec<String> = Instrument::ALL.iter().map(|i| i.to_string()).collect();
+        println!("Unknown instrument: {text}!");
+        println!("Use one of: {}", all.join(", "));
+    }
+
+    return instrument
+}
+
+fn export_text(args: TextExportArgs, markdown: bool) {
+    let Some(instrument) = parse_instrument(&args.instrument) else { return };
+    let key = if let Some(k) = args.key.as_deref() { Key::new(k) }
+    else { None };
+    let options = TextExportOptions {
+        markdown,
+        key,
+        chords: args.chords,
+        rhythm: args.rhythm,
+        fingerings: if args.fingerings { Some(instrument) } else { None },
+        notes: args.notes
+    };
+
+    let output = if args.stdout { None }
+    else { Some(args.output.unwrap_or(PathBuf::from("."))) };
+    song_library::export_text(args.path.as_deref(), output.as_deref(), &options)
+        .expect("Error during exporting songs!");
+}
+
+// Строй из '-t' или стандартный строй инструмента
+fn get_tuning(text: Option<&str>, instrument: Instrument) -> Option<Vec<Note>> {
+    let Some(text) = text else { return Some(instrument.get_tuning()) };
+
+    let Some(tuning) = Tuning::new(text) else {
+        let presets: Vec<String> = Tuning::PRESETS.iter().map(|t| t.to_string()).collect();
+        println!("Unknown tuning: {text}!");
+        println!("Use one of presets ({}) or notes from the first string, example: E, B, G, D, A, E", presets.join(", "));
+        return None
+    };
+
+    let notes = tuning.get_notes();
+    if notes.len() != instrument.get_strings() {
+        println!("Tuning for {} must have {} notes!", instrument, instrument.get_strings());
+        return None
+    }
+
+    return Some(notes)
+}
+
+fn parse_string_state(text: &str) -> Option<StringState> {
+    match text {
+        "x" | "X" => Some(StringState::Muted),
+        "0" => Some(StringState::Open),
+        _ => match text.parse::<u8>() {
+            Ok(fret) if (1..=24).contains(&fret) => Some(StringState::FrettedOn(fret)),
+            _ => None
+        }
+    }
+}
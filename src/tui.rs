@@ -15,7 +15,8 @@ use ratatui::widgets::{ListState, TableState};
 use crossterm::event::{Event, KeyEvent, KeyCode};
 
 use songbook::song_library::lib_functions::*;
-use songbook::Song;
+use songbook::song_library::index::LibraryIndex;
+use songbook::{Song, Instrument, Setlist};
 
 use config::Config;
 
@@ -65,10 +66,13 @@ struct App {
     is_long_command: bool,
     long_command: String,
 
+    warning: Option<Vec<String>>, // всплывающее окно с предупреждениями, закрывается любой клавишей
+
     help_table_state: TableState,
 
     lib_list_state: ListState,
     lib_list: Vec<(String, PathBuf)>,
+    lib_index: Option<LibraryIndex>, // пока вводится поиск, чтобы не читать индекс на каждую букву
     current_dir: PathBuf,
     last_dirs: Vec<PathBuf>,
     cutted_path: Option<PathBuf>,
@@ -76,6 +80,9 @@ struct App {
     selected_paths: Vec<PathBuf>,
     action_with_selected_paths: ActionWithSelectedPaths,
 
+    setlist: Option<(String, Setlist)>, // имя файла и сет-лист, пока он открыт в библиотеке
+    setlist_index: usize, // песня сет-листа, которая сейчас показана
+
     current_song: Option<(Song, PathBuf)>,
     song_area_height: Option<usize>,
     song_area_width: Option<usize>,
@@ -83,6 +90,7 @@ struct App {
     show_rhythm: bool,
     show_fingerings: bool,
     show_notes: bool,
+    instrument: Instrument, // для аппликатур
 
     scroll_y: u16,
     scroll_x: u16,
@@ -91,6 +99,7 @@ struct App {
 
     autoscroll: bool,
     autoscroll_speed: Duration,
+    opened_autoscroll_speed: Duration, // скорость при открытии песни, для сет-листа
     last_scroll_time: Instant
 }
 
@@ -106,15 +115,19 @@ impl App {
             hide_lib: false,
             is_long_command: false,
             long_command: String::new(),
+            warning: None,
             help_table_state: TableState::new().with_selected(Some(0)),
             lib_list_state: ListState::default().with_selected(Some(0)),
             lib_list,
+            lib_index: None,
             current_dir,
             last_dirs: Vec::new(),
             cutted_path: None,
             copied_path: None,
             selected_paths: Vec::new(),
             action_with_selected_paths: ActionWithSelectedPaths::Nothing,
+            setlist: None,
+            setlist_index: 0,
             current_song: None,
             song_area_height: None,
             song_area_width: None,
@@ -122,12 +135,14 @@ impl App {
             show_rhythm: true,
             show_fingerings: false,
             show_notes: true,
+            instrument: Instrument::Guitar,
             scroll_y: 0,
             scroll_x: 0,
             scroll_y_max: 0,
             scroll_x_max: 0,
             autoscroll: false,
             autoscroll_speed: DEFAULT_AUTOSCROLL_SPEED,
+            opened_autoscroll_speed: DEFAULT_AUTOSCROLL_SPEED,
             last_scroll_time: Instant::now()
         })
     }
@@ -163,14 +178,23 @@ impl App {
 
     fn handle_main_key_event(&mut self, key_event: KeyEvent, terminal: &mut DefaultTerminal) -> Result<()> {
         let mut is_song_changed = false;
+        if key_event.kind.is_press() && self.warning.is_some() {
+            self.warning = None;
+            return Ok(())
+        }
+
         if key_event.kind.is_press() {
             match key_event.code {
                 KeyCode::F(1) => self.current_screen = Screen::Help,
 
 
-                KeyCode::Char(c) if self.is_long_command => self.long_command.push(c),
+                KeyCode::Char(c) if self.is_long_command => {
+                    self.long_command.push(c);
+                    self.update_search()?;
+                },
                 KeyCode::Backspace if self.is_long_command => {
                     self.long_command.pop();
+                    self.update_search()?;
                     if self.long_command.is_empty() { self.is_long_command = false }
                 },
                 KeyCode::Enter if self.is_long_command => {
@@ -198,7 +222,13 @@ impl App {
             }
         }
 
-        if let Some( (song, _p) ) = &mut self.current_song {
+        if self.setlist.is_some() {
+            // В сет-лист скорость пишется, только если её поменяли
+            if self.autoscroll_speed != self.opened_autoscroll_speed {
+                self.opened_autoscroll_speed = self.autoscroll_speed;
+                is_song_changed = true;
+            }
+        } else if let Some( (song, _p) ) = &mut self.current_song {
             if let Some(speed) = song.metadata.autoscroll_speed &&
                 Duration::from_millis(speed) == self.autoscroll_speed {
             } else {
@@ -211,7 +241,14 @@ impl App {
 
 
         if is_song_changed {
-            if let Some( (song, path) ) = &self.current_song {
+            if let Some( (name, setlist) ) = &mut self.setlist {
+                // Песни сет-листа не меняются, скорость прокрутки сохраняется в сет-листе
+                if let Some(entry) = setlist.songs.get_mut(self.setlist_index)
+                    && let Ok(speed) = self.autoscroll_speed.as_millis().try_into() {
+                    entry.autoscroll_speed = Some(speed);
+                    songbook::song_library::save_setlist(name, setlist)?;
+                }
+            } else if let Some( (song, path) ) = &self.current_song {
                 save(song, path)?;
             }
         }
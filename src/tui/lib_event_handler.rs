@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::time::Duration;
 use anyhow::Result;
 
@@ -5,7 +6,9 @@ use crossterm::event::{KeyEvent, KeyCode};
 use rfd::FileDialog;
 
 use songbook::song_library::lib_functions::*;
+use songbook::song_library::index::{LibraryIndex, Query};
 use songbook::Song;
+use songbook::fuzzy::fold;
 
 use super::{Focus, DEFAULT_AUTOSCROLL_SPEED, App};
 
@@ -14,8 +17,10 @@ use super::{Focus, DEFAULT_AUTOSCROLL_SPEED, App};
 impl App {
     pub fn handle_lib_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
         use super::ActionWithSelectedPaths::*;
+        if self.setlist.is_some() { return self.handle_setlist_key_event(key_event) }
 
         match key_event.code {
+            KeyCode::Char('L') |
             KeyCode::Char('N') |
             KeyCode::Char('R') |
             KeyCode::Char('F') |
@@ -99,17 +104,7 @@ impl App {
                         self.lib_list_state.select_first();
                     } else if path.is_file() {
                         if let Ok(song) = get_song(&path) {
-                            self.focus = Focus::Song;
-                            self.scroll_y = 0;
-                            self.scroll_x = 0;
-                            self.autoscroll = false;
-                            self.autoscroll_speed = if let Some(speed) = song.metadata.autoscroll_speed {
-                                Duration::from_millis(speed)
-                            } else {
-                                DEFAULT_AUTOSCROLL_SPEED
-                            };
-
-                            self.current_song = Some( (song, path.to_path_buf()) );
+                            self.open_song(song, path.to_path_buf());
                         }
                     }
                 }
@@ -147,12 +142,115 @@ impl App {
     }
 
 
+    fn open_song(&mut self, song: Song, path: PathBuf) {
+        self.focus = Focus::Song;
+        self.scroll_y = 0;
+        self.scroll_x = 0;
+        self.autoscroll = false;
+        self.autoscroll_speed = if let Some(speed) = song.metadata.autoscroll_speed {
+            Duration::from_millis(speed)
+        } else {
+            DEFAULT_AUTOSCROLL_SPEED
+        };
+        self.opened_autoscroll_speed = self.autoscroll_speed;
+
+        self.current_song = Some( (song, path) );
+    }
+
+    // В сет-листе только перемещение по нему, песни и папки не меняются
+    fn handle_setlist_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        match key_event.code {
+            KeyCode::Char('j') | KeyCode::Down => self.lib_list_state.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => self.lib_list_state.select_previous(),
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => if let Some(selected) = self.lib_list_state.selected() {
+                self.open_setlist_song(selected)?
+            },
+            KeyCode::Char('n') => self.open_setlist_song(self.setlist_index + 1)?,
+            KeyCode::Char('p') => if let Some(index) = self.setlist_index.checked_sub(1) {
+                self.open_setlist_song(index)?
+            },
+            KeyCode::Char('h') | KeyCode::Left | KeyCode::Backspace => {
+                self.setlist = None;
+                // Песню с настройками сет-листа нельзя сохранять в библиотеку
+                self.current_song = None;
+                self.update_lib_list()?;
+                self.lib_list_state.select_first();
+            },
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn open_setlist(&mut self, name: &str) -> Result<()> {
+        let setlist = songbook::song_library::get_setlist(name)?;
+        let lib_path = songbook::song_library::get_lib_path()?;
+        self.lib_list = setlist.songs.iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let title = match get_song(&entry.path) {
+                    Ok(song) => format!("{} - {}", song.metadata.artist, song.metadata.title),
+                    Err(_) => format!("(missing) {}", entry.path.display())
+                };
+                (format!("{}. {}", i + 1, title), lib_path.join(&entry.path))
+            })
+            .collect();
+        self.lib_list_state.select_first();
+        self.setlist = Some( (name.to_string(), setlist) );
+        self.setlist_index = 0;
+
+        Ok(())
+    }
+
+    pub fn open_setlist_song(&mut self, index: usize) -> Result<()> {
+        let Some( (_, setlist) ) = &self.setlist else { return Ok(()) };
+        let Some(entry) = setlist.songs.get(index) else { return Ok(()) };
+        let path = songbook::song_library::get_lib_path()?.join(&entry.path);
+        // Пропавшая песня просто не открывается
+        let Ok(mut song) = get_song(&path) else { return Ok(()) };
+        entry.apply(&mut song);
+
+        self.setlist_index = index;
+        self.lib_list_state.select(Some(index));
+        self.open_song(song, path);
+
+        Ok(())
+    }
+
+    // Список библиотеки фильтруется, пока вводится запрос для F
+    pub fn update_search(&mut self) -> Result<()> {
+        if self.focus != Focus::Library || !self.long_command.starts_with('F') { return Ok(()) }
+
+        let query: String = self.long_command.chars().skip(1).collect();
+        if query.trim().is_empty() {
+            self.lib_index = None;
+            self.update_lib_list()?;
+            self.lib_list_state.select_first();
+            return Ok(())
+        }
+
+        if self.lib_index.is_none() { self.lib_index = Some(LibraryIndex::load()?) }
+        if let Some(index) = &self.lib_index {
+            self.lib_list = LibraryIndex::get_list(&index.search_fuzzy(&Query::new(&query)))?;
+            self.lib_list_state.select_first();
+        }
+
+        Ok(())
+    }
+
     pub fn handle_long_command_in_library(&mut self) -> Result<()> {
         let command = if let Some(c) = self.long_command.chars().next() { c }
             else { return Ok(()) };
         let command_data: String = self.long_command.chars().skip(1).collect();
         if command_data.is_empty() { return Ok(()) }
         match command {
+            'L' => {
+                let names = songbook::song_library::get_setlist_names()?;
+                // Полное имя или его начало
+                let name = names.iter().find(|n| **n == command_data)
+                    .or_else(|| names.iter().find(|n| fold(n).starts_with(&fold(&command_data))));
+                if let Some(name) = name.cloned() { self.open_setlist(&name)? }
+            },
             'N' => {
                 songbook::song_library::mkdir(
                     &self.current_dir.join(command_data)
@@ -170,7 +268,11 @@ impl App {
             },
             'F' => {
                 self.current_dir = songbook::song_library::get_lib_path()?;
-                self.lib_list = find(&command_data)?;
+                let index = match self.lib_index.take() {
+                    Some(index) => index,
+                    None => LibraryIndex::load()?
+                };
+                self.lib_list = LibraryIndex::get_list(&index.search_fuzzy(&Query::new(&command_data)))?;
             },
             'A' => {
                 let subcommand = if let Some(c) = command_data.chars().nth(0) { c }
@@ -200,21 +302,28 @@ impl App {
                         if let Some(file) = FileDialog::new()
                             .add_filter("text", &["chordpo", "cho"])
                             .pick_file() {
-                            let mut song = Song::from_chordpro(&file)?;
-                            if let Some( (artist, title) ) = meta {
-                                song.metadata.title = title;
-                                song.metadata.artist = artist;
+                            let songs = Song::from_chordpro(&file)?;
+                            let is_single = songs.len() == 1;
+                            for result in songs {
+                                let (mut song, fingerings, _warnings) = result?;
+                                // Без аппликатуры песню всё равно можно добавить
+                                for (instrument, fing) in &fingerings {
+                                    let _ = songbook::song_library::add_fingering(fing, *instrument);
+                                }
+                                if is_single && let Some( (artist, title) ) = &meta {
+                                    song.metadata.title = title.clone();
+                                    song.metadata.artist = artist.clone();
+                                }
+                                songbook::song_library::add(&song)?;
                             }
+                            self.update_lib_list()?;
 
-                            Some(song)
+                            None
                         } else { None }
                     },
                     's' => {
                         if let Some(file) = FileDialog::new() .pick_file() {
-                            let songs = Song::from_sbp(&file)?;
-                            for s in songs {
-                                songbook::song_library::add(&s)?;
-                            }
+                            songbook::song_library::import_sbp(&file)?;
                             self.update_lib_list()?;
 
                             None
@@ -1,9 +1,11 @@
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Layout};
-use ratatui::widgets::{Block, Paragraph, List, ListItem, Table, Row};
+use ratatui::widgets::{Block, Paragraph, List, ListItem, Table, Row, Clear, Wrap};
 use ratatui::prelude::*;
 use Constraint::{Percentage, Fill, Length};
 
+use songbook::{Song, Instrument};
+
 use super::{
     song_formater,
     Focus,
@@ -55,10 +57,13 @@ impl App {
                 .highlight_symbol("->")
                 .block(
                     Block::bordered().title(
-                        self.current_dir
-                            .file_name()
-                            .and_then(|n| n.to_str())
-                            .unwrap_or("library")
+                        match &self.setlist {
+                            Some( (_, setlist) ) => setlist.name.as_str(),
+                            None => self.current_dir
+                                .file_name()
+                                .and_then(|n| n.to_str())
+                                .unwrap_or("library")
+                        }
                         )
 
                         .border_style(if self.focus == Focus::Library {
@@ -81,7 +86,17 @@ impl App {
 
         let title: String;
         let title_top: String;
+        let shifted_song: Song;
         let song = if let Some((song, _p)) = &self.current_song {
+            // Сдвиг тональности только для показа, в файле аккорды не меняются
+            let song = if song.metadata.key_shift.is_some() {
+                shifted_song = {
+                    let mut s = song.clone();
+                    s.apply_key_shift();
+                    s
+                };
+                &shifted_song
+            } else { song };
             title = format!("{} - {}", song.metadata.artist, song.metadata.title);
 
             let mut t_top_buf = String::new();
@@ -101,6 +116,23 @@ impl App {
                 t_top_buf.push_str("Capo: ");
                 t_top_buf.push_str(&capo.to_string());
             }
+            if let Some(tuning) = &song.metadata.tuning {
+                if !t_top_buf.is_empty() { t_top_buf.push_str(", ") }
+                t_top_buf.push_str("Tuning: ");
+                t_top_buf.push_str(&tuning.to_string());
+            }
+            if let Some(tempo) = song.metadata.tempo {
+                if !t_top_buf.is_empty() { t_top_buf.push_str(", ") }
+                t_top_buf.push_str(&format!("{tempo} BPM"));
+            }
+            if let Some(time) = &song.metadata.time {
+                if !t_top_buf.is_empty() { t_top_buf.push_str(", ") }
+                t_top_buf.push_str(time);
+            }
+            if self.show_fingerings && self.instrument != Instrument::Guitar {
+                if !t_top_buf.is_empty() { t_top_buf.push_str(", ") }
+                t_top_buf.push_str(&self.instrument.to_string());
+            }
             title_top = t_top_buf;
 
 
@@ -115,7 +147,7 @@ impl App {
                 width,
                 self.show_chords,
                 self.show_rhythm,
-                self.show_fingerings,
+                if self.show_fingerings { Some(self.instrument) } else { None },
                 self.show_notes,
                 [title_color, chords_color, rhythm_color, notes_color, text_color]
             );
@@ -139,6 +171,22 @@ impl App {
                 ))
         );
         frame.render_widget(song, song_area);
+
+        if let Some(warning) = &self.warning {
+            let area = frame.area().centered(Percentage(60), Percentage(50));
+            let popup = Paragraph::new(
+                    warning.iter().map(|w| Line::from(w.as_str())).collect::<Vec<Line>>()
+                )
+                .wrap(Wrap { trim: false })
+                .block(
+                    Block::bordered()
+                        .title(Line::from(" Unknown chords ").centered())
+                        .title_bottom(Line::from(" Press any key ").centered())
+                        .border_style(Style::new().fg(Color::Yellow))
+                );
+            frame.render_widget(Clear, area);
+            frame.render_widget(popup, area);
+        }
     }
 
 
@@ -231,7 +279,7 @@ impl App {
             Row::new(vec![
                 Line::from("F(find query)"),
                 Line::default(),
-                Line::from("Find")
+                Line::from("Find, filters while typing")
             ]),
 
             Row::new(vec![
@@ -239,6 +287,12 @@ impl App {
                 Line::default(),
                 Line::from("Add song")
             ]),
+
+            Row::new(vec![
+                Line::from("L(setlist name)"),
+                Line::default(),
+                Line::from("Open setlist, h - close")
+            ]),
             Row::new(vec!["", " ", ""]),
 
 
@@ -319,10 +373,22 @@ impl App {
                 Line::from("Toggle fingerings")
             ]),
 
+            Row::new(vec![
+                Line::from("i"),
+                Line::default(),
+                Line::from("Next instrument")
+            ]),
+
             Row::new(vec![
                 Line::from("n"),
                 Line::default(),
-                Line::from("Toggle notes")
+                Line::from("Toggle notes, next song in setlist")
+            ]),
+
+            Row::new(vec![
+                Line::from("p, N"),
+                Line::default(),
+                Line::from("Previous song and toggle notes in setlist")
             ]),
 
             Row::new(vec![
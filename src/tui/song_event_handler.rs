@@ -4,6 +4,7 @@ use anyhow::Result;
 use ratatui::{DefaultTerminal};
 use crossterm::event::{KeyEvent, KeyCode};
 
+use songbook::Instrument;
 use songbook::song_library::lib_functions::*;
 use super::App;
 
@@ -19,6 +20,23 @@ impl App {
         if let Some( (_, _) ) = &self.current_song {
         } else { return Ok(()) }
 
+        if self.setlist.is_some() {
+            match key_event.code {
+                KeyCode::Char('n') => return self.open_setlist_song(self.setlist_index + 1),
+                KeyCode::Char('p') => {
+                    if let Some(index) = self.setlist_index.checked_sub(1) { self.open_setlist_song(index)? }
+                    return Ok(())
+                },
+                KeyCode::Char('N') => {
+                    self.show_notes = !self.show_notes;
+                    return Ok(())
+                },
+                // Песни сет-листа не меняются, для этого их нужно открыть из библиотеки
+                KeyCode::Char('e') | KeyCode::Char('R') | KeyCode::Char('T') | KeyCode::Char('C') => return Ok(()),
+                _ => {}
+            }
+        }
+
         match key_event.code {
             KeyCode::Char('T') | KeyCode::Char('C') => {
                 self.is_long_command = true;
@@ -97,6 +115,12 @@ impl App {
                     self.show_fingerings = true
                 }
             },
+            KeyCode::Char('i') => {
+                let index = Instrument::ALL.iter()
+                    .position(|i| *i == self.instrument)
+                    .unwrap_or(0);
+                self.instrument = Instrument::ALL[(index + 1) % Instrument::ALL.len()];
+            },
             KeyCode::Char('n') => {
                 if self.show_notes {
                     self.show_notes = false
@@ -116,6 +140,12 @@ impl App {
                 if let Some( (song, _path) ) = &mut self.current_song {
                     ratatui::restore();
                     edit(song)?;
+                    let diagnostics = song.validate();
+                    if !diagnostics.is_empty() {
+                        self.warning = Some(
+                            diagnostics.iter().map(|d| d.to_string()).collect()
+                        );
+                    }
                     *is_song_changed = true;
                     *terminal = ratatui::init();
                     self.scroll_y = 0;
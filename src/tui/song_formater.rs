@@ -2,7 +2,7 @@ use ratatui::style::Style;
 use ratatui::text::{Line, Span};
 use ratatui::widgets::Paragraph;
 
-use songbook::{Song, STANDART_TUNING};
+use songbook::{Song, Instrument};
 use songbook::song::block;
 use songbook::chord_generator::chord_fingerings::sum_text_in_fingerings;
 
@@ -12,7 +12,7 @@ pub fn get_as_paragraph<'a>(
     available_width: usize,
     needs_chords: bool,
     needs_rhythm: bool,
-    needs_fingerings: bool,
+    needs_fingerings: Option<Instrument>,
     needs_notes: bool,
     colors: [ratatui::style::Color; 5]
 ) -> (Paragraph<'a>, usize, usize) {
@@ -20,6 +20,14 @@ pub fn get_as_paragraph<'a>(
     let mut columns = 0;
     let [title_color, chords_color, rhythm_color, notes_color, text_color] = colors;
 
+    // Альбом, авторы, метки - вместе с заметками
+    if needs_notes {
+        for detail in song.metadata.get_details() {
+            if detail.chars().count() > columns { columns = detail.chars().count() }
+            lines.push(Line::styled(detail, Style::new().fg(notes_color)));
+        }
+    }
+
     if let Some(text) = &song.notes && !text.is_empty() && needs_notes {
         lines.extend(text.lines()
             .map(|l| Line::styled(l, Style::new().fg(notes_color)))
@@ -30,18 +38,8 @@ pub fn get_as_paragraph<'a>(
         }
     }
 
-    if needs_chords && needs_fingerings {
-        let mut fings = Vec::new();
-        
-        for chord in &song.chord_list {
-            if let Ok(Some(f)) = songbook::song_library::get_fingering(&chord.text) {
-                fings.push(f)
-            } else {
-                fings.push( chord.get_fingerings(&STANDART_TUNING)[0].clone() )
-            }
-        }
-
-        
+    if needs_chords && let Some(instrument) = needs_fingerings {
+        let fings = song.get_fingerings_for_showing(instrument);
         if let Some(text) = sum_text_in_fingerings(&fings, Some(available_width)) {
             lines.extend( text.lines()
                 .map(|l| Line::from(l.to_string()))